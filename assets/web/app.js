@@ -0,0 +1,317 @@
+"use strict";
+
+// How many samples each graph keeps, matching what the server sends on connect
+const MAX_SAMPLES = 600;
+
+const LINE_COLOR = "rgb(20, 130, 46)";
+const FILL_COLOR = "rgba(20, 130, 46, 0.12)";
+const GRAPH_BG = "rgb(16, 10, 10)";
+
+const gpus = new Map();
+let selectedId = null;
+let sorting = { column: "memory", descending: true };
+
+const gpuSelect = document.getElementById("gpu");
+const statusLabel = document.getElementById("status");
+
+function formatBytes(bytes) {
+  if (bytes > 1024 * 1024 * 1024) {
+    return (bytes / 1024 / 1024 / 1024).toFixed(2) + "GiB";
+  } else if (bytes > 1024 * 1024) {
+    return (bytes / 1024 / 1024).toFixed(2) + "MiB";
+  }
+  return (bytes / 1024).toFixed(2) + "KiB";
+}
+
+function formatDuration(secs) {
+  if (secs < 60) return Math.floor(secs) + "s";
+  if (secs < 3600) return Math.floor(secs / 60) + "m";
+  return Math.floor(secs / 3600) + "h";
+}
+
+function pushSample(values, value) {
+  values.push(value);
+  if (values.length > MAX_SAMPLES) {
+    values.shift();
+  }
+}
+
+function processMemory(process) {
+  const memory = process.info.used_gpu_memory;
+  return memory && memory.Used !== undefined ? memory.Used : null;
+}
+
+function setStatus(text, className) {
+  statusLabel.textContent = text;
+  statusLabel.className = "status " + className;
+}
+
+function updateGpuSelect() {
+  const options = [...gpus.values()].map((gpu) => {
+    const prefix = gpu.alerting ? "⚠ " : "";
+    return [String(gpu.id), prefix + gpu.label];
+  });
+
+  const current = [...gpuSelect.options].map((option) => [option.value, option.textContent]);
+  if (JSON.stringify(options) === JSON.stringify(current)) {
+    return;
+  }
+
+  gpuSelect.replaceChildren(
+    ...options.map(([value, text]) => {
+      const option = document.createElement("option");
+      option.value = value;
+      option.textContent = text;
+      return option;
+    })
+  );
+  if (selectedId === null && options.length > 0) {
+    selectedId = Number(options[0][0]);
+  }
+  gpuSelect.value = String(selectedId);
+}
+
+function handleHistory(history) {
+  gpus.clear();
+  for (const gpu of history.gpus) {
+    gpus.set(gpu.id, {
+      id: gpu.id,
+      label: gpu.label,
+      alerting: false,
+      memoryTotal: gpu.memory_total,
+      utilization: gpu.utilization,
+      memory: gpu.memory_used,
+      temperature: gpu.temperature,
+      latest: null,
+    });
+  }
+  updateGpuSelect();
+  render();
+}
+
+function handleUpdate(update) {
+  const alerts = [];
+  for (const snapshot of update.gpus) {
+    let gpu = gpus.get(snapshot.id);
+    if (!gpu) {
+      gpu = { id: snapshot.id, utilization: [], memory: [], temperature: [] };
+      gpus.set(snapshot.id, gpu);
+    }
+    gpu.label = snapshot.label;
+    gpu.alerting = snapshot.alerts.length > 0;
+    gpu.memoryTotal = snapshot.memory_total;
+    gpu.latest = snapshot;
+    pushSample(gpu.utilization, snapshot.utilization);
+    pushSample(gpu.memory, snapshot.memory_used);
+    pushSample(gpu.temperature, snapshot.temperature);
+
+    for (const alert of snapshot.alerts) {
+      alerts.push(`⚠ ${snapshot.label} ${alert}`);
+    }
+  }
+
+  const alertsBox = document.getElementById("alerts");
+  alertsBox.hidden = alerts.length === 0;
+  alertsBox.replaceChildren(
+    ...alerts.map((text) => {
+      const line = document.createElement("div");
+      line.textContent = text;
+      return line;
+    })
+  );
+
+  updateGpuSelect();
+  render();
+}
+
+// Draws a graph the same way the native app does: newest value on the right, one pixel
+// per sample, with guide lines at every quarter of the maximum.
+function drawGraph(canvas, values, maxValue, format) {
+  const ratio = window.devicePixelRatio || 1;
+  const width = canvas.clientWidth;
+  const height = canvas.clientHeight;
+  if (canvas.width !== width * ratio || canvas.height !== height * ratio) {
+    canvas.width = width * ratio;
+    canvas.height = height * ratio;
+  }
+
+  const ctx = canvas.getContext("2d");
+  ctx.setTransform(ratio, 0, 0, ratio, 0, 0);
+  ctx.clearRect(0, 0, width, height);
+  ctx.fillStyle = GRAPH_BG;
+  ctx.beginPath();
+  ctx.roundRect(0, 0, width, height, 6);
+  ctx.fill();
+
+  const padding = 10.5;
+  const textWidth = 50;
+  const left = padding + textWidth;
+  const right = width - padding - textWidth;
+  const top = padding;
+  const bottom = height - padding;
+  const plotHeight = bottom - top;
+  const yFor = (value) => bottom - (value / maxValue) * plotHeight;
+
+  ctx.font = "10px system-ui, sans-serif";
+  ctx.textBaseline = "middle";
+  ctx.lineWidth = 1;
+  for (const fraction of [0, 0.25, 0.5, 0.75, 1]) {
+    const y = Math.round(bottom - fraction * plotHeight) - 0.5;
+    ctx.strokeStyle = FILL_COLOR;
+    ctx.beginPath();
+    ctx.moveTo(left, y);
+    ctx.lineTo(right, y);
+    ctx.stroke();
+
+    ctx.fillStyle = "white";
+    ctx.textAlign = "right";
+    ctx.fillText(format(fraction * maxValue), left - 5, y);
+  }
+
+  const latest = values[values.length - 1];
+  if (latest !== null && latest !== undefined) {
+    ctx.textAlign = "left";
+    ctx.fillText(format(latest), right + 5, yFor(latest));
+  }
+
+  ctx.strokeStyle = LINE_COLOR;
+  ctx.strokeRect(left, top, right - left, bottom - top);
+
+  ctx.save();
+  ctx.beginPath();
+  ctx.rect(left, top, right - left, bottom - top);
+  ctx.clip();
+
+  // Gaps in the data (null values) split the line into separate runs
+  const runs = [];
+  let run = [];
+  for (let i = 0; i < values.length && i <= right - left; i++) {
+    const value = values[values.length - 1 - i];
+    if (value === null || value === undefined) {
+      if (run.length > 0) runs.push(run);
+      run = [];
+      continue;
+    }
+    run.push([right - i, yFor(value)]);
+  }
+  if (run.length > 0) runs.push(run);
+
+  for (const points of runs) {
+    ctx.fillStyle = FILL_COLOR;
+    ctx.beginPath();
+    ctx.moveTo(points[0][0], bottom);
+    for (const [x, y] of points) ctx.lineTo(x, y);
+    ctx.lineTo(points[points.length - 1][0], bottom);
+    ctx.closePath();
+    ctx.fill();
+
+    ctx.strokeStyle = LINE_COLOR;
+    ctx.lineWidth = 2;
+    ctx.beginPath();
+    points.forEach(([x, y], index) => (index === 0 ? ctx.moveTo(x, y) : ctx.lineTo(x, y)));
+    if (points.length === 1) ctx.lineTo(points[0][0] - 1, points[0][1]);
+    ctx.stroke();
+  }
+  ctx.restore();
+}
+
+function sortValue(process, column) {
+  switch (column) {
+    case "pid":
+      return process.info.pid;
+    case "name":
+      return process.name.toLowerCase();
+    case "user":
+      return (process.user || "").toLowerCase();
+    case "kind":
+      return process.kind;
+    case "memory":
+      return processMemory(process) ?? -1;
+    case "usage":
+      return process.gpu_usage;
+  }
+}
+
+function renderProcesses(gpu) {
+  const body = document.querySelector("#processes tbody");
+  const error = document.getElementById("process-error");
+  const processes = gpu.latest ? gpu.latest.processes : { Ok: [] };
+
+  if (processes.Err !== undefined) {
+    error.hidden = false;
+    error.textContent = "Failed to fetch process list: " + processes.Err;
+    body.replaceChildren();
+    return;
+  }
+  error.hidden = true;
+
+  const rows = [...processes.Ok].sort((a, b) => {
+    const [x, y] = [sortValue(a, sorting.column), sortValue(b, sorting.column)];
+    const order = x < y ? -1 : x > y ? 1 : 0;
+    return sorting.descending ? -order : order;
+  });
+
+  body.replaceChildren(
+    ...rows.map((process) => {
+      const row = document.createElement("tr");
+      const memory = processMemory(process);
+      const cells = [
+        process.info.pid,
+        process.name,
+        process.user || "",
+        process.kind,
+        memory === null ? "Unavailable" : formatBytes(memory),
+        process.gpu_usage + "%",
+      ];
+      for (const text of cells) {
+        const cell = document.createElement("td");
+        cell.textContent = text;
+        row.appendChild(cell);
+      }
+
+      if (process.idle_for) {
+        const badge = document.createElement("span");
+        badge.className = "idle";
+        badge.textContent = "idle " + formatDuration(process.idle_for.secs);
+        badge.title = "Holding memory without using the GPU";
+        row.lastChild.appendChild(badge);
+      }
+      return row;
+    })
+  );
+}
+
+function render() {
+  const gpu = gpus.get(selectedId);
+  if (!gpu) return;
+
+  drawGraph(document.getElementById("usage"), gpu.utilization, 100, (v) => v.toFixed(0) + "%");
+  drawGraph(document.getElementById("memory"), gpu.memory, gpu.memoryTotal || 1, formatBytes);
+  drawGraph(document.getElementById("temperature"), gpu.temperature, 100, (v) => v.toFixed(0) + "°C");
+  renderProcesses(gpu);
+}
+
+gpuSelect.addEventListener("change", () => {
+  selectedId = Number(gpuSelect.value);
+  render();
+});
+
+for (const header of document.querySelectorAll("#processes th")) {
+  header.addEventListener("click", () => {
+    const column = header.dataset.column;
+    if (sorting.column === column) {
+      sorting.descending = !sorting.descending;
+    } else {
+      sorting = { column, descending: false };
+    }
+    render();
+  });
+}
+
+window.addEventListener("resize", render);
+
+const events = new EventSource("/events");
+events.addEventListener("open", () => setStatus("Live", "connected"));
+events.addEventListener("error", () => setStatus("Reconnecting…", "disconnected"));
+events.addEventListener("history", (event) => handleHistory(JSON.parse(event.data)));
+events.addEventListener("update", (event) => handleUpdate(JSON.parse(event.data)));
@@ -0,0 +1,100 @@
+//! Measures the CPU cost of looking up metadata for the processes using the GPU, comparing
+//! the shared `ProcessDataBank` against the previous approach of every process table
+//! scanning the whole system.
+//!
+//! Run with `cargo bench --bench process_bank`.
+
+#[allow(dead_code, unused_imports)]
+#[path = "../src/processes.rs"]
+mod processes;
+
+use std::time::{Duration, Instant};
+
+use nvml_wrapper::{enums::device::UsedGpuMemory, struct_wrappers::device::ProcessInfo};
+use sysinfo::{ProcessRefreshKind, RefreshKind, System, UpdateKind};
+
+use processes::{ProcessDataBank, ProcessKind};
+
+/// How many process lists the old approach polled per GPU (graphics, compute and all).
+const TABLES_PER_GPU: u32 = 3;
+const GPU_COUNT: u32 = 8;
+const GPU_PROCESS_COUNT: usize = 16;
+const ITERATIONS: u32 = 20;
+
+fn old_refresh_kind() -> ProcessRefreshKind {
+    ProcessRefreshKind::new()
+        .with_user(UpdateKind::OnlyIfNotSet)
+        .with_cmd(UpdateKind::OnlyIfNotSet)
+}
+
+fn time(iterations: u32, mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    start.elapsed() / iterations
+}
+
+/// Pretends a handful of the processes currently running are using the GPU.
+fn fake_gpu_processes() -> Vec<(ProcessInfo, ProcessKind)> {
+    let sys = System::new_with_specifics(RefreshKind::new().with_processes(old_refresh_kind()));
+    sys.processes()
+        .keys()
+        .take(GPU_PROCESS_COUNT)
+        .map(|pid| {
+            let info = ProcessInfo {
+                pid: pid.as_u32(),
+                used_gpu_memory: UsedGpuMemory::Used(512 * 1024 * 1024),
+                gpu_instance_id: None,
+                compute_instance_id: None,
+            };
+            (info, ProcessKind::Compute)
+        })
+        .collect()
+}
+
+fn main() {
+    let gpu_processes = fake_gpu_processes();
+    let scans = TABLES_PER_GPU * GPU_COUNT;
+
+    println!(
+        "{} GPUs, {} GPU processes, {} processes on the system",
+        GPU_COUNT,
+        gpu_processes.len(),
+        System::new_all().processes().len()
+    );
+
+    let old_startup = time(ITERATIONS, || {
+        for _ in 0..scans {
+            System::new_with_specifics(RefreshKind::new().with_processes(old_refresh_kind()));
+        }
+    });
+    let new_startup = time(ITERATIONS, || {
+        ProcessDataBank::new();
+    });
+    println!(
+        "startup:      old {:>10.3?}   shared bank {:>10.3?}",
+        old_startup, new_startup
+    );
+
+    let mut old_systems = (0..scans)
+        .map(|_| System::new_with_specifics(RefreshKind::new().with_processes(old_refresh_kind())))
+        .collect::<Vec<_>>();
+    let old_refresh = time(ITERATIONS, || {
+        for sys in old_systems.iter_mut() {
+            sys.refresh_all();
+        }
+    });
+
+    // A zero interval forces every lookup to hit the OS, which is the worst case
+    let mut bank = ProcessDataBank::with_refresh_interval(Duration::ZERO);
+    let new_refresh = time(ITERATIONS, || {
+        for _ in 0..GPU_COUNT {
+            bank.map_process_list(gpu_processes.clone(), Some(Vec::new()));
+        }
+    });
+    println!(
+        "refresh:      old {:>10.3?}   shared bank {:>10.3?}",
+        old_refresh, new_refresh
+    );
+}
@@ -0,0 +1,246 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use toml_edit::DocumentMut;
+
+use serde::Deserialize;
+
+use crate::{
+    data::{
+        alerts::{default_alert_rules, AlertRule},
+        levels::GraphConfig,
+    },
+    gui::{layout::GuiLayout, overlay::OverlayConfig},
+    hooks::HookConfig,
+    remote::client::RemoteConfig,
+    theme::{resolve_palette, CustomTheme, Palette},
+    tui::layout::TuiLayoutConfig,
+};
+
+/// User configuration, loaded from `gpustat/config.toml` in the platform config directory.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Alert rules evaluated against every GPU. Replaces the built-in rules if set.
+    pub alerts: Vec<AlertRule>,
+    /// Whether firing alerts are sent as desktop notifications.
+    pub desktop_notifications: bool,
+    /// How long a process has to hold memory without using the GPU before it's flagged
+    /// as idle.
+    pub idle_after_secs: u64,
+    /// Where alerts and process events are delivered, besides desktop notifications.
+    pub hooks: Vec<HookConfig>,
+    /// Agents on other machines whose GPUs are shown alongside the local ones.
+    pub remotes: Vec<RemoteConfig>,
+    /// `dark`, `light`, `high-contrast`, one of `themes`, or `system` to follow the desktop.
+    pub theme: String,
+    /// User palettes, by name.
+    pub themes: HashMap<String, CustomTheme>,
+    /// Whether the theme's green, amber and red are swapped for colours that people with
+    /// colour blindness can tell apart.
+    pub color_blind: bool,
+    pub graphs: GraphConfig,
+    /// The GUI's metric panels, which it saves back here when they're changed.
+    pub gui_layout: GuiLayout,
+    /// Layout presets for the TUI.
+    pub tui_layout: TuiLayoutConfig,
+    /// The always-on-top overlay shown by `gpustat gui --overlay`.
+    pub overlay: OverlayConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            alerts: default_alert_rules(),
+            desktop_notifications: true,
+            idle_after_secs: 300,
+            hooks: Vec::new(),
+            remotes: Vec::new(),
+            theme: "system".to_string(),
+            themes: HashMap::new(),
+            color_blind: false,
+            graphs: GraphConfig::default(),
+            gui_layout: GuiLayout::default(),
+            tui_layout: TuiLayoutConfig::default(),
+            overlay: OverlayConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// The config for one-off commands, which leave delivering alerts to whatever is
+    /// monitoring the GPUs for good rather than delivering them twice. Alerts are still
+    /// evaluated, so they can be shown, but no notifications, alert commands or hooks run.
+    pub fn without_alert_delivery(self) -> Self {
+        let alerts = self
+            .alerts
+            .into_iter()
+            .map(|rule| AlertRule {
+                command: None,
+                ..rule
+            })
+            .collect();
+        Self {
+            alerts,
+            desktop_notifications: false,
+            hooks: Vec::new(),
+            ..self
+        }
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("gpustat").join("config.toml"))
+    }
+
+    /// Loads the config file, falling back to the defaults if it doesn't exist.
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(format!("Failed to read {}: {}", path.display(), err)),
+        };
+
+        let config = toml::from_str::<Self>(&contents)
+            .map_err(|err| format!("Invalid {}: {}", path.display(), err))?;
+        resolve_palette(&config.theme, &config.themes, None)
+            .and_then(|_| config.gui_layout.validate())
+            .and_then(|_| config.tui_layout.validate())
+            .and_then(|_| config.overlay.validate())
+            .map_err(|err| format!("Invalid {}: {}", path.display(), err))?;
+        Ok(config)
+    }
+
+    /// The palette of the configured theme, where `prefers_dark` is the system's preference
+    /// for when the theme follows it.
+    pub fn palette(&self, prefers_dark: Option<bool>) -> Palette {
+        // The theme was checked when the config was loaded
+        let palette = resolve_palette(&self.theme, &self.themes, prefers_dark)
+            .unwrap_or_else(|_| Palette::dark());
+        if self.color_blind {
+            palette.color_blind_safe()
+        } else {
+            palette
+        }
+    }
+
+    /// Writes the GUI layout to the config file, keeping the rest of the file as it is.
+    pub fn save_gui_layout(layout: &GuiLayout) -> Result<(), String> {
+        let path = Self::path().ok_or("there is no config directory")?;
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(format!("Failed to read {}: {}", path.display(), err)),
+        };
+        let contents = with_gui_layout(&contents, layout)
+            .map_err(|err| format!("Invalid {}: {}", path.display(), err))?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|err| format!("Failed to create {}: {}", dir.display(), err))?;
+        }
+        fs::write(&path, contents)
+            .map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+    }
+}
+
+/// The config file's contents with its `[gui_layout]` section replaced by `layout`.
+fn with_gui_layout(contents: &str, layout: &GuiLayout) -> Result<String, String> {
+    let mut document = contents
+        .parse::<DocumentMut>()
+        .map_err(|err| err.to_string())?;
+
+    let section = toml::to_string(layout)
+        .map_err(|err| err.to_string())?
+        .parse::<DocumentMut>()
+        .map_err(|err| err.to_string())?;
+    document["gui_layout"] = toml_edit::Item::Table(section.as_table().clone());
+    Ok(document.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_off_commands_deliver_no_alerts() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [[alerts]]
+            name = "Hot"
+            when = "temperature_above"
+            celsius = 85
+            command = "page-someone"
+
+            [[hooks]]
+            type = "syslog"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.alerts[0].command.as_deref(), Some("page-someone"));
+        assert_eq!(config.hooks.len(), 1);
+
+        let config = config.without_alert_delivery();
+        assert!(!config.alerts.is_empty());
+        assert!(config.alerts.iter().all(|rule| rule.command.is_none()));
+        assert!(!config.desktop_notifications);
+        assert!(config.hooks.is_empty());
+    }
+
+    #[test]
+    fn a_saved_gui_layout_loads_back_the_same() {
+        use crate::gui::layout::{Arrangement, MetricPanel, PanelLayout};
+
+        let layout = GuiLayout {
+            arrangement: Arrangement::Grid,
+            columns: 3,
+            panels: vec![
+                PanelLayout {
+                    metric: MetricPanel::Temperature,
+                    height: 250.0,
+                },
+                PanelLayout {
+                    metric: MetricPanel::Usage,
+                    height: 80.0,
+                },
+            ],
+        };
+        let contents = with_gui_layout(
+            "# My settings\ntheme = \"light\"\n\n[gui_layout]\ncolumns = 1\n",
+            &layout,
+        )
+        .unwrap();
+        assert!(contents.starts_with("# My settings\n"));
+
+        let config = toml::from_str::<Config>(&contents).unwrap();
+        assert_eq!(config.gui_layout, layout);
+        assert_eq!(config.theme, "light");
+        assert!(config.gui_layout.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_gui_layouts() {
+        let layout = |section: &str| {
+            toml::from_str::<Config>(&format!("[gui_layout]\n{}", section))
+                .unwrap()
+                .gui_layout
+        };
+
+        assert!(layout("columns = 4").validate().is_ok());
+        assert!(layout("columns = 0").validate().is_err());
+        assert!(layout("columns = 5").validate().is_err());
+
+        let duplicated = layout(
+            r#"panels = [
+                { metric = "memory", height = 100.0 },
+                { metric = "usage", height = 100.0 },
+                { metric = "memory", height = 200.0 },
+            ]"#,
+        );
+        let err = duplicated.validate().unwrap_err();
+        assert!(err.contains("VRAM Usage"), "{}", err);
+    }
+}
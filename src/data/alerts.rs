@@ -0,0 +1,439 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+use super::sample::GpuSample;
+
+/// The condition an alert rule checks on each sample.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "when", rename_all = "snake_case")]
+pub enum AlertCondition {
+    TemperatureAbove {
+        celsius: f32,
+    },
+    MemoryAbove {
+        percent: f32,
+    },
+    UtilizationAbove {
+        percent: f32,
+    },
+    /// The GPU is idle while processes are holding memory on it, e.g. a stuck job.
+    StuckProcess,
+    DeviceLost,
+    /// The ECC error or retired page counts went up. Each increase happens at a single
+    /// sample, so the alert is held for a while afterwards rather than resolving at the next.
+    EccErrors {
+        #[serde(default = "default_ecc_hold_secs")]
+        hold_secs: u64,
+    },
+}
+
+fn default_ecc_hold_secs() -> u64 {
+    300
+}
+
+impl AlertCondition {
+    fn is_met(&self, sample: &GpuSample) -> bool {
+        match self {
+            AlertCondition::TemperatureAbove { celsius } => {
+                sample.temperature.is_some_and(|t| t > *celsius)
+            }
+            AlertCondition::MemoryAbove { percent } => {
+                sample.memory_percent().is_some_and(|m| m > *percent)
+            }
+            AlertCondition::UtilizationAbove { percent } => {
+                sample.utilization.is_some_and(|u| u > *percent)
+            }
+            AlertCondition::StuckProcess => {
+                sample.utilization == Some(0.0) && sample.process_memory > 0
+            }
+            AlertCondition::DeviceLost => sample.lost,
+            AlertCondition::EccErrors { hold_secs } => {
+                sample.ecc_increase.as_ref().is_some_and(|increase| {
+                    sample.time.saturating_duration_since(increase.time)
+                        < Duration::from_secs(*hold_secs)
+                })
+            }
+        }
+    }
+
+    fn describe(&self, sample: &GpuSample) -> String {
+        match self {
+            AlertCondition::TemperatureAbove { celsius } => format!(
+                "temperature {:.0}°C is above {:.0}°C",
+                sample.temperature.unwrap_or(0.0),
+                celsius
+            ),
+            AlertCondition::MemoryAbove { percent } => format!(
+                "VRAM usage {:.0}% is above {:.0}%",
+                sample.memory_percent().unwrap_or(0.0),
+                percent
+            ),
+            AlertCondition::UtilizationAbove { percent } => format!(
+                "utilization {:.0}% is above {:.0}%",
+                sample.utilization.unwrap_or(0.0),
+                percent
+            ),
+            AlertCondition::StuckProcess => {
+                "processes are holding memory while the GPU is idle".to_string()
+            }
+            AlertCondition::DeviceLost => "the device stopped responding".to_string(),
+            AlertCondition::EccErrors { .. } => match &sample.ecc_increase {
+                Some(increase) => increase.description.clone(),
+                None => "memory errors went up".to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AlertRule {
+    pub name: String,
+    #[serde(flatten)]
+    pub condition: AlertCondition,
+    /// How long the condition has to hold before the alert fires.
+    #[serde(default)]
+    pub for_secs: u64,
+    /// A shell command to run when the alert fires or resolves.
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+pub fn default_alert_rules() -> Vec<AlertRule> {
+    vec![
+        AlertRule {
+            name: "High temperature".to_string(),
+            condition: AlertCondition::TemperatureAbove { celsius: 85.0 },
+            for_secs: 30,
+            command: None,
+        },
+        AlertRule {
+            name: "VRAM almost full".to_string(),
+            condition: AlertCondition::MemoryAbove { percent: 95.0 },
+            for_secs: 0,
+            command: None,
+        },
+        AlertRule {
+            name: "Stuck job".to_string(),
+            condition: AlertCondition::StuckProcess,
+            for_secs: 300,
+            command: None,
+        },
+        AlertRule {
+            name: "Device lost".to_string(),
+            condition: AlertCondition::DeviceLost,
+            for_secs: 0,
+            command: None,
+        },
+        AlertRule {
+            name: "ECC errors".to_string(),
+            condition: AlertCondition::EccErrors {
+                hold_secs: default_ecc_hold_secs(),
+            },
+            for_secs: 0,
+            command: None,
+        },
+    ]
+}
+
+#[derive(Clone, Debug)]
+pub struct Alert {
+    pub rule: String,
+    pub message: String,
+    pub device_index: u32,
+    pub device_uuid: String,
+    pub device_name: String,
+    pub command: Option<String>,
+    pub since: Instant,
+}
+
+#[derive(Clone, Debug)]
+pub enum AlertTransition {
+    Fired(Alert),
+    Resolved(Alert),
+}
+
+#[derive(Default)]
+struct RuleState {
+    met_since: Option<Instant>,
+    firing: Option<Alert>,
+}
+
+/// Evaluates alert rules against a stream of samples, keeping track of which are firing.
+///
+/// The engine only looks at the samples it's given, including their timestamps, so it
+/// doesn't depend on any real device.
+pub struct AlertEngine {
+    rules: Vec<AlertRule>,
+    /// The state of each rule, keyed by device uuid and rule index.
+    states: HashMap<(String, usize), RuleState>,
+}
+
+impl AlertEngine {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        Self {
+            rules,
+            states: HashMap::new(),
+        }
+    }
+
+    /// Evaluates every rule against the sample, returning the alerts that started or
+    /// stopped firing because of it.
+    pub fn evaluate(&mut self, sample: &GpuSample) -> Vec<AlertTransition> {
+        let mut transitions = Vec::new();
+
+        for (index, rule) in self.rules.iter().enumerate() {
+            let state = self
+                .states
+                .entry((sample.device_uuid.clone(), index))
+                .or_default();
+
+            if !rule.condition.is_met(sample) {
+                state.met_since = None;
+                if let Some(alert) = state.firing.take() {
+                    transitions.push(AlertTransition::Resolved(alert));
+                }
+                continue;
+            }
+
+            let met_since = *state.met_since.get_or_insert(sample.time);
+            let held_for = sample.time.saturating_duration_since(met_since);
+
+            if state.firing.is_none() && held_for >= Duration::from_secs(rule.for_secs) {
+                let alert = Alert {
+                    rule: rule.name.clone(),
+                    message: rule.condition.describe(sample),
+                    device_index: sample.device_index,
+                    device_uuid: sample.device_uuid.clone(),
+                    device_name: sample.device_name.clone(),
+                    command: rule.command.clone(),
+                    since: sample.time,
+                };
+                state.firing = Some(alert.clone());
+                transitions.push(AlertTransition::Fired(alert));
+            }
+        }
+
+        transitions
+    }
+
+    /// Resolves every alert firing for the device, e.g. once its readings have stopped
+    /// arriving. Its rules start over from the next sample.
+    pub fn resolve_device(&mut self, device_uuid: &str) -> Vec<AlertTransition> {
+        self.states
+            .iter_mut()
+            .filter(|((uuid, _), _)| uuid == device_uuid)
+            .filter_map(|(_, state)| {
+                state.met_since = None;
+                state.firing.take().map(AlertTransition::Resolved)
+            })
+            .collect()
+    }
+
+    /// All alerts currently firing, across every device.
+    pub fn firing(&self) -> impl Iterator<Item = &Alert> {
+        self.states
+            .values()
+            .filter_map(|state| state.firing.as_ref())
+    }
+
+    pub fn is_firing(&self, device_uuid: &str) -> bool {
+        self.firing().any(|alert| alert.device_uuid == device_uuid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(device: u32, start: Instant, secs: u64) -> GpuSample {
+        GpuSample {
+            time: start + Duration::from_secs(secs),
+            device_index: device,
+            device_uuid: format!("GPU-{}", device),
+            device_name: format!("GPU {}", device),
+            utilization: Some(50.0),
+            memory_used: Some(1000),
+            memory_total: 10_000,
+            temperature: Some(60.0),
+            process_memory: 0,
+            lost: false,
+            throttle_reasons: None,
+            ecc: None,
+            ecc_increase: None,
+        }
+    }
+
+    fn hot(device: u32, start: Instant, secs: u64) -> GpuSample {
+        GpuSample {
+            temperature: Some(90.0),
+            ..sample(device, start, secs)
+        }
+    }
+
+    fn fired(transitions: &[AlertTransition]) -> Vec<&str> {
+        transitions
+            .iter()
+            .filter_map(|transition| match transition {
+                AlertTransition::Fired(alert) => Some(alert.rule.as_str()),
+                AlertTransition::Resolved(_) => None,
+            })
+            .collect()
+    }
+
+    fn resolved(transitions: &[AlertTransition]) -> Vec<&str> {
+        transitions
+            .iter()
+            .filter_map(|transition| match transition {
+                AlertTransition::Resolved(alert) => Some(alert.rule.as_str()),
+                AlertTransition::Fired(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fires_only_after_the_condition_holds_for_long_enough() {
+        let mut engine = AlertEngine::new(default_alert_rules());
+        let start = Instant::now();
+
+        for secs in [0, 10, 20, 29] {
+            let transitions = engine.evaluate(&hot(0, start, secs));
+            assert!(transitions.is_empty(), "fired after {}s", secs);
+        }
+        let transitions = engine.evaluate(&hot(0, start, 30));
+        assert_eq!(fired(&transitions), ["High temperature"]);
+        assert!(engine.is_firing("GPU-0"));
+
+        // Already firing, so it doesn't fire again
+        assert!(engine.evaluate(&hot(0, start, 40)).is_empty());
+    }
+
+    #[test]
+    fn dropping_below_the_threshold_resets_the_hold() {
+        let mut engine = AlertEngine::new(default_alert_rules());
+        let start = Instant::now();
+
+        engine.evaluate(&hot(0, start, 0));
+        engine.evaluate(&sample(0, start, 20));
+        assert!(engine.evaluate(&hot(0, start, 30)).is_empty());
+        assert_eq!(
+            fired(&engine.evaluate(&hot(0, start, 60))),
+            ["High temperature"]
+        );
+    }
+
+    #[test]
+    fn resolves_when_the_value_drops_back() {
+        let mut engine = AlertEngine::new(default_alert_rules());
+        let start = Instant::now();
+
+        engine.evaluate(&hot(0, start, 0));
+        engine.evaluate(&hot(0, start, 30));
+        assert!(engine.is_firing("GPU-0"));
+
+        let transitions = engine.evaluate(&sample(0, start, 31));
+        assert_eq!(resolved(&transitions), ["High temperature"]);
+        assert!(!engine.is_firing("GPU-0"));
+    }
+
+    #[test]
+    fn keeps_separate_state_for_each_device() {
+        let mut engine = AlertEngine::new(default_alert_rules());
+        let start = Instant::now();
+
+        engine.evaluate(&hot(0, start, 0));
+        engine.evaluate(&hot(1, start, 20));
+        assert_eq!(
+            fired(&engine.evaluate(&hot(0, start, 30))),
+            ["High temperature"]
+        );
+        assert!(engine.evaluate(&hot(1, start, 30)).is_empty());
+        assert!(engine.is_firing("GPU-0"));
+        assert!(!engine.is_firing("GPU-1"));
+
+        // GPU 1 cooling down doesn't resolve GPU 0's alert
+        engine.evaluate(&sample(1, start, 35));
+        assert!(engine.is_firing("GPU-0"));
+        assert_eq!(engine.firing().count(), 1);
+    }
+
+    #[test]
+    fn fires_for_idle_gpus_with_processes_holding_memory() {
+        let mut engine = AlertEngine::new(default_alert_rules());
+        let start = Instant::now();
+        let stuck = |secs| GpuSample {
+            utilization: Some(0.0),
+            process_memory: 500,
+            ..sample(0, start, secs)
+        };
+
+        assert!(engine.evaluate(&stuck(0)).is_empty());
+        assert!(engine.evaluate(&stuck(299)).is_empty());
+        assert_eq!(fired(&engine.evaluate(&stuck(300))), ["Stuck job"]);
+
+        // Any utilization means it's not stuck
+        let busy = GpuSample {
+            utilization: Some(1.0),
+            process_memory: 500,
+            ..sample(0, start, 301)
+        };
+        assert_eq!(resolved(&engine.evaluate(&busy)), ["Stuck job"]);
+
+        // Nor is an idle GPU with nothing on it
+        let idle = |secs| GpuSample {
+            utilization: Some(0.0),
+            ..sample(0, start, secs)
+        };
+        assert!(engine.evaluate(&idle(302)).is_empty());
+        assert!(engine.evaluate(&idle(1000)).is_empty());
+    }
+
+    #[test]
+    fn fires_immediately_when_the_device_is_lost() {
+        let mut engine = AlertEngine::new(default_alert_rules());
+        let start = Instant::now();
+        let lost = GpuSample {
+            lost: true,
+            utilization: None,
+            temperature: None,
+            ..sample(0, start, 0)
+        };
+
+        let transitions = engine.evaluate(&lost);
+        assert_eq!(fired(&transitions), ["Device lost"]);
+        assert_eq!(transitions.len(), 1);
+
+        let transitions = engine.evaluate(&sample(0, start, 1));
+        assert_eq!(resolved(&transitions), ["Device lost"]);
+    }
+
+    #[test]
+    fn resolving_a_device_only_resolves_its_alerts() {
+        let mut engine = AlertEngine::new(default_alert_rules());
+        let start = Instant::now();
+
+        for secs in [0, 30] {
+            engine.evaluate(&hot(0, start, secs));
+            engine.evaluate(&hot(1, start, secs));
+        }
+        assert!(engine.is_firing("GPU-0") && engine.is_firing("GPU-1"));
+
+        assert_eq!(
+            resolved(&engine.resolve_device("GPU-0")),
+            ["High temperature"]
+        );
+        assert!(!engine.is_firing("GPU-0"));
+        assert!(engine.is_firing("GPU-1"));
+        assert!(engine.resolve_device("GPU-0").is_empty());
+
+        // The rule has to be held again before it fires again
+        assert!(fired(&engine.evaluate(&hot(0, start, 31))).is_empty());
+        assert_eq!(
+            fired(&engine.evaluate(&hot(0, start, 61))),
+            ["High temperature"]
+        );
+    }
+}
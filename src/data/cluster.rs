@@ -0,0 +1,328 @@
+use std::{cmp::Ordering, collections::HashMap, time::Duration};
+
+use nvml_wrapper::enums::device::UsedGpuMemory;
+
+use crate::{processes::ProcessData, remote::client::ConnectionStatus};
+
+use super::{
+    process_table::{SortingDirection, TableSorting},
+    GpuDeviceMonitor,
+};
+
+/// How long a GPU or host can go without new readings before they're shown as stale.
+pub const STALE_AFTER: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClusterColumn {
+    Host,
+    Gpu,
+    Utilization,
+    Memory,
+    Temperature,
+    TopUser,
+}
+
+impl ClusterColumn {
+    pub const ALL: [ClusterColumn; 6] = [
+        ClusterColumn::Host,
+        ClusterColumn::Gpu,
+        ClusterColumn::Utilization,
+        ClusterColumn::Memory,
+        ClusterColumn::Temperature,
+        ClusterColumn::TopUser,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ClusterColumn::Host => "Host",
+            ClusterColumn::Gpu => "GPU",
+            ClusterColumn::Utilization => "Util",
+            ClusterColumn::Memory => "VRAM",
+            ClusterColumn::Temperature => "Temp",
+            ClusterColumn::TopUser => "Top User",
+        }
+    }
+}
+
+/// One GPU's line in the cluster table.
+#[derive(Clone, Debug)]
+pub struct ClusterRow {
+    /// The GPU's index in [`super::GpuMonitoringData::gpus`].
+    pub gpu: usize,
+    pub host: String,
+    pub device_index: u32,
+    pub device_name: String,
+    pub utilization: Option<f32>,
+    pub memory_used: Option<u64>,
+    pub memory_total: u64,
+    pub temperature: Option<f32>,
+    /// The user holding the most memory on the GPU, and how much they're holding.
+    pub top_user: Option<(String, u64)>,
+    /// How long it's been since the last reading, if it's been long enough to be stale.
+    pub stale_for: Option<Duration>,
+    pub lost: bool,
+}
+
+impl ClusterRow {
+    pub fn new(gpu: usize, monitor: &GpuDeviceMonitor, local_host: &str) -> Self {
+        let sample = monitor.last_sample();
+
+        Self {
+            gpu,
+            host: monitor.host().unwrap_or(local_host).to_string(),
+            device_index: monitor.device_index(),
+            device_name: monitor.device_name().to_string(),
+            utilization: sample.and_then(|sample| sample.utilization),
+            memory_used: sample.and_then(|sample| sample.memory_used),
+            memory_total: monitor.max_memory(),
+            temperature: sample.and_then(|sample| sample.temperature),
+            top_user: users_by_memory(monitor).into_iter().next(),
+            stale_for: sample
+                .map(|sample| sample.time.elapsed())
+                .filter(|age| *age >= STALE_AFTER),
+            lost: sample.is_some_and(|sample| sample.lost),
+        }
+    }
+
+    pub fn memory_percent(&self) -> Option<f32> {
+        let used = self.memory_used?;
+        if self.memory_total == 0 {
+            return None;
+        }
+        Some(used as f32 / self.memory_total as f32 * 100.0)
+    }
+}
+
+/// How much memory each user's processes are holding on a GPU, most first.
+pub fn users_by_memory(monitor: &GpuDeviceMonitor) -> Vec<(String, u64)> {
+    match monitor.processes().all_processes() {
+        Ok(processes) => sum_memory_by_user(processes),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn sum_memory_by_user(processes: &[ProcessData]) -> Vec<(String, u64)> {
+    let mut memory_by_user = HashMap::<&str, u64>::new();
+    for process in processes {
+        let memory = match process.info.used_gpu_memory {
+            UsedGpuMemory::Used(bytes) => bytes,
+            UsedGpuMemory::Unavailable => 0,
+        };
+        let user = process.user.as_deref().unwrap_or("unknown");
+        *memory_by_user.entry(user).or_default() += memory;
+    }
+
+    let mut users = memory_by_user
+        .into_iter()
+        .map(|(user, memory)| (user.to_string(), memory))
+        .collect::<Vec<_>>();
+    users.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    users
+}
+
+fn compare_options<T: PartialOrd>(a: Option<T>, b: Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// The sorting and selection of the cluster table.
+pub struct ClusterTableData {
+    sorting: TableSorting<ClusterColumn>,
+    /// The GPU index of the selected row.
+    selected_gpu: Option<usize>,
+}
+
+impl ClusterTableData {
+    pub fn new() -> Self {
+        Self {
+            sorting: TableSorting {
+                column: ClusterColumn::Host,
+                direction: SortingDirection::Ascending,
+            },
+            selected_gpu: None,
+        }
+    }
+
+    /// Sorts the rows by the current column, falling back to host and GPU order.
+    pub fn sort(&self, rows: &mut [ClusterRow]) {
+        rows.sort_by(|a, b| {
+            let ordering = match self.sorting.column {
+                ClusterColumn::Host => Ordering::Equal,
+                ClusterColumn::Gpu => a.device_index.cmp(&b.device_index),
+                ClusterColumn::Utilization => compare_options(a.utilization, b.utilization),
+                ClusterColumn::Memory => compare_options(a.memory_percent(), b.memory_percent()),
+                ClusterColumn::Temperature => compare_options(a.temperature, b.temperature),
+                ClusterColumn::TopUser => compare_options(
+                    a.top_user.as_ref().map(|(user, _)| user),
+                    b.top_user.as_ref().map(|(user, _)| user),
+                ),
+            }
+            .then_with(|| a.host.cmp(&b.host))
+            .then_with(|| a.device_index.cmp(&b.device_index));
+
+            match self.sorting.direction {
+                SortingDirection::Ascending => ordering,
+                SortingDirection::Descending => ordering.reverse(),
+            }
+        });
+    }
+
+    pub fn sorting(&self) -> &TableSorting<ClusterColumn> {
+        &self.sorting
+    }
+
+    pub fn sorting_mut(&mut self) -> &mut TableSorting<ClusterColumn> {
+        &mut self.sorting
+    }
+
+    pub fn selected_gpu(&self) -> Option<usize> {
+        self.selected_gpu
+    }
+
+    pub fn set_selected_gpu(&mut self, gpu: Option<usize>) {
+        self.selected_gpu = gpu;
+    }
+}
+
+/// How a host's connection is doing.
+#[derive(Clone, Debug)]
+pub struct HostHealth {
+    pub name: String,
+    /// The agent's address, for remote hosts.
+    pub address: Option<String>,
+    pub status: ConnectionStatus,
+    /// How long ago readings last arrived from the host.
+    pub last_update: Option<Duration>,
+    pub gpu_count: usize,
+}
+
+impl HostHealth {
+    /// Whether the host is connected but hasn't sent anything for a while.
+    pub fn is_stale(&self) -> bool {
+        self.status == ConnectionStatus::Connected
+            && self.last_update.is_some_and(|age| age >= STALE_AFTER)
+    }
+
+    /// A short description of the connection, e.g. "connected" or the reason it dropped.
+    pub fn describe(&self) -> String {
+        match &self.status {
+            ConnectionStatus::Connecting => "connecting".to_string(),
+            ConnectionStatus::Connected if self.is_stale() => "not responding".to_string(),
+            ConnectionStatus::Connected => "connected".to_string(),
+            ConnectionStatus::Disconnected(err) => err.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nvml_wrapper::struct_wrappers::device::ProcessInfo;
+
+    use crate::processes::ProcessKind;
+
+    use super::*;
+
+    fn process(user: Option<&str>, memory: UsedGpuMemory) -> ProcessData {
+        ProcessData {
+            info: ProcessInfo {
+                pid: 1,
+                used_gpu_memory: memory,
+                gpu_instance_id: None,
+                compute_instance_id: None,
+            },
+            name: "python".to_string(),
+            command: String::new(),
+            start_time: None,
+            user: user.map(str::to_string),
+            is_current_user: false,
+            kind: ProcessKind::Compute,
+            gpu_usage: None,
+            idle_for: None,
+        }
+    }
+
+    #[test]
+    fn sums_each_users_memory_most_first() {
+        let processes = [
+            process(Some("bob"), UsedGpuMemory::Used(300)),
+            process(Some("alice"), UsedGpuMemory::Used(100)),
+            process(None, UsedGpuMemory::Used(50)),
+            process(Some("alice"), UsedGpuMemory::Used(250)),
+            process(Some("carol"), UsedGpuMemory::Used(300)),
+            process(Some("dave"), UsedGpuMemory::Unavailable),
+        ];
+        assert_eq!(
+            sum_memory_by_user(&processes),
+            [
+                ("alice".to_string(), 350),
+                // Ties are in name order
+                ("bob".to_string(), 300),
+                ("carol".to_string(), 300),
+                ("unknown".to_string(), 50),
+                ("dave".to_string(), 0),
+            ]
+        );
+    }
+
+    fn row(host: &str, device_index: u32, utilization: Option<f32>) -> ClusterRow {
+        ClusterRow {
+            gpu: 0,
+            host: host.to_string(),
+            device_index,
+            device_name: "Test GPU".to_string(),
+            utilization,
+            memory_used: None,
+            memory_total: 0,
+            temperature: None,
+            top_user: None,
+            stale_for: None,
+            lost: false,
+        }
+    }
+
+    fn sorted(table: &ClusterTableData, rows: &mut [ClusterRow]) -> Vec<(String, u32)> {
+        table.sort(rows);
+        rows.iter()
+            .map(|row| (row.host.clone(), row.device_index))
+            .collect()
+    }
+
+    #[test]
+    fn sorts_by_column_then_host_and_gpu() {
+        let mut rows = [
+            row("b", 1, Some(50.0)),
+            row("a", 1, None),
+            row("b", 0, Some(90.0)),
+            row("a", 0, Some(50.0)),
+        ];
+        let mut table = ClusterTableData::new();
+        let order = |pairs: &[(&str, u32)]| {
+            pairs
+                .iter()
+                .map(|(host, index)| (host.to_string(), *index))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            sorted(&table, &mut rows),
+            order(&[("a", 0), ("a", 1), ("b", 0), ("b", 1)])
+        );
+
+        // Unknown values sort below every known one
+        table.sorting_mut().column = ClusterColumn::Utilization;
+        assert_eq!(
+            sorted(&table, &mut rows),
+            order(&[("a", 1), ("a", 0), ("b", 1), ("b", 0)])
+        );
+
+        table.sorting_mut().direction = SortingDirection::Descending;
+        assert_eq!(
+            sorted(&table, &mut rows),
+            order(&[("b", 0), ("b", 1), ("a", 0), ("a", 1)])
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Static information about a GPU, such as its driver, firmware and limits, for the device
+//! info view and for pasting into bug reports.
+
+use std::sync::OnceLock;
+
+use nvml_wrapper::{
+    cuda_driver_version_major, cuda_driver_version_minor,
+    enum_wrappers::device::{ComputeMode, TemperatureThreshold},
+    Device,
+};
+use nvml_wrapper_sys::bindings::{nvmlReturn_enum_NVML_SUCCESS, NvmlLib, NVML_DEVICE_MIG_ENABLE};
+use serde::{Deserialize, Serialize};
+
+/// A setting that can have a different value pending until the GPU is reset.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ModeState {
+    pub enabled: bool,
+    pub pending: bool,
+}
+
+fn enabled_text(enabled: bool) -> &'static str {
+    if enabled {
+        "Enabled"
+    } else {
+        "Disabled"
+    }
+}
+
+impl ModeState {
+    fn describe(&self) -> String {
+        if self.enabled == self.pending {
+            enabled_text(self.enabled).to_string()
+        } else {
+            format!(
+                "{} ({} after reset)",
+                enabled_text(self.enabled),
+                enabled_text(self.pending).to_lowercase()
+            )
+        }
+    }
+}
+
+/// Everything NVML reports about a GPU that doesn't change while it's running. Each field
+/// is `None` where the GPU or driver doesn't support reading it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct DeviceInfo {
+    pub driver_version: Option<String>,
+    /// The newest CUDA version the driver supports, e.g. "12.2".
+    pub cuda_version: Option<String>,
+    pub vbios_version: Option<String>,
+    pub pci_bus_id: Option<String>,
+    pub serial: Option<String>,
+    pub architecture: Option<String>,
+    /// e.g. "8.6".
+    pub compute_capability: Option<String>,
+    /// In bits.
+    pub memory_bus_width: Option<u32>,
+    /// Power limits, in milliwatts.
+    pub power_limit_default: Option<u32>,
+    pub power_limit_min: Option<u32>,
+    pub power_limit_max: Option<u32>,
+    pub power_limit_enforced: Option<u32>,
+    /// Temperatures, in °C, at which the GPU slows down and shuts down.
+    pub temperature_slowdown: Option<u32>,
+    pub temperature_shutdown: Option<u32>,
+    pub temperature_gpu_max: Option<u32>,
+    pub temperature_memory_max: Option<u32>,
+    pub persistence_mode: Option<bool>,
+    pub compute_mode: Option<String>,
+    pub ecc_mode: Option<ModeState>,
+    pub mig_mode: Option<ModeState>,
+}
+
+impl DeviceInfo {
+    pub fn read(device: &Device) -> Self {
+        let nvml = device.nvml();
+        let cuda_version = nvml.sys_cuda_driver_version().ok().map(|version| {
+            format!(
+                "{}.{}",
+                cuda_driver_version_major(version),
+                cuda_driver_version_minor(version)
+            )
+        });
+        let power_limits = device.power_management_limit_constraints().ok();
+        let threshold = |threshold| device.temperature_threshold(threshold).ok();
+
+        Self {
+            driver_version: nvml.sys_driver_version().ok(),
+            cuda_version,
+            vbios_version: device.vbios_version().ok(),
+            pci_bus_id: device.pci_info().ok().map(|pci| pci.bus_id),
+            serial: device.serial().ok(),
+            architecture: device.architecture().ok().map(|arch| arch.to_string()),
+            compute_capability: device
+                .cuda_compute_capability()
+                .ok()
+                .map(|capability| format!("{}.{}", capability.major, capability.minor)),
+            memory_bus_width: device.memory_bus_width().ok(),
+            power_limit_default: device.power_management_limit_default().ok(),
+            power_limit_min: power_limits.as_ref().map(|limits| limits.min_limit),
+            power_limit_max: power_limits.as_ref().map(|limits| limits.max_limit),
+            power_limit_enforced: device.enforced_power_limit().ok(),
+            temperature_slowdown: threshold(TemperatureThreshold::Slowdown),
+            temperature_shutdown: threshold(TemperatureThreshold::Shutdown),
+            temperature_gpu_max: threshold(TemperatureThreshold::GpuMax),
+            temperature_memory_max: threshold(TemperatureThreshold::MemoryMax),
+            persistence_mode: device.is_in_persistent_mode().ok(),
+            compute_mode: device.compute_mode().ok().map(|mode| {
+                match mode {
+                    ComputeMode::Default => "Default",
+                    ComputeMode::ExclusiveThread => "Exclusive thread",
+                    ComputeMode::Prohibited => "Prohibited",
+                    ComputeMode::ExclusiveProcess => "Exclusive process",
+                }
+                .to_string()
+            }),
+            ecc_mode: device.is_ecc_enabled().ok().map(|state| ModeState {
+                enabled: state.currently_enabled,
+                pending: state.pending_enabled,
+            }),
+            mig_mode: read_mig_mode(device),
+        }
+    }
+
+    /// Each field as a label and its value, in the order they're shown.
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        let text = |value: &Option<String>| value.clone().unwrap_or_else(|| "N/A".to_string());
+        let watts = |milliwatts: Option<u32>| {
+            milliwatts
+                .map(|milliwatts| format!("{:.0} W", milliwatts as f32 / 1000.0))
+                .unwrap_or_else(|| "N/A".to_string())
+        };
+        let celsius = |celsius: Option<u32>| {
+            celsius
+                .map(|celsius| format!("{}°C", celsius))
+                .unwrap_or_else(|| "N/A".to_string())
+        };
+        let mode = |mode: Option<ModeState>| {
+            mode.map(|mode| mode.describe())
+                .unwrap_or_else(|| "N/A".to_string())
+        };
+
+        vec![
+            ("Driver version", text(&self.driver_version)),
+            ("CUDA version", text(&self.cuda_version)),
+            ("VBIOS version", text(&self.vbios_version)),
+            ("PCI bus ID", text(&self.pci_bus_id)),
+            ("Serial", text(&self.serial)),
+            ("Architecture", text(&self.architecture)),
+            ("Compute capability", text(&self.compute_capability)),
+            (
+                "Memory bus width",
+                self.memory_bus_width
+                    .map(|bits| format!("{} bit", bits))
+                    .unwrap_or_else(|| "N/A".to_string()),
+            ),
+            ("Default power limit", watts(self.power_limit_default)),
+            ("Min power limit", watts(self.power_limit_min)),
+            ("Max power limit", watts(self.power_limit_max)),
+            ("Enforced power limit", watts(self.power_limit_enforced)),
+            ("Slowdown temperature", celsius(self.temperature_slowdown)),
+            ("Shutdown temperature", celsius(self.temperature_shutdown)),
+            ("Max GPU temperature", celsius(self.temperature_gpu_max)),
+            (
+                "Max memory temperature",
+                celsius(self.temperature_memory_max),
+            ),
+            (
+                "Persistence mode",
+                self.persistence_mode
+                    .map(|enabled| enabled_text(enabled).to_string())
+                    .unwrap_or_else(|| "N/A".to_string()),
+            ),
+            ("Compute mode", text(&self.compute_mode)),
+            ("ECC mode", mode(self.ecc_mode)),
+            ("MIG mode", mode(self.mig_mode)),
+        ]
+    }
+}
+
+/// Rows of info as plain text under a heading, for copying into a bug report.
+pub fn report(heading: &str, rows: &[(&'static str, String)]) -> String {
+    let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+
+    let mut report = format!("{}\n", heading);
+    for (label, value) in rows {
+        report.push_str(&format!("{:width$}  {}\n", label, value, width = width));
+    }
+    report
+}
+
+/// nvml-wrapper doesn't wrap MIG mode, so it's read through the raw bindings. The library is
+/// already loaded, so opening it again only gets another handle to it.
+fn read_mig_mode(device: &Device) -> Option<ModeState> {
+    static LIB: OnceLock<Option<NvmlLib>> = OnceLock::new();
+
+    let lib_path = if cfg!(windows) {
+        "nvml.dll"
+    } else {
+        "libnvidia-ml.so"
+    };
+    let lib = LIB
+        .get_or_init(|| unsafe { NvmlLib::new(lib_path).ok() })
+        .as_ref()?;
+    // Drivers from before MIG don't have the function at all
+    let get_mig_mode = lib.nvmlDeviceGetMigMode.as_ref().ok()?;
+
+    let (mut current, mut pending) = (0, 0);
+    let result = unsafe { get_mig_mode(device.handle(), &mut current, &mut pending) };
+    (result == nvmlReturn_enum_NVML_SUCCESS).then_some(ModeState {
+        enabled: current == NVML_DEVICE_MIG_ENABLE,
+        pending: pending == NVML_DEVICE_MIG_ENABLE,
+    })
+}
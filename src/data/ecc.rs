@@ -0,0 +1,308 @@
+//! ECC error counts and the memory retired or remapped because of them, sampled alongside a
+//! GPU's other readings so that new errors can be graphed and alerted on.
+
+use std::time::Instant;
+
+use nvml_wrapper::{
+    enums::device::SampleValue, structs::device::FieldId, sys_exports::field_id::*, Device,
+};
+use serde::{Deserialize, Serialize};
+
+use super::graph::GraphViewerData;
+
+/// The fields read in one NVML call, in the order of [`EccCounts`]'s fields.
+const FIELDS: [u32; 11] = [
+    NVML_FI_DEV_ECC_SBE_VOL_TOTAL,
+    NVML_FI_DEV_ECC_DBE_VOL_TOTAL,
+    NVML_FI_DEV_ECC_SBE_AGG_TOTAL,
+    NVML_FI_DEV_ECC_DBE_AGG_TOTAL,
+    NVML_FI_DEV_RETIRED_SBE,
+    NVML_FI_DEV_RETIRED_DBE,
+    NVML_FI_DEV_RETIRED_PENDING,
+    NVML_FI_DEV_REMAPPED_COR,
+    NVML_FI_DEV_REMAPPED_UNC,
+    NVML_FI_DEV_REMAPPED_PENDING,
+    NVML_FI_DEV_REMAPPED_FAILURE,
+];
+
+/// A GPU's memory error counters at one point in time. Each is `None` where the GPU doesn't
+/// support it, e.g. GPUs without ECC memory, or page retirement on GPUs that remap rows
+/// instead.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(default)]
+pub struct EccCounts {
+    /// Single-bit (corrected) errors since the driver was loaded.
+    pub volatile_single_bit: Option<u64>,
+    /// Double-bit (uncorrected) errors since the driver was loaded.
+    pub volatile_double_bit: Option<u64>,
+    /// Single-bit errors over the GPU's lifetime.
+    pub aggregate_single_bit: Option<u64>,
+    /// Double-bit errors over the GPU's lifetime.
+    pub aggregate_double_bit: Option<u64>,
+    /// Pages retired because of repeated single-bit errors.
+    pub retired_pages_single_bit: Option<u64>,
+    /// Pages retired because of a double-bit error.
+    pub retired_pages_double_bit: Option<u64>,
+    /// Whether pages are waiting to be retired when the GPU is next reset.
+    pub retired_pages_pending: Option<bool>,
+    /// Rows remapped because of corrected errors.
+    pub remapped_rows_corrected: Option<u64>,
+    /// Rows remapped because of uncorrected errors.
+    pub remapped_rows_uncorrected: Option<u64>,
+    /// Whether rows are waiting to be remapped when the GPU is next reset.
+    pub remapped_rows_pending: Option<bool>,
+    /// Whether remapping a row has failed, which means the GPU needs servicing.
+    pub remapped_rows_failed: Option<bool>,
+}
+
+impl EccCounts {
+    /// Reads the counters, or returns `None` if the GPU supports none of them.
+    pub fn read(device: &Device) -> Option<Self> {
+        let ids = FIELDS.map(FieldId);
+        let values = device
+            .field_values_for(&ids)
+            .ok()?
+            .into_iter()
+            .map(|sample| match sample.ok()?.value.ok()? {
+                SampleValue::U32(value) => Some(value as u64),
+                SampleValue::U64(value) => Some(value),
+                SampleValue::I64(value) => u64::try_from(value).ok(),
+                SampleValue::F64(_) => None,
+            })
+            .collect::<Vec<_>>();
+        let value = |index: usize| values.get(index).copied().flatten();
+        let flag = |index: usize| value(index).map(|value| value != 0);
+
+        let counts = Self {
+            volatile_single_bit: value(0),
+            volatile_double_bit: value(1),
+            aggregate_single_bit: value(2),
+            aggregate_double_bit: value(3),
+            retired_pages_single_bit: value(4),
+            retired_pages_double_bit: value(5),
+            retired_pages_pending: flag(6),
+            remapped_rows_corrected: value(7),
+            remapped_rows_uncorrected: value(8),
+            remapped_rows_pending: flag(9),
+            remapped_rows_failed: flag(10),
+        };
+        (counts != Self::default()).then_some(counts)
+    }
+
+    fn retirement_counters(&self) -> [Option<u64>; 4] {
+        [
+            self.retired_pages_single_bit,
+            self.retired_pages_double_bit,
+            self.remapped_rows_corrected,
+            self.remapped_rows_uncorrected,
+        ]
+    }
+
+    /// Pages retired and rows remapped, for any reason.
+    pub fn retired_or_remapped(&self) -> Option<u64> {
+        self.retirement_counters()
+            .into_iter()
+            .flatten()
+            .reduce(|total, count| total + count)
+    }
+
+    /// Each counter as a label and its value, in the order they're shown.
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        let count = |count: Option<u64>| {
+            count
+                .map(|count| count.to_string())
+                .unwrap_or_else(|| "N/A".to_string())
+        };
+        let flag = |flag: Option<bool>| {
+            match flag {
+                Some(true) => "Yes",
+                Some(false) => "No",
+                None => "N/A",
+            }
+            .to_string()
+        };
+
+        vec![
+            ("Volatile single-bit ECC", count(self.volatile_single_bit)),
+            ("Volatile double-bit ECC", count(self.volatile_double_bit)),
+            ("Aggregate single-bit ECC", count(self.aggregate_single_bit)),
+            ("Aggregate double-bit ECC", count(self.aggregate_double_bit)),
+            (
+                "Retired pages (single-bit)",
+                count(self.retired_pages_single_bit),
+            ),
+            (
+                "Retired pages (double-bit)",
+                count(self.retired_pages_double_bit),
+            ),
+            ("Retirement pending", flag(self.retired_pages_pending)),
+            (
+                "Remapped rows (corrected)",
+                count(self.remapped_rows_corrected),
+            ),
+            (
+                "Remapped rows (uncorrected)",
+                count(self.remapped_rows_uncorrected),
+            ),
+            ("Remapping pending", flag(self.remapped_rows_pending)),
+            ("Remapping failed", flag(self.remapped_rows_failed)),
+        ]
+    }
+
+    /// Describes which counters went up since `previous`, or returns `None` if none did.
+    /// Counters that went down, e.g. volatile ones after the driver was reloaded, are ignored.
+    pub fn increase_since(&self, previous: &EccCounts) -> Option<String> {
+        // A counter that could only be read in one of the two would change the total without
+        // anything having been retired, so the totals are only compared if they're of the
+        // same counters
+        let same_retirement_counters = previous
+            .retirement_counters()
+            .iter()
+            .zip(self.retirement_counters())
+            .all(|(before, after)| before.is_some() == after.is_some());
+
+        let counters = [
+            (
+                ("single-bit ECC error", "single-bit ECC errors"),
+                previous.volatile_single_bit.zip(self.volatile_single_bit),
+            ),
+            (
+                ("double-bit ECC error", "double-bit ECC errors"),
+                previous.volatile_double_bit.zip(self.volatile_double_bit),
+            ),
+            (
+                ("retired or remapped page", "retired or remapped pages"),
+                previous
+                    .retired_or_remapped()
+                    .zip(self.retired_or_remapped())
+                    .filter(|_| same_retirement_counters),
+            ),
+        ];
+
+        let increases = counters
+            .into_iter()
+            .filter_map(|((singular, plural), counts)| {
+                let (before, after) = counts?;
+                let new = after.checked_sub(before).filter(|&new| new > 0)?;
+                let label = if new == 1 { singular } else { plural };
+                Some(format!("{} new {}", new, label))
+            })
+            .collect::<Vec<_>>();
+        (!increases.is_empty()).then(|| increases.join(", "))
+    }
+}
+
+/// The last time a GPU's memory error counters went up.
+#[derive(Clone, Debug)]
+pub struct EccIncrease {
+    pub time: Instant,
+    /// e.g. "2 new single-bit ECC errors, 1 new retired or remapped page".
+    pub description: String,
+}
+
+/// Graphs of the counters that matter most, kept in step with the other graphs.
+pub struct EccHistory {
+    pub single_bit: GraphViewerData,
+    pub double_bit: GraphViewerData,
+    pub retired_or_remapped: GraphViewerData,
+}
+
+impl EccHistory {
+    pub fn new() -> Self {
+        Self {
+            single_bit: GraphViewerData::new(),
+            double_bit: GraphViewerData::new(),
+            retired_or_remapped: GraphViewerData::new(),
+        }
+    }
+
+    pub fn update(&mut self, counts: Option<&EccCounts>) {
+        let value = |count: Option<u64>| count.map(|count| count as f32);
+        self.single_bit
+            .update(value(counts.and_then(|c| c.volatile_single_bit)));
+        self.double_bit
+            .update(value(counts.and_then(|c| c.volatile_double_bit)));
+        self.retired_or_remapped
+            .update(value(counts.and_then(|c| c.retired_or_remapped())));
+    }
+
+    /// Each graph with its name, for showing them together.
+    pub fn graphs(&self) -> [(&'static str, &GraphViewerData); 3] {
+        [
+            ("Single-bit ECC errors", &self.single_bit),
+            ("Double-bit ECC errors", &self.double_bit),
+            ("Retired or remapped pages", &self.retired_or_remapped),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(single_bit: u64, double_bit: u64, retired: u64) -> EccCounts {
+        EccCounts {
+            volatile_single_bit: Some(single_bit),
+            volatile_double_bit: Some(double_bit),
+            retired_pages_single_bit: Some(retired),
+            retired_pages_double_bit: Some(0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn describes_the_counters_that_went_up() {
+        let before = counts(3, 0, 1);
+
+        assert_eq!(before.increase_since(&before), None);
+        assert_eq!(
+            counts(4, 0, 1).increase_since(&before).as_deref(),
+            Some("1 new single-bit ECC error")
+        );
+        assert_eq!(
+            counts(5, 1, 3).increase_since(&before).as_deref(),
+            Some(
+                "2 new single-bit ECC errors, 1 new double-bit ECC error, \
+                 2 new retired or remapped pages"
+            )
+        );
+    }
+
+    #[test]
+    fn counters_that_go_down_or_appear_are_not_increases() {
+        let before = counts(3, 2, 1);
+
+        // The volatile counters start again when the driver is reloaded
+        assert_eq!(counts(0, 0, 1).increase_since(&before), None);
+
+        let unreadable = EccCounts {
+            volatile_single_bit: None,
+            ..before
+        };
+        assert_eq!(before.increase_since(&unreadable), None);
+        assert_eq!(unreadable.increase_since(&before), None);
+    }
+
+    #[test]
+    fn retirement_totals_are_only_compared_over_the_same_counters() {
+        let before = counts(0, 0, 1);
+        let with_remapping = EccCounts {
+            remapped_rows_corrected: Some(4),
+            ..before
+        };
+        assert_eq!(before.retired_or_remapped(), Some(1));
+        assert_eq!(with_remapping.retired_or_remapped(), Some(5));
+
+        assert_eq!(with_remapping.increase_since(&before), None);
+        assert_eq!(before.increase_since(&with_remapping), None);
+
+        let more = EccCounts {
+            remapped_rows_corrected: Some(6),
+            ..with_remapping
+        };
+        assert_eq!(
+            more.increase_since(&with_remapping).as_deref(),
+            Some("2 new retired or remapped pages")
+        );
+    }
+}
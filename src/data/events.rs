@@ -0,0 +1,394 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    io::{self, Write},
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use nvml_wrapper::{enums::device::UsedGpuMemory, Device};
+
+use crate::{
+    processes::ProcessData,
+    utils::{bytes_to_mib_gib, quote_field},
+};
+
+/// The maximum number of events kept in the log before the oldest are dropped.
+const MAX_EVENTS: usize = 1000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProcessEventKind {
+    Started {
+        memory: u64,
+    },
+    Exited {
+        peak_memory: u64,
+        duration: Duration,
+    },
+    /// A process that started and exited between two refreshes, only known about
+    /// through NVML's accounting stats.
+    ShortLived {
+        peak_memory: u64,
+        duration: Duration,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct ProcessEvent {
+    pub time: SystemTime,
+    /// The host the GPU is on, for remote GPUs.
+    pub host: Option<String>,
+    pub gpu_index: u32,
+    pub pid: u32,
+    pub name: String,
+    pub kind: ProcessEventKind,
+}
+
+impl ProcessEvent {
+    /// `GPU N`, or `GPU N of host` if the GPU is remote.
+    fn gpu(&self) -> String {
+        match &self.host {
+            Some(host) => format!("GPU {} of {}", self.gpu_index, host),
+            None => format!("GPU {}", self.gpu_index),
+        }
+    }
+}
+
+impl fmt::Display for ProcessEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pid {} ({}) ", self.pid, self.name)?;
+        match &self.kind {
+            ProcessEventKind::Started { memory } => write!(
+                f,
+                "started on {} using {}",
+                self.gpu(),
+                bytes_to_mib_gib(*memory as f32)
+            ),
+            ProcessEventKind::Exited {
+                peak_memory,
+                duration,
+            } => write!(
+                f,
+                "exited from {} after {}s, peak {}",
+                self.gpu(),
+                duration.as_secs(),
+                bytes_to_mib_gib(*peak_memory as f32)
+            ),
+            ProcessEventKind::ShortLived {
+                peak_memory,
+                duration,
+            } => write!(
+                f,
+                "ran briefly on {} for {}ms, peak {}",
+                self.gpu(),
+                duration.as_millis(),
+                bytes_to_mib_gib(*peak_memory as f32)
+            ),
+        }
+    }
+}
+
+/// A bounded, chronological log of process events across all GPUs.
+pub struct EventLog {
+    events: VecDeque<ProcessEvent>,
+    /// How many events have ever been pushed, including ones that have been dropped.
+    total_pushed: usize,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self {
+            events: VecDeque::new(),
+            total_pushed: 0,
+        }
+    }
+
+    pub fn push(&mut self, event: ProcessEvent) {
+        self.total_pushed += 1;
+        self.events.push_back(event);
+        while self.events.len() > MAX_EVENTS {
+            self.events.pop_front();
+        }
+    }
+
+    /// The events in the log, oldest first.
+    pub fn events(&self) -> impl DoubleEndedIterator<Item = &ProcessEvent> {
+        self.events.iter()
+    }
+
+    pub fn total_pushed(&self) -> usize {
+        self.total_pushed
+    }
+
+    /// The events pushed since `total_pushed` returned the given count, oldest first.
+    pub fn events_since(&self, total_pushed: usize) -> impl Iterator<Item = &ProcessEvent> {
+        let new = self.total_pushed.saturating_sub(total_pushed);
+        let skip = self.events.len().saturating_sub(new);
+        self.events.iter().skip(skip)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Writes the log as tab separated lines of unix time, host (empty for local GPUs), GPU,
+    /// pid, name and description.
+    /// Names and descriptions with tabs, newlines or quotes in them are quoted.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "time\thost\tgpu\tpid\tname\tevent")?;
+        for event in &self.events {
+            let time = event
+                .time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64();
+            writeln!(
+                writer,
+                "{:.3}\t{}\t{}\t{}\t{}\t{}",
+                time,
+                quote_field(event.host.as_deref().unwrap_or_default(), '\t'),
+                event.gpu_index,
+                event.pid,
+                quote_field(&event.name, '\t'),
+                quote_field(&event.to_string(), '\t')
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn export(&self, path: &Path) -> io::Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut file)?;
+        file.flush()
+    }
+}
+
+struct TrackedProcess {
+    name: String,
+    started: Instant,
+    peak_memory: u64,
+}
+
+/// Diffs successive process lists of a GPU to find processes that started or exited.
+pub struct ProcessLifecycleTracker {
+    host: Option<String>,
+    gpu_index: u32,
+    known: HashMap<u32, TrackedProcess>,
+    /// Accounting pids that have already been looked at, so they're only reported once.
+    seen_accounting_pids: HashSet<u32>,
+    initialized: bool,
+}
+
+fn used_memory(process: &ProcessData) -> u64 {
+    match process.info.used_gpu_memory {
+        UsedGpuMemory::Used(bytes) => bytes,
+        UsedGpuMemory::Unavailable => 0,
+    }
+}
+
+impl ProcessLifecycleTracker {
+    /// Tracks the processes of the GPU at `gpu_index`, on `host` if it's remote.
+    pub fn new(host: Option<String>, gpu_index: u32) -> Self {
+        Self {
+            host,
+            gpu_index,
+            known: HashMap::new(),
+            seen_accounting_pids: HashSet::new(),
+            initialized: false,
+        }
+    }
+
+    fn event(&self, pid: u32, name: String, kind: ProcessEventKind) -> ProcessEvent {
+        ProcessEvent {
+            time: SystemTime::now(),
+            host: self.host.clone(),
+            gpu_index: self.gpu_index,
+            pid,
+            name,
+            kind,
+        }
+    }
+
+    /// Compares the new process list with the previous one, recording any changes in
+    /// the log. Processes already running the first time this is called aren't reported.
+    ///
+    /// Without a device, as for GPUs on other hosts, accounting stats aren't consulted.
+    pub fn update(
+        &mut self,
+        device: Option<&Device>,
+        processes: &[ProcessData],
+        log: &mut EventLog,
+    ) {
+        let now = Instant::now();
+
+        for process in processes {
+            let memory = used_memory(process);
+            match self.known.get_mut(&process.info.pid) {
+                Some(tracked) => tracked.peak_memory = tracked.peak_memory.max(memory),
+                None => {
+                    if self.initialized {
+                        log.push(self.event(
+                            process.info.pid,
+                            process.name.clone(),
+                            ProcessEventKind::Started { memory },
+                        ));
+                    }
+                    self.known.insert(
+                        process.info.pid,
+                        TrackedProcess {
+                            name: process.name.clone(),
+                            started: now,
+                            peak_memory: memory,
+                        },
+                    );
+                }
+            }
+        }
+
+        let exited = self
+            .known
+            .keys()
+            .filter(|pid| !processes.iter().any(|p| p.info.pid == **pid))
+            .copied()
+            .collect::<Vec<_>>();
+
+        let accounting_device =
+            device.filter(|device| device.is_accounting_enabled().unwrap_or(false));
+
+        for pid in exited {
+            let Some(tracked) = self.known.remove(&pid) else {
+                continue;
+            };
+
+            // Accounting stats know about allocations made in between our refreshes
+            let accounted_peak = accounting_device
+                .and_then(|device| device.accounting_stats_for(pid).ok())
+                .and_then(|stats| stats.max_memory_usage)
+                .unwrap_or(0);
+
+            log.push(self.event(
+                pid,
+                tracked.name,
+                ProcessEventKind::Exited {
+                    peak_memory: tracked.peak_memory.max(accounted_peak),
+                    duration: now.duration_since(tracked.started),
+                },
+            ));
+            self.seen_accounting_pids.insert(pid);
+        }
+
+        if let Some(device) = accounting_device {
+            self.capture_short_lived(device, log);
+        }
+
+        self.initialized = true;
+    }
+
+    /// Reports processes that NVML accounted for but that never showed up in a process list.
+    fn capture_short_lived(&mut self, device: &Device, log: &mut EventLog) {
+        let Ok(pids) = device.accounting_pids() else {
+            return;
+        };
+
+        // The accounting buffer is circular, so forget pids that have dropped out of it
+        self.seen_accounting_pids.retain(|pid| pids.contains(pid));
+
+        for pid in pids {
+            if self.known.contains_key(&pid) || self.seen_accounting_pids.contains(&pid) {
+                continue;
+            }
+
+            let Ok(stats) = device.accounting_stats_for(pid) else {
+                continue;
+            };
+            if stats.is_running {
+                // It'll show up in the next process list instead
+                continue;
+            }
+
+            self.seen_accounting_pids.insert(pid);
+
+            // Everything in the buffer at startup is old history rather than a new event
+            if !self.initialized {
+                continue;
+            }
+
+            log.push(self.event(
+                pid,
+                "Unknown".to_string(),
+                ProcessEventKind::ShortLived {
+                    peak_memory: stats.max_memory_usage.unwrap_or(0),
+                    duration: Duration::from_millis(stats.time),
+                },
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exported(host: Option<&str>, name: &str) -> String {
+        let mut log = EventLog::new();
+        log.push(ProcessEvent {
+            time: UNIX_EPOCH + Duration::from_secs(100),
+            host: host.map(str::to_string),
+            gpu_index: 0,
+            pid: 42,
+            name: name.to_string(),
+            kind: ProcessEventKind::Started { memory: 0 },
+        });
+        let mut output = Vec::new();
+        log.write_to(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn plain_names_are_written_as_is() {
+        let output = exported(None, "python");
+        let row = output.lines().nth(1).unwrap();
+        assert_eq!(
+            row.split('\t').collect::<Vec<_>>(),
+            [
+                "100.000",
+                "",
+                "0",
+                "42",
+                "python",
+                "pid 42 (python) started on GPU 0 using 0.00KiB"
+            ]
+        );
+    }
+
+    #[test]
+    fn names_that_would_break_the_row_are_quoted() {
+        let output = exported(None, "bad\tname\n\"x\"");
+        assert_eq!(
+            output.lines().skip(1).collect::<Vec<_>>().join("\n"),
+            "100.000\t\t0\t42\t\"bad\tname\n\"\"x\"\"\"\t\
+             \"pid 42 (bad\tname\n\"\"x\"\") started on GPU 0 using 0.00KiB\""
+        );
+    }
+
+    #[test]
+    fn remote_events_say_which_host_they_are_from() {
+        let output = exported(Some("trainer"), "python");
+        let row = output.lines().nth(1).unwrap();
+        assert_eq!(
+            row.split('\t').collect::<Vec<_>>(),
+            [
+                "100.000",
+                "trainer",
+                "0",
+                "42",
+                "python",
+                "pid 42 (python) started on GPU 0 of trainer using 0.00KiB"
+            ]
+        );
+    }
+}
@@ -1,30 +1,68 @@
-use std::collections::VecDeque;
+use std::{collections::VecDeque, time::SystemTime};
 
 pub struct GraphViewerData {
-    historical: VecDeque<Option<f32>>,
+    /// When each value was recorded, and the value itself, newest first.
+    historical: VecDeque<(SystemTime, Option<f32>)>,
+    max_len: usize,
 }
 
 impl GraphViewerData {
     pub fn new() -> Self {
+        Self::with_max_len(5000)
+    }
+
+    pub fn with_max_len(max_len: usize) -> Self {
         Self {
             historical: VecDeque::new(),
+            max_len,
         }
     }
 
     pub fn update(&mut self, value: Option<f32>) {
-        self.historical.push_front(value);
+        self.push(SystemTime::now(), value);
+    }
+
+    /// Adds a value recorded at `time`, which should be after every value so far.
+    pub fn push(&mut self, time: SystemTime, value: Option<f32>) {
+        self.historical.push_front((time, value));
         self.trim_length()
     }
 
     fn trim_length(&mut self) {
-        let max_len = 5000;
-
-        while self.historical.len() > max_len {
+        while self.historical.len() > self.max_len {
             self.historical.pop_back();
         }
     }
 
+    /// The largest value in the history, if there are any values.
+    pub fn max_value(&self) -> Option<f32> {
+        self.historical
+            .iter()
+            .filter_map(|(_, value)| *value)
+            .reduce(f32::max)
+    }
+
     pub fn get_value_at(&self, index: usize) -> Option<f32> {
-        self.historical.get(index).copied().flatten()
+        self.historical.get(index).and_then(|(_, value)| *value)
+    }
+
+    /// When the value at `index` was recorded, and the value itself.
+    pub fn get_sample_at(&self, index: usize) -> Option<(SystemTime, Option<f32>)> {
+        self.historical.get(index).copied()
+    }
+
+    /// The newest `count` values (or fewer, if there aren't that many), oldest first.
+    pub fn recent(&self, count: usize) -> Vec<Option<f32>> {
+        self.historical
+            .iter()
+            .take(count)
+            .rev()
+            .map(|(_, value)| *value)
+            .collect()
+    }
+
+    /// Every value along with when it was recorded, oldest first.
+    pub fn samples(&self) -> impl Iterator<Item = (SystemTime, Option<f32>)> + '_ {
+        self.historical.iter().rev().copied()
     }
 }
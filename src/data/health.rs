@@ -0,0 +1,140 @@
+//! XID errors, which the driver reports when something goes wrong with a GPU, such as it
+//! falling off the bus or hitting an uncorrectable ECC error.
+
+use std::{
+    fmt,
+    sync::mpsc::{self, Receiver},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// The number of health events kept for each GPU before the oldest are dropped.
+pub const MAX_HEALTH_EVENTS: usize = 50;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct HealthEvent {
+    pub time: SystemTime,
+    /// The XID error code, if the driver said which it was.
+    pub xid: Option<u64>,
+}
+
+impl HealthEvent {
+    /// What some of the more common XIDs mean, going by NVIDIA's XID documentation.
+    fn meaning(&self) -> Option<&'static str> {
+        Some(match self.xid? {
+            13 => "graphics engine exception",
+            31 => "GPU memory page fault",
+            43 => "GPU stopped processing",
+            45 => "preemptive cleanup",
+            48 => "double-bit ECC error",
+            61 | 62 => "internal micro-controller error",
+            63 => "ECC page retirement or row remapping",
+            64 => "ECC page retirement or row remapping failure",
+            74 => "NVLink error",
+            79 => "GPU has fallen off the bus",
+            92 => "high single-bit ECC error rate",
+            94 => "contained ECC error",
+            95 => "uncontained ECC error",
+            119 | 120 => "GSP error",
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for HealthEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.xid {
+            Some(xid) => write!(f, "XID {}", xid)?,
+            None => write!(f, "Unknown XID")?,
+        }
+        if let Some(meaning) = self.meaning() {
+            write!(f, " ({})", meaning)?;
+        }
+        Ok(())
+    }
+}
+
+/// Watches the local GPUs for XID errors on its own thread, as waiting for them blocks.
+pub struct XidWatcher {
+    /// The UUID of the GPU each error happened on, and the error.
+    events: Receiver<(String, HealthEvent)>,
+}
+
+impl XidWatcher {
+    /// Starts watching, where NVML supports reporting XID errors, which is only on Linux.
+    pub fn spawn() -> Self {
+        let (sender, events) = mpsc::channel();
+
+        #[cfg(target_os = "linux")]
+        std::thread::spawn(move || watch_xid_errors(sender));
+        #[cfg(not(target_os = "linux"))]
+        drop(sender);
+
+        Self { events }
+    }
+
+    /// The errors since the last call.
+    pub fn take_events(&self) -> Vec<(String, HealthEvent)> {
+        self.events.try_iter().collect()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn watch_xid_errors(sender: mpsc::Sender<(String, HealthEvent)>) {
+    use nvml_wrapper::{
+        bitmasks::event::EventTypes, enums::event::XidError, error::NvmlError, Nvml,
+    };
+
+    // A separate handle, as the event set borrows it for as long as it's being waited on
+    let Ok(nvml) = Nvml::init() else {
+        return;
+    };
+    let Ok(mut set) = nvml.create_event_set() else {
+        return;
+    };
+
+    let mut registered = 0;
+    for index in 0..nvml.device_count().unwrap_or(0) {
+        let Ok(device) = nvml.device_by_index(index) else {
+            continue;
+        };
+        let supported = device
+            .supported_event_types()
+            .is_ok_and(|types| types.contains(EventTypes::CRITICAL_XID_ERROR));
+        if !supported {
+            continue;
+        }
+        // The set is released if registering fails, along with the other GPUs in it
+        set = match device.register_events(EventTypes::CRITICAL_XID_ERROR, set) {
+            Ok(set) => set,
+            Err(_) => return,
+        };
+        registered += 1;
+    }
+    if registered == 0 {
+        return;
+    }
+
+    loop {
+        match set.wait(1000) {
+            Ok(data) => {
+                let Ok(uuid) = data.device.uuid() else {
+                    continue;
+                };
+                let event = HealthEvent {
+                    time: SystemTime::now(),
+                    xid: match data.event_data {
+                        Some(XidError::Value(xid)) => Some(xid),
+                        Some(XidError::Unknown) | None => None,
+                    },
+                };
+                if sender.send((uuid, event)).is_err() {
+                    return;
+                }
+            }
+            Err(NvmlError::Timeout) => {}
+            Err(_) => return,
+        }
+    }
+}
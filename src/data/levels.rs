@@ -0,0 +1,141 @@
+use serde::Deserialize;
+
+/// How close a value is to its limit, which graphs show by colour.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+    Normal,
+    Warning,
+    Critical,
+}
+
+/// The values at which a metric becomes a warning and then critical.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Thresholds {
+    pub warning: f32,
+    pub critical: f32,
+}
+
+impl Thresholds {
+    pub fn level(&self, value: f32) -> Level {
+        if value >= self.critical {
+            Level::Critical
+        } else if value >= self.warning {
+            Level::Warning
+        } else {
+            Level::Normal
+        }
+    }
+
+    fn scaled(&self, factor: f32) -> Self {
+        Self {
+            warning: self.warning * factor,
+            critical: self.critical * factor,
+        }
+    }
+}
+
+/// How a graph shows the level of its values.
+#[derive(Clone, Copy, Debug)]
+pub struct GraphLevels {
+    /// In the graph's units.
+    pub thresholds: Thresholds,
+    /// Whether warning and critical values are also marked with a pattern, so that colour
+    /// isn't the only signal.
+    pub patterns: bool,
+}
+
+/// The `[graphs]` config section.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct GraphConfig {
+    /// Whether graphs change colour as values approach their limits.
+    pub color_by_value: bool,
+    pub patterns: bool,
+    /// In °C.
+    pub temperature: Thresholds,
+    /// In percent of the GPU's memory.
+    pub memory: Thresholds,
+}
+
+impl Default for GraphConfig {
+    fn default() -> Self {
+        Self {
+            color_by_value: true,
+            patterns: false,
+            temperature: Thresholds {
+                warning: 70.0,
+                critical: 85.0,
+            },
+            memory: Thresholds {
+                warning: 75.0,
+                critical: 90.0,
+            },
+        }
+    }
+}
+
+impl GraphConfig {
+    fn levels(&self, thresholds: Thresholds) -> Option<GraphLevels> {
+        self.color_by_value.then_some(GraphLevels {
+            thresholds,
+            patterns: self.patterns,
+        })
+    }
+
+    pub fn temperature_levels(&self) -> Option<GraphLevels> {
+        self.levels(self.temperature)
+    }
+
+    /// The levels of a memory graph in bytes, for a GPU with `max_memory` bytes.
+    pub fn memory_levels(&self, max_memory: u64) -> Option<GraphLevels> {
+        self.levels(self.memory.scaled(max_memory as f32 / 100.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_start_at_their_thresholds() {
+        let thresholds = GraphConfig::default().temperature;
+        assert_eq!(thresholds.level(-10.0), Level::Normal);
+        assert_eq!(thresholds.level(69.9), Level::Normal);
+        assert_eq!(thresholds.level(70.0), Level::Warning);
+        assert_eq!(thresholds.level(84.9), Level::Warning);
+        assert_eq!(thresholds.level(85.0), Level::Critical);
+        assert_eq!(thresholds.level(120.0), Level::Critical);
+    }
+
+    #[test]
+    fn memory_thresholds_are_a_share_of_the_gpus_memory() {
+        let config = GraphConfig::default();
+        let gib = 1024.0 * 1024.0 * 1024.0;
+        let levels = config.memory_levels(16 * 1024 * 1024 * 1024).unwrap();
+
+        assert_eq!(levels.thresholds.warning, 12.0 * gib);
+        assert_eq!(levels.thresholds.critical, 14.4 * gib);
+        assert_eq!(levels.thresholds.level(11.9 * gib), Level::Normal);
+        assert_eq!(levels.thresholds.level(15.0 * gib), Level::Critical);
+        assert!(!levels.patterns);
+    }
+
+    #[test]
+    fn graphs_are_only_coloured_by_value_if_enabled() {
+        let config = toml::from_str::<GraphConfig>(
+            "patterns = true\ntemperature = { warning = 60.0, critical = 80.0 }",
+        )
+        .unwrap();
+        let levels = config.temperature_levels().unwrap();
+        assert!(levels.patterns);
+        assert_eq!(levels.thresholds.level(65.0), Level::Warning);
+
+        let config = GraphConfig {
+            color_by_value: false,
+            ..config
+        };
+        assert!(config.temperature_levels().is_none());
+        assert!(config.memory_levels(1024).is_none());
+    }
+}
@@ -1,117 +1,569 @@
-use std::time::Instant;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
 
-use nvml_wrapper::{enum_wrappers::device::TemperatureSensor, Device, Nvml};
+use nvml_wrapper::{
+    enum_wrappers::device::TemperatureSensor, enums::device::UsedGpuMemory, error::NvmlError,
+    Device, Nvml,
+};
+use sysinfo::System;
 
-use self::{graph::GraphViewerData, process_table::ProcessTableData};
+use crate::{
+    config::Config,
+    hooks::{HookDispatcher, HookEvent},
+    notify::AlertNotifier,
+    processes::ProcessDataBank,
+    remote::{
+        client::{ConnectionStatus, RemoteConnection},
+        protocol::DeviceSnapshot,
+    },
+};
 
+use self::{
+    alerts::AlertEngine,
+    cluster::{ClusterRow, HostHealth, STALE_AFTER},
+    device_info::DeviceInfo,
+    ecc::{EccCounts, EccHistory, EccIncrease},
+    events::{EventLog, ProcessLifecycleTracker},
+    graph::GraphViewerData,
+    health::{HealthEvent, XidWatcher, MAX_HEALTH_EVENTS},
+    process_table::ProcessTableData,
+    sample::GpuSample,
+    throttle::{ThrottleHistory, ThrottleReasons},
+};
+
+pub mod alerts;
+pub mod cluster;
+pub mod device_info;
+pub mod ecc;
+pub mod events;
 pub mod graph;
+pub mod health;
+pub mod levels;
+pub mod process_filter;
+pub mod process_history;
 pub mod process_table;
+pub mod sample;
+pub mod throttle;
 
 pub struct GpuMonitoringData {
-    nvml: Nvml,
+    nvml: Option<Nvml>,
+    /// Set when there are local GPUs to watch for XID errors.
+    xid_watcher: Option<XidWatcher>,
+    remotes: Vec<RemoteConnection>,
     monitors: Vec<GpuDeviceMonitor>,
+    events: EventLog,
+    process_bank: ProcessDataBank,
+    alerts: AlertEngine,
+    notifier: AlertNotifier,
+    hooks: HookDispatcher,
+    idle_threshold: Duration,
+    /// The name of this machine, to show local GPUs under alongside remote ones.
+    local_host: String,
 }
 
 impl GpuMonitoringData {
-    pub fn new() -> Self {
-        let nvml = Nvml::init().unwrap();
+    pub fn new(config: &Config) -> Self {
+        let idle_threshold = Duration::from_secs(config.idle_after_secs);
 
-        let gpu_count = nvml.device_count().unwrap();
+        // Machines without an NVIDIA GPU can still show the GPUs of remote agents
+        let nvml = match Nvml::init() {
+            Ok(nvml) => Some(nvml),
+            Err(err) if !config.remotes.is_empty() => {
+                eprintln!("Not monitoring local GPUs: {}", err);
+                None
+            }
+            Err(err) => panic!("Failed to initialize NVML: {}", err),
+        };
 
-        let monitors = (0..gpu_count)
-            .map(|i| {
-                let device = nvml.device_by_index(i).unwrap();
-                GpuDeviceMonitor::new(&device)
-            })
+        let monitors = match &nvml {
+            Some(nvml) => (0..nvml.device_count().unwrap())
+                .map(|i| {
+                    let device = nvml.device_by_index(i).unwrap();
+                    GpuDeviceMonitor::new(&device, idle_threshold)
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let remotes = config
+            .remotes
+            .iter()
+            .cloned()
+            .map(RemoteConnection::connect)
             .collect();
 
-        Self { nvml, monitors }
+        Self {
+            xid_watcher: nvml.as_ref().map(|_| XidWatcher::spawn()),
+            nvml,
+            remotes,
+            monitors,
+            events: EventLog::new(),
+            process_bank: ProcessDataBank::new(),
+            alerts: AlertEngine::new(config.alerts.clone()),
+            notifier: AlertNotifier::new(config.desktop_notifications),
+            hooks: HookDispatcher::new(&config.hooks),
+            idle_threshold,
+            local_host: System::host_name().unwrap_or_else(|| "localhost".to_string()),
+        }
     }
 
     pub fn update(&mut self) {
-        for monitor in self.monitors.iter_mut() {
-            let uuid = monitor.device_uuid();
-            let device = self.nvml.device_by_uuid(uuid).unwrap();
-            monitor.update(&device);
+        let events_before = self.events.total_pushed();
+        let mut samples = Vec::new();
+
+        if let Some(nvml) = &self.nvml {
+            for monitor in self.monitors.iter_mut() {
+                if monitor.source() != GpuSource::Local {
+                    continue;
+                }
+
+                let sample = match nvml.device_by_uuid(monitor.device_uuid()) {
+                    Ok(device) => monitor.update(&device, &mut self.process_bank, &mut self.events),
+                    Err(_) => monitor.update_lost(),
+                };
+                samples.extend(sample);
+            }
+        }
+
+        if let Some(watcher) = &self.xid_watcher {
+            for (uuid, event) in watcher.take_events() {
+                let monitor = self.monitors.iter_mut().find(|monitor| {
+                    monitor.source() == GpuSource::Local && monitor.device_uuid() == uuid
+                });
+                if let Some(monitor) = monitor {
+                    monitor.push_health_event(event);
+                }
+            }
+        }
+
+        for (remote_index, remote) in self.remotes.iter().enumerate() {
+            let Some(snapshot) = remote.take_snapshot() else {
+                continue;
+            };
+
+            for device in snapshot.devices {
+                let source = GpuSource::Remote(remote_index);
+                let existing = self.monitors.iter().position(|monitor| {
+                    monitor.source() == source && monitor.device_uuid() == device.uuid
+                });
+                let monitor = match existing {
+                    Some(index) => &mut self.monitors[index],
+                    None => {
+                        self.monitors.push(GpuDeviceMonitor::from_snapshot(
+                            remote_index,
+                            &snapshot.host,
+                            &device,
+                            self.idle_threshold,
+                        ));
+                        self.monitors.last_mut().unwrap()
+                    }
+                };
+                samples.push(monitor.apply_snapshot(device, &mut self.events));
+            }
+        }
+
+        let mut transitions = Vec::new();
+        for sample in samples {
+            transitions.extend(self.alerts.evaluate(&sample));
+        }
+
+        // A host that has stopped responding can't say whether its alerts still apply, so
+        // they're resolved rather than left firing on readings that are going out of date
+        for (remote_index, remote) in self.remotes.iter().enumerate() {
+            let stale = remote
+                .last_received()
+                .is_none_or(|time| time.elapsed() >= STALE_AFTER);
+            if !stale {
+                continue;
+            }
+            for monitor in &self.monitors {
+                if monitor.source() == GpuSource::Remote(remote_index) {
+                    transitions.extend(self.alerts.resolve_device(monitor.device_uuid()));
+                }
+            }
+        }
+
+        for transition in transitions {
+            self.notifier.notify(&transition);
+            self.hooks.dispatch(HookEvent::Alert(transition));
+        }
+
+        for event in self.events.events_since(events_before) {
+            self.hooks.dispatch(HookEvent::Process(event.clone()));
+        }
+    }
+
+    pub fn alerts(&self) -> &AlertEngine {
+        &self.alerts
+    }
+
+    /// Whether any alerts are firing for the GPU at the given index.
+    pub fn is_alerting(&self, index: usize) -> bool {
+        self.alerts.is_firing(self.monitors[index].device_uuid())
+    }
+
+    pub fn events(&self) -> &EventLog {
+        &self.events
+    }
+
+    pub fn events_mut(&mut self) -> &mut EventLog {
+        &mut self.events
+    }
+
+    pub fn remotes(&self) -> &[RemoteConnection] {
+        &self.remotes
+    }
+
+    /// A row for every GPU on every host, in the order of [`Self::gpus`].
+    pub fn cluster_rows(&self) -> Vec<ClusterRow> {
+        self.monitors
+            .iter()
+            .enumerate()
+            .map(|(index, monitor)| ClusterRow::new(index, monitor, &self.local_host))
+            .collect()
+    }
+
+    /// The connection health of this machine, if its GPUs are being monitored, and of
+    /// every remote agent.
+    pub fn hosts(&self) -> Vec<HostHealth> {
+        let mut hosts = Vec::new();
+
+        if self.nvml.is_some() {
+            let local = self
+                .monitors
+                .iter()
+                .filter(|monitor| monitor.source() == GpuSource::Local);
+            hosts.push(HostHealth {
+                name: self.local_host.clone(),
+                address: None,
+                status: ConnectionStatus::Connected,
+                last_update: local
+                    .clone()
+                    .filter_map(|monitor| monitor.last_sample())
+                    .map(|sample| sample.time.elapsed())
+                    .min(),
+                gpu_count: local.count(),
+            });
         }
+
+        for (index, remote) in self.remotes.iter().enumerate() {
+            hosts.push(HostHealth {
+                name: remote
+                    .host()
+                    .unwrap_or_else(|| remote.address().to_string()),
+                address: Some(remote.address().to_string()),
+                status: remote.status(),
+                last_update: remote.last_received().map(|time| time.elapsed()),
+                gpu_count: self
+                    .monitors
+                    .iter()
+                    .filter(|monitor| monitor.source() == GpuSource::Remote(index))
+                    .count(),
+            });
+        }
+
+        hosts
     }
 
+    /// Every GPU being monitored: the local ones first, then remote ones in the order
+    /// they were first heard from.
     pub fn gpus(&mut self) -> &mut [GpuDeviceMonitor] {
         &mut self.monitors
     }
 }
 
+/// Where a GPU's readings come from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GpuSource {
+    Local,
+    /// Snapshots from the remote agent at this index in the config.
+    Remote(usize),
+}
+
 pub struct GpuDeviceMonitor {
+    source: GpuSource,
+    /// The host the GPU is on, for remote GPUs.
+    host: Option<String>,
+    device_index: u32,
     device_uuid: String,
     device_name: String,
     last_graph_update: Option<Instant>,
     usage_graph: GraphViewerData,
     memory_graph: GraphViewerData,
     temperature_graph: GraphViewerData,
+    throttle_history: ThrottleHistory,
+    /// XID errors, oldest first.
+    health_events: VecDeque<HealthEvent>,
+    ecc_history: EccHistory,
+    /// The latest ECC counts that could be read, which new ones are compared against.
+    last_ecc: Option<EccCounts>,
+    ecc_increase: Option<EccIncrease>,
 
-    graphics_processes: ProcessTableData,
-    compute_processes: ProcessTableData,
-    all_processes: ProcessTableData,
+    processes: ProcessTableData,
+    lifecycle: ProcessLifecycleTracker,
 
     max_memory: u64,
+    last_sample: Option<GpuSample>,
+    /// The GPU's driver, firmware and limits, if known. Remote agents from before this was
+    /// part of the protocol don't send it.
+    info: Option<DeviceInfo>,
 }
 
 impl GpuDeviceMonitor {
-    pub fn new(device: &Device) -> Self {
+    pub fn new(device: &Device, idle_threshold: Duration) -> Self {
+        let device_index = device.index().unwrap();
+
         Self {
+            source: GpuSource::Local,
+            host: None,
+            device_index,
             device_uuid: device.uuid().unwrap(),
             device_name: device.name().unwrap(),
             last_graph_update: None,
             usage_graph: GraphViewerData::new(),
             memory_graph: GraphViewerData::new(),
             temperature_graph: GraphViewerData::new(),
+            throttle_history: ThrottleHistory::new(),
+            health_events: VecDeque::new(),
+            ecc_history: EccHistory::new(),
+            last_ecc: None,
+            ecc_increase: None,
 
-            graphics_processes: ProcessTableData::new(Box::new(|device| {
-                device.running_graphics_processes()
-            })),
-            compute_processes: ProcessTableData::new(Box::new(|device| {
-                device.running_compute_processes()
-            })),
-            all_processes: ProcessTableData::new(Box::new(|device| {
-                let graphics = device.running_graphics_processes()?;
-                let compute = device.running_compute_processes()?;
-
-                Ok(graphics.into_iter().chain(compute.into_iter()).collect())
-            })),
+            processes: ProcessTableData::new(idle_threshold),
+            lifecycle: ProcessLifecycleTracker::new(None, device_index),
 
             max_memory: device.memory_info().unwrap().total,
+            last_sample: None,
+            info: Some(DeviceInfo::read(device)),
         }
     }
 
-    pub fn update(&mut self, device: &Device) {
-        // Update the graphs every 500ms
-        if self.last_graph_update.is_none()
-            || self.last_graph_update.unwrap().elapsed().as_millis() > 500
-        {
+    pub fn from_snapshot(
+        remote_index: usize,
+        host: &str,
+        snapshot: &DeviceSnapshot,
+        idle_threshold: Duration,
+    ) -> Self {
+        Self {
+            source: GpuSource::Remote(remote_index),
+            host: Some(host.to_string()),
+            device_index: snapshot.index,
+            device_uuid: snapshot.uuid.clone(),
+            device_name: snapshot.name.clone(),
+            last_graph_update: None,
+            usage_graph: GraphViewerData::new(),
+            memory_graph: GraphViewerData::new(),
+            temperature_graph: GraphViewerData::new(),
+            throttle_history: ThrottleHistory::new(),
+            health_events: VecDeque::new(),
+            ecc_history: EccHistory::new(),
+            last_ecc: None,
+            ecc_increase: None,
+
+            processes: ProcessTableData::new(idle_threshold),
+            lifecycle: ProcessLifecycleTracker::new(Some(host.to_string()), snapshot.index),
+
+            max_memory: snapshot.memory_total,
+            last_sample: None,
+            info: snapshot.info.clone(),
+        }
+    }
+
+    /// Whether the graphs are due to be updated, which happens every 500ms.
+    fn take_sample_due(&mut self) -> bool {
+        let due = self.last_graph_update.is_none()
+            || self.last_graph_update.unwrap().elapsed().as_millis() > 500;
+        if due {
             self.last_graph_update = Some(Instant::now());
+        }
+        due
+    }
 
-            let percent = device.utilization_rates().map(|r| r.gpu as f32).ok();
-            self.usage_graph.update(percent);
+    fn empty_sample(&self) -> GpuSample {
+        GpuSample {
+            time: Instant::now(),
+            device_index: self.device_index,
+            device_uuid: self.device_uuid.clone(),
+            device_name: self.label(),
+            utilization: None,
+            memory_used: None,
+            memory_total: self.max_memory,
+            temperature: None,
+            process_memory: 0,
+            lost: false,
+            throttle_reasons: None,
+            ecc: None,
+            ecc_increase: self.ecc_increase.clone(),
+        }
+    }
 
-            let used = device.memory_info().map(|m| m.used as f32).ok();
-            self.memory_graph.update(used);
+    fn push_sample(&mut self, sample: &GpuSample) {
+        self.usage_graph.update(sample.utilization);
+        self.memory_graph
+            .update(sample.memory_used.map(|used| used as f32));
+        self.temperature_graph.update(sample.temperature);
+        self.throttle_history.update(sample.throttle_reasons);
+        self.ecc_history.update(sample.ecc.as_ref());
+        self.last_sample = Some(sample.clone());
+    }
 
-            let used = device
+    /// Compares the ECC counts with the last ones read, noting when they went up. This is
+    /// done before the sample is built so that the sample carries the latest increase.
+    fn track_ecc(&mut self, ecc: Option<EccCounts>) {
+        let Some(ecc) = ecc else {
+            return;
+        };
+        if let Some(description) = self.last_ecc.and_then(|last| ecc.increase_since(&last)) {
+            self.ecc_increase = Some(EccIncrease {
+                time: Instant::now(),
+                description,
+            });
+        }
+        self.last_ecc = Some(ecc);
+    }
+
+    /// The total memory held by the processes on the GPU, as of the last process refresh.
+    fn process_memory(&self) -> u64 {
+        self.processes
+            .all_processes()
+            .map(|processes| {
+                processes
+                    .iter()
+                    .map(|process| match process.info.used_gpu_memory {
+                        UsedGpuMemory::Used(bytes) => bytes,
+                        UsedGpuMemory::Unavailable => 0,
+                    })
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Updates the graphs and process list, returning a sample if the graphs were updated.
+    pub fn update(
+        &mut self,
+        device: &Device,
+        process_bank: &mut ProcessDataBank,
+        events: &mut EventLog,
+    ) -> Option<GpuSample> {
+        if self.processes.update(device, process_bank) {
+            if let Ok(processes) = self.processes.all_processes() {
+                self.lifecycle.update(Some(device), processes, events);
+            }
+        }
+
+        if !self.take_sample_due() {
+            return None;
+        }
+
+        let utilization = device.utilization_rates();
+        let lost = matches!(utilization, Err(NvmlError::GpuLost));
+        let ecc = EccCounts::read(device);
+        self.track_ecc(ecc);
+
+        let sample = GpuSample {
+            utilization: utilization.map(|r| r.gpu as f32).ok(),
+            memory_used: device.memory_info().map(|m| m.used).ok(),
+            temperature: device
                 .temperature(TemperatureSensor::Gpu)
                 .map(|m| m as f32)
-                .ok();
-            self.temperature_graph.update(used);
+                .ok(),
+            process_memory: self.process_memory(),
+            lost,
+            throttle_reasons: device
+                .current_throttle_reasons()
+                .map(ThrottleReasons::from_nvml)
+                .ok(),
+            ecc,
+            ..self.empty_sample()
+        };
+
+        self.push_sample(&sample);
+        Some(sample)
+    }
+
+    /// Records a gap in the graphs for a device that can no longer be reached.
+    pub fn update_lost(&mut self) -> Option<GpuSample> {
+        if !self.take_sample_due() {
+            return None;
         }
 
-        self.graphics_processes.update(device);
-        self.compute_processes.update(device);
-        self.all_processes.update(device);
+        let sample = GpuSample {
+            lost: true,
+            ..self.empty_sample()
+        };
+
+        self.push_sample(&sample);
+        Some(sample)
+    }
+
+    /// Updates the graphs and process list from a remote agent's snapshot of the GPU.
+    pub fn apply_snapshot(&mut self, snapshot: DeviceSnapshot, events: &mut EventLog) -> GpuSample {
+        self.device_name = snapshot.name;
+        self.max_memory = snapshot.memory_total;
+        if snapshot.info.is_some() {
+            self.info = snapshot.info;
+        }
+        self.health_events = snapshot.health_events.into();
+
+        // The agent's error message doesn't map back onto an NVML error
+        self.processes
+            .set_processes(snapshot.processes.map_err(|_| NvmlError::Unknown));
+        if let Ok(processes) = self.processes.all_processes() {
+            self.lifecycle.update(None, processes, events);
+        }
+        self.track_ecc(snapshot.ecc);
+
+        let sample = GpuSample {
+            utilization: snapshot.utilization,
+            memory_used: snapshot.memory_used,
+            temperature: snapshot.temperature,
+            process_memory: self.process_memory(),
+            lost: snapshot.lost,
+            throttle_reasons: snapshot.throttle_reasons,
+            ecc: snapshot.ecc,
+            ..self.empty_sample()
+        };
+
+        self.last_graph_update = Some(Instant::now());
+        self.push_sample(&sample);
+        sample
+    }
+
+    /// The GPU's latest readings and processes, in the form sent to agent clients.
+    pub fn snapshot(&self) -> Option<DeviceSnapshot> {
+        let sample = self.last_sample.as_ref()?;
+
+        Some(DeviceSnapshot {
+            index: self.device_index,
+            uuid: self.device_uuid.clone(),
+            name: self.device_name.clone(),
+            memory_total: self.max_memory,
+            utilization: sample.utilization,
+            memory_used: sample.memory_used,
+            temperature: sample.temperature,
+            lost: sample.lost,
+            info: self.info.clone(),
+            throttle_reasons: sample.throttle_reasons,
+            health_events: self.health_events.iter().copied().collect(),
+            ecc: sample.ecc,
+            processes: self
+                .processes
+                .all_processes()
+                .map(|processes| processes.to_vec())
+                .map_err(|err| err.to_string()),
+        })
     }
 
     pub fn memory_graph_mut(&mut self) -> &mut GraphViewerData {
         &mut self.memory_graph
     }
 
+    pub fn usage_graph(&self) -> &GraphViewerData {
+        &self.usage_graph
+    }
+
     pub fn usage_graph_mut(&mut self) -> &mut GraphViewerData {
         &mut self.usage_graph
     }
@@ -120,22 +572,82 @@ impl GpuDeviceMonitor {
         &mut self.temperature_graph
     }
 
-    pub fn graphics_processes_mut(&mut self) -> &mut ProcessTableData {
-        &mut self.graphics_processes
+    pub fn throttle_history(&self) -> &ThrottleHistory {
+        &self.throttle_history
+    }
+
+    /// XID errors on the GPU, oldest first.
+    pub fn health_events(&self) -> &VecDeque<HealthEvent> {
+        &self.health_events
     }
 
-    pub fn compute_processes_mut(&mut self) -> &mut ProcessTableData {
-        &mut self.compute_processes
+    pub fn ecc_history(&self) -> &EccHistory {
+        &self.ecc_history
     }
 
-    pub fn all_processes_mut(&mut self) -> &mut ProcessTableData {
-        &mut self.all_processes
+    /// The latest ECC counts, if the GPU reports them.
+    pub fn ecc(&self) -> Option<&EccCounts> {
+        self.last_ecc.as_ref()
+    }
+
+    /// The last time the ECC counts went up, if they have since gpustat started.
+    pub fn ecc_increase(&self) -> Option<&EccIncrease> {
+        self.ecc_increase.as_ref()
+    }
+
+    fn push_health_event(&mut self, event: HealthEvent) {
+        self.health_events.push_back(event);
+        while self.health_events.len() > MAX_HEALTH_EVENTS {
+            self.health_events.pop_front();
+        }
+    }
+
+    pub fn processes(&self) -> &ProcessTableData {
+        &self.processes
+    }
+
+    pub fn processes_mut(&mut self) -> &mut ProcessTableData {
+        &mut self.processes
     }
 
     pub fn device_name(&self) -> &str {
         &self.device_name
     }
 
+    pub fn source(&self) -> GpuSource {
+        self.source
+    }
+
+    /// The host the GPU is on, if it's on another machine.
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    /// The most recent reading of the GPU.
+    pub fn last_sample(&self) -> Option<&GpuSample> {
+        self.last_sample.as_ref()
+    }
+
+    /// The GPU's name, prefixed with `host:gpuN` if it's on another machine.
+    pub fn label(&self) -> String {
+        match &self.host {
+            Some(_) => format!("{} {}", self.gpu_id(), self.device_name),
+            None => self.device_name.clone(),
+        }
+    }
+
+    /// A short identifier that's unique across hosts: `gpuN`, or `host:gpuN` if remote.
+    pub fn gpu_id(&self) -> String {
+        match &self.host {
+            Some(host) => format!("{}:gpu{}", host, self.device_index),
+            None => format!("gpu{}", self.device_index),
+        }
+    }
+
+    pub fn device_index(&self) -> u32 {
+        self.device_index
+    }
+
     pub fn device_uuid(&self) -> &str {
         &self.device_uuid
     }
@@ -143,4 +655,18 @@ impl GpuDeviceMonitor {
     pub fn max_memory(&self) -> u64 {
         self.max_memory
     }
+
+    pub fn info(&self) -> Option<&DeviceInfo> {
+        self.info.as_ref()
+    }
+
+    /// The GPU's info and ECC counts as plain text, headed by its name and UUID.
+    pub fn info_report(&self) -> Option<String> {
+        let heading = format!("{} ({})", self.label(), self.device_uuid);
+        let mut rows = self.info.as_ref()?.rows();
+        if let Some(ecc) = &self.last_ecc {
+            rows.extend(ecc.rows());
+        }
+        Some(device_info::report(&heading, &rows))
+    }
 }
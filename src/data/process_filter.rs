@@ -0,0 +1,238 @@
+use clap::Args;
+use nvml_wrapper::enums::device::UsedGpuMemory;
+use regex::{Regex, RegexBuilder};
+
+use crate::processes::ProcessData;
+
+/// Narrows down the processes shown in a process table.
+///
+/// The query is matched case-insensitively against the process name, pid, user and
+/// command line, either as a plain substring or as a regular expression.
+#[derive(Default)]
+pub struct ProcessFilter {
+    query: String,
+    use_regex: bool,
+    compiled: Option<Result<Regex, regex::Error>>,
+
+    pub only_current_user: bool,
+    /// Only show processes owned by the user with this name.
+    pub user: Option<String>,
+    pub min_memory_mib: Option<u64>,
+}
+
+impl ProcessFilter {
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn set_query(&mut self, query: impl Into<String>) {
+        self.query = query.into();
+        self.compile();
+    }
+
+    pub fn use_regex(&self) -> bool {
+        self.use_regex
+    }
+
+    pub fn set_use_regex(&mut self, use_regex: bool) {
+        self.use_regex = use_regex;
+        self.compile();
+    }
+
+    /// Returns true if any of the filters would hide processes.
+    pub fn is_active(&self) -> bool {
+        !self.query.is_empty()
+            || self.only_current_user
+            || self.user.is_some()
+            || self.min_memory_mib.is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.set_query("");
+        self.only_current_user = false;
+        self.user = None;
+        self.min_memory_mib = None;
+    }
+
+    /// The reason the query failed to compile, if it's an invalid regex.
+    pub fn regex_error(&self) -> Option<String> {
+        match &self.compiled {
+            Some(Err(err)) => Some(err.to_string()),
+            _ => None,
+        }
+    }
+
+    fn compile(&mut self) {
+        self.compiled = if self.use_regex && !self.query.is_empty() {
+            Some(
+                RegexBuilder::new(&self.query)
+                    .case_insensitive(true)
+                    .build(),
+            )
+        } else {
+            None
+        };
+    }
+
+    pub fn matches(&self, process: &ProcessData) -> bool {
+        if self.only_current_user && !process.is_current_user {
+            return false;
+        }
+        if self
+            .user
+            .as_ref()
+            .is_some_and(|user| process.user.as_ref() != Some(user))
+        {
+            return false;
+        }
+
+        if let Some(min_memory_mib) = self.min_memory_mib {
+            let used_mib = match process.info.used_gpu_memory {
+                UsedGpuMemory::Used(bytes) => bytes / 1024 / 1024,
+                UsedGpuMemory::Unavailable => 0,
+            };
+            if used_mib < min_memory_mib {
+                return false;
+            }
+        }
+
+        if self.query.is_empty() {
+            return true;
+        }
+
+        let pid = process.info.pid.to_string();
+        let user = process.user.as_deref().unwrap_or("");
+        let fields = [process.name.as_str(), pid.as_str(), user, &process.command];
+
+        if self.use_regex {
+            match &self.compiled {
+                Some(Ok(regex)) => fields.iter().any(|field| regex.is_match(field)),
+                // An invalid regex hides everything, so the user notices the error
+                _ => false,
+            }
+        } else {
+            let query = self.query.to_lowercase();
+            fields
+                .iter()
+                .any(|field| field.to_lowercase().contains(&query))
+        }
+    }
+}
+
+/// The process filter as command line options, for the commands that list processes.
+#[derive(Args, Clone, Debug, Default)]
+pub struct ProcessFilterArgs {
+    /// Only include processes whose name, pid, user or command contains this
+    #[arg(long)]
+    filter: Option<String>,
+    /// Match --filter as a case-insensitive regular expression
+    #[arg(long, requires = "filter")]
+    regex: bool,
+    /// Only include processes owned by this user
+    #[arg(long)]
+    user: Option<String>,
+    /// Only include processes owned by the user running gpustat
+    #[arg(long, conflicts_with = "user")]
+    mine: bool,
+    /// Only include processes holding at least this many MiB of GPU memory
+    #[arg(long, value_name = "MIB")]
+    min_memory: Option<u64>,
+}
+
+impl ProcessFilterArgs {
+    pub fn to_filter(&self) -> Result<ProcessFilter, String> {
+        let mut filter = ProcessFilter {
+            only_current_user: self.mine,
+            user: self.user.clone(),
+            min_memory_mib: self.min_memory,
+            ..Default::default()
+        };
+        filter.set_use_regex(self.regex);
+        filter.set_query(self.filter.clone().unwrap_or_default());
+
+        match filter.regex_error() {
+            Some(err) => Err(format!("invalid --filter regex: {}", err)),
+            None => Ok(filter),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nvml_wrapper::struct_wrappers::device::ProcessInfo;
+
+    use super::*;
+    use crate::processes::ProcessKind;
+
+    fn process(pid: u32, name: &str, user: &str, memory_mib: u64) -> ProcessData {
+        ProcessData {
+            info: ProcessInfo {
+                pid,
+                used_gpu_memory: UsedGpuMemory::Used(memory_mib * 1024 * 1024),
+                gpu_instance_id: None,
+                compute_instance_id: None,
+            },
+            name: name.to_string(),
+            command: format!("/usr/bin/{} --serve", name),
+            start_time: None,
+            user: Some(user.to_string()),
+            is_current_user: user == "me",
+            kind: ProcessKind::Compute,
+            gpu_usage: Some(0),
+            idle_for: None,
+        }
+    }
+
+    fn matching(args: ProcessFilterArgs) -> Vec<u32> {
+        let filter = args.to_filter().unwrap();
+        [
+            process(1, "python", "alice", 2048),
+            process(2, "jupyter", "bob", 100),
+            process(3, "Xorg", "me", 50),
+        ]
+        .iter()
+        .filter(|process| filter.matches(process))
+        .map(|process| process.info.pid)
+        .collect()
+    }
+
+    #[test]
+    fn no_options_match_everything() {
+        assert_eq!(matching(ProcessFilterArgs::default()), [1, 2, 3]);
+    }
+
+    #[test]
+    fn filters_by_query_user_and_memory() {
+        let args = |f: fn(&mut ProcessFilterArgs)| {
+            let mut args = ProcessFilterArgs::default();
+            f(&mut args);
+            args
+        };
+
+        assert_eq!(matching(args(|a| a.filter = Some("PYTH".into()))), [1]);
+        assert_eq!(
+            matching(args(|a| a.filter = Some("--serve".into()))),
+            [1, 2, 3]
+        );
+        assert_eq!(matching(args(|a| a.user = Some("bob".into()))), [2]);
+        assert_eq!(matching(args(|a| a.mine = true)), [3]);
+        assert_eq!(matching(args(|a| a.min_memory = Some(100))), [1, 2]);
+        assert_eq!(
+            matching(args(|a| {
+                a.filter = Some("^(python|xorg)$".into());
+                a.regex = true;
+            })),
+            [1, 3]
+        );
+    }
+
+    #[test]
+    fn rejects_an_invalid_regex() {
+        let args = ProcessFilterArgs {
+            filter: Some("(".into()),
+            regex: true,
+            ..Default::default()
+        };
+        assert!(args.to_filter().is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use nvml_wrapper::enums::device::UsedGpuMemory;
+
+use crate::processes::ProcessData;
+
+use super::graph::GraphViewerData;
+
+/// How many samples of history are kept for each process.
+const PROCESS_HISTORY_LEN: usize = 600;
+
+/// How long the history of an exited process is kept around before being evicted.
+const EXITED_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+pub struct ProcessHistory {
+    name: String,
+    /// When the process started, to tell it apart from a later process given its pid.
+    start_time: Option<u64>,
+    user: Option<String>,
+    memory_graph: GraphViewerData,
+    usage_graph: GraphViewerData,
+    last_seen: Instant,
+    /// Whether the process was missing from the latest process list.
+    exited: bool,
+    /// When the process started holding memory without using the GPU.
+    idle_since: Option<Instant>,
+}
+
+impl ProcessHistory {
+    fn new(process: &ProcessData, now: Instant) -> Self {
+        Self {
+            name: process.name.clone(),
+            start_time: process.start_time,
+            user: process.user.clone(),
+            memory_graph: GraphViewerData::with_max_len(PROCESS_HISTORY_LEN),
+            usage_graph: GraphViewerData::with_max_len(PROCESS_HISTORY_LEN),
+            last_seen: now,
+            exited: false,
+            idle_since: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    pub fn memory_graph(&self) -> &GraphViewerData {
+        &self.memory_graph
+    }
+
+    pub fn usage_graph(&self) -> &GraphViewerData {
+        &self.usage_graph
+    }
+
+    /// How long the process has been holding memory while its GPU usage stayed at 0%.
+    pub fn idle_for(&self) -> Option<Duration> {
+        Some(self.last_seen.duration_since(self.idle_since?))
+    }
+
+    /// Whether the process was missing from the latest process list.
+    pub fn has_exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Whether `process` is the one this is the history of, rather than a new process that
+    /// was given the same pid.
+    fn is_of(&self, process: &ProcessData) -> bool {
+        let start_time_matches = match (self.start_time, process.start_time) {
+            (Some(start_time), Some(process_start_time)) => start_time == process_start_time,
+            // The start time isn't known for every process, e.g. from older agents
+            _ => true,
+        };
+        self.name == process.name && start_time_matches
+    }
+}
+
+/// Keeps a bounded history of memory and SM usage for every process seen in a table.
+pub struct ProcessHistoryBank {
+    histories: HashMap<u32, ProcessHistory>,
+}
+
+impl ProcessHistoryBank {
+    pub fn new() -> Self {
+        Self {
+            histories: HashMap::new(),
+        }
+    }
+
+    /// Records one sample for every process, and fills in how long each has been idle.
+    /// Processes that aren't in the list get a gap in their history, and are evicted once
+    /// they've been gone for the grace period.
+    /// A pid that's been reused by another process starts a new history.
+    pub fn update(&mut self, processes: &mut [ProcessData]) {
+        self.update_at(processes, Instant::now());
+    }
+
+    fn update_at(&mut self, processes: &mut [ProcessData], now: Instant) {
+        for process in processes.iter_mut() {
+            let history = self
+                .histories
+                .entry(process.info.pid)
+                .and_modify(|history| {
+                    if !history.is_of(process) {
+                        *history = ProcessHistory::new(process, now);
+                    }
+                })
+                .or_insert_with(|| ProcessHistory::new(process, now));
+
+            let memory = match process.info.used_gpu_memory {
+                UsedGpuMemory::Used(bytes) => Some(bytes as f32),
+                UsedGpuMemory::Unavailable => None,
+            };
+
+            history.memory_graph.update(memory);
+            history
+                .usage_graph
+                .update(process.gpu_usage.map(|usage| usage as f32));
+            history.last_seen = now;
+            history.exited = false;
+
+            // Only a measured 0% counts as idle, while not knowing the usage leaves the
+            // process as it was
+            let holds_memory = memory.is_some_and(|memory| memory > 0.0);
+            match process.gpu_usage {
+                Some(0) if holds_memory => {
+                    history.idle_since.get_or_insert(now);
+                }
+                Some(_) => history.idle_since = None,
+                None if !holds_memory => history.idle_since = None,
+                None => {}
+            }
+            process.idle_for = history.idle_for();
+        }
+
+        for history in self.histories.values_mut() {
+            if history.last_seen != now {
+                history.exited = true;
+                history.memory_graph.update(None);
+                history.usage_graph.update(None);
+            }
+        }
+
+        self.histories
+            .retain(|_, history| now.duration_since(history.last_seen) < EXITED_GRACE_PERIOD);
+    }
+
+    pub fn get(&self, pid: u32) -> Option<&ProcessHistory> {
+        self.histories.get(&pid)
+    }
+
+    /// Every tracked process's pid and history, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &ProcessHistory)> {
+        self.histories.iter().map(|(pid, history)| (*pid, history))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nvml_wrapper::struct_wrappers::device::ProcessInfo;
+
+    use crate::processes::ProcessKind;
+
+    use super::*;
+
+    fn process(pid: u32, name: &str, gpu_usage: Option<u32>) -> ProcessData {
+        ProcessData {
+            info: ProcessInfo {
+                pid,
+                used_gpu_memory: UsedGpuMemory::Used(1024 * 1024 * 1024),
+                gpu_instance_id: None,
+                compute_instance_id: None,
+            },
+            name: name.to_string(),
+            command: String::new(),
+            start_time: Some(1000),
+            user: None,
+            is_current_user: false,
+            kind: ProcessKind::Compute,
+            gpu_usage,
+            idle_for: None,
+        }
+    }
+
+    #[test]
+    fn only_a_measured_zero_is_idle() {
+        let mut bank = ProcessHistoryBank::new();
+        let mut processes = [
+            process(1, "idle", Some(0)),
+            process(2, "busy", Some(50)),
+            process(3, "unknown", None),
+        ];
+        bank.update(&mut processes);
+
+        assert!(processes[0].idle_for.is_some());
+        assert!(processes[1].idle_for.is_none());
+        assert!(processes[2].idle_for.is_none());
+        assert!(!bank.get(3).unwrap().has_exited());
+
+        // Losing track of the usage doesn't reset how long a process has been idle
+        let mut processes = [process(1, "idle", None)];
+        bank.update(&mut processes);
+        assert!(processes[0].idle_for.is_some());
+        assert!(bank.get(2).unwrap().has_exited());
+    }
+
+    #[test]
+    fn exited_processes_are_kept_for_the_grace_period() {
+        let mut bank = ProcessHistoryBank::new();
+        let start = Instant::now();
+        bank.update_at(&mut [process(1, "python", Some(50))], start);
+
+        bank.update_at(
+            &mut [],
+            start + EXITED_GRACE_PERIOD - Duration::from_secs(1),
+        );
+        let history = bank.get(1).unwrap();
+        assert!(history.has_exited());
+        assert_eq!(history.memory_graph().recent(2), [Some(1073741824.0), None]);
+
+        bank.update_at(&mut [], start + EXITED_GRACE_PERIOD);
+        assert!(bank.get(1).is_none());
+    }
+
+    #[test]
+    fn a_reused_pid_starts_a_new_history() {
+        let mut bank = ProcessHistoryBank::new();
+        let start = Instant::now();
+        bank.update_at(&mut [process(1, "python", Some(0))], start);
+        bank.update_at(&mut [], start + Duration::from_secs(1));
+
+        // Another program is given the pid within the grace period
+        let mut processes = [process(1, "bash", Some(0))];
+        bank.update_at(&mut processes, start + Duration::from_secs(2));
+        let history = bank.get(1).unwrap();
+        assert_eq!(history.name(), "bash");
+        assert_eq!(history.memory_graph().recent(3), [Some(1073741824.0)]);
+        assert_eq!(processes[0].idle_for, Some(Duration::ZERO));
+
+        // As is another run of the same program
+        let mut rerun = process(1, "bash", Some(0));
+        rerun.start_time = Some(2000);
+        bank.update_at(&mut [rerun], start + Duration::from_secs(3));
+        let history = bank.get(1).unwrap();
+        assert_eq!(history.memory_graph().recent(2), [Some(1073741824.0)]);
+        assert_eq!(history.idle_for(), Some(Duration::ZERO));
+    }
+}
@@ -1,15 +1,19 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, time::Duration};
 
-use nvml_wrapper::{
-    enums::device::UsedGpuMemory, error::NvmlError, struct_wrappers::device::ProcessInfo, Device,
-};
+use nvml_wrapper::{enums::device::UsedGpuMemory, error::NvmlError, Device};
+
+use crate::processes::{fetch_device_processes, ProcessData, ProcessDataBank};
 
-use crate::processes::{ProcessData, ProcessDataBank};
+use super::{
+    process_filter::ProcessFilter,
+    process_history::{ProcessHistory, ProcessHistoryBank},
+};
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum TableColumn {
     Pid,
     Name,
+    Kind,
     GpuMemory,
     GpuUsage,
 }
@@ -20,13 +24,17 @@ pub enum SortingDirection {
     Descending,
 }
 
-pub struct ProcessTableSorting {
-    pub column: TableColumn,
+/// Which column a table is sorted by, and in which direction.
+pub struct TableSorting<C> {
+    pub column: C,
     pub direction: SortingDirection,
 }
 
-impl ProcessTableSorting {
-    pub fn click(&mut self, column: TableColumn) {
+pub type ProcessTableSorting = TableSorting<TableColumn>;
+
+impl<C: Copy + PartialEq> TableSorting<C> {
+    /// Sorts by the column, or reverses the direction if already sorted by it.
+    pub fn click(&mut self, column: C) {
         if self.column == column {
             self.direction = match self.direction {
                 SortingDirection::Ascending => SortingDirection::Descending,
@@ -41,50 +49,88 @@ impl ProcessTableSorting {
 
 pub struct ProcessTableData {
     sorting: ProcessTableSorting,
+    filter: ProcessFilter,
+    history: ProcessHistoryBank,
+    selected_pid: Option<u32>,
+    /// How long a process has to be idle before it's flagged.
+    idle_threshold: Duration,
     processes: Result<Vec<ProcessData>, NvmlError>,
     last_refresh: Option<std::time::Instant>,
-    process_bank: ProcessDataBank,
-    fetcher: Box<dyn Fn(&Device) -> Result<Vec<ProcessInfo>, NvmlError>>,
 }
 
 impl ProcessTableData {
-    pub fn new(fetcher: Box<dyn Fn(&Device) -> Result<Vec<ProcessInfo>, NvmlError>>) -> Self {
+    pub fn new(idle_threshold: Duration) -> Self {
         Self {
             sorting: ProcessTableSorting {
                 column: TableColumn::GpuMemory,
                 direction: SortingDirection::Descending,
             },
+            filter: ProcessFilter::default(),
+            history: ProcessHistoryBank::new(),
+            selected_pid: None,
+            idle_threshold,
             processes: Err(NvmlError::Unknown),
             last_refresh: None,
-            process_bank: ProcessDataBank::new(),
-            fetcher,
         }
     }
 
-    fn fetch_last_process_array(&mut self, device: &Device) -> Result<Vec<ProcessData>, NvmlError> {
-        let processes = (self.fetcher)(device)?;
+    fn fetch_last_process_array(
+        device: &Device,
+        process_bank: &mut ProcessDataBank,
+    ) -> Result<Vec<ProcessData>, NvmlError> {
+        let processes = fetch_device_processes(device)?;
 
-        let utilization_list = device.process_utilization_stats(None).unwrap_or_default();
+        let utilization_list = match device.process_utilization_stats(None) {
+            Ok(utilization_list) => Some(utilization_list),
+            // NVML reports having no samples at all as an error
+            Err(NvmlError::NotFound) => Some(Vec::new()),
+            Err(_) => None,
+        };
 
-        Ok(self
-            .process_bank
-            .map_process_list(processes, utilization_list))
+        Ok(process_bank.map_process_list(processes, utilization_list))
     }
 
-    pub fn update(&mut self, device: &Device) {
+    /// Refreshes the process list if it's due, returning true if it was refreshed.
+    pub fn update(&mut self, device: &Device, process_bank: &mut ProcessDataBank) -> bool {
         // Refresh the process data every 1 second
         if self.last_refresh.is_none() || self.last_refresh.unwrap().elapsed().as_millis() > 1000 {
-            self.processes = self.fetch_last_process_array(device);
-            self.last_refresh = Some(std::time::Instant::now());
+            self.set_processes(Self::fetch_last_process_array(device, process_bank));
+            true
+        } else {
+            false
         }
     }
 
+    /// Replaces the process list with one fetched elsewhere, e.g. from a remote agent.
+    pub fn set_processes(&mut self, processes: Result<Vec<ProcessData>, NvmlError>) {
+        self.processes = processes;
+        if let Ok(processes) = &mut self.processes {
+            self.history.update(processes);
+        }
+        self.last_refresh = Some(std::time::Instant::now());
+    }
+
+    /// When the process list was last refreshed.
+    pub fn last_refresh(&self) -> Option<std::time::Instant> {
+        self.last_refresh
+    }
+
+    /// The latest process list, unfiltered and unsorted.
+    pub fn all_processes(&self) -> Result<&[ProcessData], &NvmlError> {
+        self.processes.as_deref()
+    }
+
     fn get_processes_sorted_by(
         &self,
         by: impl Fn(&ProcessData, &ProcessData) -> Ordering,
         direction: SortingDirection,
     ) -> Result<Vec<&ProcessData>, &NvmlError> {
-        let mut processes = self.processes.as_ref()?.iter().collect::<Vec<_>>();
+        let mut processes = self
+            .processes
+            .as_ref()?
+            .iter()
+            .filter(|process| self.filter.matches(process))
+            .collect::<Vec<_>>();
         processes.sort_by(|a, b| {
             if direction == SortingDirection::Ascending {
                 by(a, b)
@@ -104,9 +150,13 @@ impl ProcessTableData {
             TableColumn::Name => {
                 self.get_processes_sorted_by(|a, b| a.name.cmp(&b.name), self.sorting.direction)
             }
+            TableColumn::Kind => self.get_processes_sorted_by(
+                |a, b| a.kind.label().cmp(b.kind.label()),
+                self.sorting.direction,
+            ),
             TableColumn::GpuMemory => self.get_processes_sorted_by(
                 |a, b| match (&a.info.used_gpu_memory, &b.info.used_gpu_memory) {
-                    (UsedGpuMemory::Used(a), UsedGpuMemory::Used(b)) => a.cmp(&b),
+                    (UsedGpuMemory::Used(a), UsedGpuMemory::Used(b)) => a.cmp(b),
                     (UsedGpuMemory::Used(_), UsedGpuMemory::Unavailable) => Ordering::Less,
                     (UsedGpuMemory::Unavailable, UsedGpuMemory::Used(_)) => Ordering::Greater,
                     (UsedGpuMemory::Unavailable, UsedGpuMemory::Unavailable) => Ordering::Equal,
@@ -114,20 +164,68 @@ impl ProcessTableData {
                 self.sorting.direction,
             ),
             TableColumn::GpuUsage => self.get_processes_sorted_by(
-                |a, b| {
-                    a.gpu_usage
-                        .partial_cmp(&b.gpu_usage)
-                        .unwrap_or(Ordering::Equal)
-                },
+                |a, b| a.gpu_usage.cmp(&b.gpu_usage),
                 self.sorting.direction,
             ),
         }
     }
 
+    pub fn sorting(&self) -> &ProcessTableSorting {
+        &self.sorting
+    }
+
     pub fn sorting_mut(&mut self) -> &mut ProcessTableSorting {
         &mut self.sorting
     }
 
+    pub fn filter(&self) -> &ProcessFilter {
+        &self.filter
+    }
+
+    pub fn filter_mut(&mut self) -> &mut ProcessFilter {
+        &mut self.filter
+    }
+
+    /// How long the process has been idle for, if it's been idle long enough to be flagged.
+    pub fn flagged_idle(&self, process: &ProcessData) -> Option<Duration> {
+        process
+            .idle_for
+            .filter(|idle_for| *idle_for >= self.idle_threshold)
+    }
+
+    pub fn history(&self, pid: u32) -> Option<&ProcessHistory> {
+        self.history.get(pid)
+    }
+
+    /// The history of every process seen recently, including ones that have exited.
+    pub fn histories(&self) -> impl Iterator<Item = (u32, &ProcessHistory)> {
+        self.history.iter()
+    }
+
+    pub fn selected_pid(&self) -> Option<u32> {
+        self.selected_pid
+    }
+
+    /// Selects the given process, or deselects it if it's already selected.
+    pub fn toggle_selected_pid(&mut self, pid: u32) {
+        if self.selected_pid == Some(pid) {
+            self.selected_pid = None;
+        } else {
+            self.selected_pid = Some(pid);
+        }
+    }
+
+    pub fn set_selected_pid(&mut self, pid: Option<u32>) {
+        self.selected_pid = pid;
+    }
+
+    /// The history of the selected process, if it's still being tracked.
+    pub fn selected_history(&self) -> Option<(u32, &ProcessHistory)> {
+        let pid = self.selected_pid?;
+        Some((pid, self.history.get(pid)?))
+    }
+
+    /// All processes matching the current filter, in the current sort order.
     pub fn processes(&self) -> Result<Vec<&ProcessData>, &NvmlError> {
         self.get_processes_sorted()
     }
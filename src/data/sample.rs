@@ -0,0 +1,41 @@
+use std::time::Instant;
+
+use super::{
+    ecc::{EccCounts, EccIncrease},
+    throttle::ThrottleReasons,
+};
+
+/// A single reading of a GPU's state, taken every time its graphs are updated.
+#[derive(Clone, Debug)]
+pub struct GpuSample {
+    pub time: Instant,
+    pub device_index: u32,
+    pub device_uuid: String,
+    pub device_name: String,
+    /// Utilization as a percentage, if it could be read.
+    pub utilization: Option<f32>,
+    pub memory_used: Option<u64>,
+    pub memory_total: u64,
+    /// Temperature in °C, if it could be read.
+    pub temperature: Option<f32>,
+    /// The total memory held by the processes on the GPU, as of the last process refresh.
+    pub process_memory: u64,
+    /// Whether the device has stopped responding.
+    pub lost: bool,
+    /// Why the clocks are being held down, if that could be read.
+    pub throttle_reasons: Option<ThrottleReasons>,
+    /// Memory error counts, if the GPU reports them.
+    pub ecc: Option<EccCounts>,
+    /// The last time the memory error counts went up, which alerts are raised on.
+    pub ecc_increase: Option<EccIncrease>,
+}
+
+impl GpuSample {
+    pub fn memory_percent(&self) -> Option<f32> {
+        let used = self.memory_used?;
+        if self.memory_total == 0 {
+            return None;
+        }
+        Some(used as f32 / self.memory_total as f32 * 100.0)
+    }
+}
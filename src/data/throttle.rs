@@ -0,0 +1,271 @@
+//! Why a GPU's clocks are being held down, sampled alongside its other readings.
+
+use std::collections::VecDeque;
+
+use nvml_wrapper::bitmasks::device::ThrottleReasons as NvmlThrottleReasons;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ThrottleReason {
+    /// The driver is keeping the power draw under the power limit.
+    PowerCap,
+    /// The GPU or its memory is above its maximum operating temperature.
+    SwThermal,
+    /// The GPU is too hot and the hardware has at least halved the clocks.
+    HwThermal,
+    /// The hardware has at least halved the clocks, e.g. because of the temperature or the
+    /// power supply.
+    HwSlowdown,
+    /// The power supply has asserted the external power brake.
+    HwPowerBrake,
+    /// Another GPU in the same sync boost group is holding the clocks down.
+    SyncBoost,
+    /// The clocks are limited by the applications clocks setting.
+    ApplicationClocks,
+    /// The clocks are limited by the display clocks setting.
+    DisplayClocks,
+}
+
+impl ThrottleReason {
+    pub const ALL: [ThrottleReason; 8] = [
+        ThrottleReason::PowerCap,
+        ThrottleReason::SwThermal,
+        ThrottleReason::HwThermal,
+        ThrottleReason::HwSlowdown,
+        ThrottleReason::HwPowerBrake,
+        ThrottleReason::SyncBoost,
+        ThrottleReason::ApplicationClocks,
+        ThrottleReason::DisplayClocks,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ThrottleReason::PowerCap => "power cap",
+            ThrottleReason::SwThermal => "thermal slowdown",
+            ThrottleReason::HwThermal => "HW thermal slowdown",
+            ThrottleReason::HwSlowdown => "HW slowdown",
+            ThrottleReason::HwPowerBrake => "HW power brake",
+            ThrottleReason::SyncBoost => "sync boost",
+            ThrottleReason::ApplicationClocks => "applications clocks",
+            ThrottleReason::DisplayClocks => "display clocks",
+        }
+    }
+
+    fn nvml_flag(&self) -> NvmlThrottleReasons {
+        match self {
+            ThrottleReason::PowerCap => NvmlThrottleReasons::SW_POWER_CAP,
+            ThrottleReason::SwThermal => NvmlThrottleReasons::SW_THERMAL_SLOWDOWN,
+            ThrottleReason::HwThermal => NvmlThrottleReasons::HW_THERMAL_SLOWDOWN,
+            ThrottleReason::HwSlowdown => NvmlThrottleReasons::HW_SLOWDOWN,
+            ThrottleReason::HwPowerBrake => NvmlThrottleReasons::HW_POWER_BRAKE_SLOWDOWN,
+            ThrottleReason::SyncBoost => NvmlThrottleReasons::SYNC_BOOST,
+            ThrottleReason::ApplicationClocks => NvmlThrottleReasons::APPLICATIONS_CLOCKS_SETTING,
+            ThrottleReason::DisplayClocks => NvmlThrottleReasons::DISPLAY_CLOCK_SETTING,
+        }
+    }
+
+    fn bit(&self) -> u16 {
+        1 << Self::ALL.iter().position(|reason| reason == self).unwrap()
+    }
+}
+
+/// The reasons a GPU is throttled at one point in time, which is empty if it isn't. An idle
+/// GPU lowering its clocks doesn't count as being throttled.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(from = "Vec<ThrottleReason>", into = "Vec<ThrottleReason>")]
+pub struct ThrottleReasons(u16);
+
+impl ThrottleReasons {
+    pub fn from_nvml(reasons: NvmlThrottleReasons) -> Self {
+        ThrottleReason::ALL
+            .iter()
+            .filter(|reason| reasons.contains(reason.nvml_flag()))
+            .copied()
+            .collect()
+    }
+
+    pub fn contains(&self, reason: ThrottleReason) -> bool {
+        self.0 & reason.bit() != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ThrottleReason> + '_ {
+        ThrottleReason::ALL
+            .into_iter()
+            .filter(|reason| self.contains(*reason))
+    }
+
+    /// e.g. "Throttled because of power cap and HW slowdown", or "Not throttled".
+    pub fn describe(&self) -> String {
+        let labels = self.iter().map(|reason| reason.label()).collect::<Vec<_>>();
+        match labels.split_last() {
+            None => "Not throttled".to_string(),
+            Some((last, [])) => format!("Throttled because of {}", last),
+            Some((last, rest)) => format!("Throttled because of {} and {}", rest.join(", "), last),
+        }
+    }
+}
+
+impl FromIterator<ThrottleReason> for ThrottleReasons {
+    fn from_iter<I: IntoIterator<Item = ThrottleReason>>(iter: I) -> Self {
+        Self(iter.into_iter().fold(0, |bits, reason| bits | reason.bit()))
+    }
+}
+
+impl From<Vec<ThrottleReason>> for ThrottleReasons {
+    fn from(reasons: Vec<ThrottleReason>) -> Self {
+        reasons.into_iter().collect()
+    }
+}
+
+impl From<ThrottleReasons> for Vec<ThrottleReason> {
+    fn from(reasons: ThrottleReasons) -> Self {
+        reasons.iter().collect()
+    }
+}
+
+/// The throttle reasons of every sample, kept in step with the graphs so that they can be
+/// drawn underneath them.
+pub struct ThrottleHistory {
+    /// The reasons at each sample, or `None` if they couldn't be read, newest first.
+    historical: VecDeque<Option<ThrottleReasons>>,
+    max_len: usize,
+}
+
+impl ThrottleHistory {
+    pub fn new() -> Self {
+        Self {
+            historical: VecDeque::new(),
+            max_len: 5000,
+        }
+    }
+
+    pub fn update(&mut self, reasons: Option<ThrottleReasons>) {
+        self.historical.push_front(reasons);
+        self.historical.truncate(self.max_len);
+    }
+
+    /// The reasons `index` samples back.
+    pub fn get_at(&self, index: usize) -> Option<ThrottleReasons> {
+        self.historical.get(index).copied().flatten()
+    }
+
+    /// The latest reasons, if they could be read.
+    pub fn current(&self) -> Option<ThrottleReasons> {
+        self.get_at(0)
+    }
+
+    /// Whether any of the newest `count` samples have throttle reasons, i.e. whether the GPU
+    /// reports them at all.
+    pub fn is_known(&self, count: usize) -> bool {
+        self.historical.iter().take(count).any(Option::is_some)
+    }
+
+    /// Every reason seen in the newest `count` samples, in the order of [`ThrottleReason::ALL`].
+    pub fn reasons_seen(&self, count: usize) -> Vec<ThrottleReason> {
+        let seen = self
+            .historical
+            .iter()
+            .take(count)
+            .flatten()
+            .flat_map(|reasons| reasons.iter())
+            .collect::<ThrottleReasons>();
+        seen.iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_nvml_reasons_ignoring_idle() {
+        let reasons = ThrottleReasons::from_nvml(
+            NvmlThrottleReasons::GPU_IDLE
+                | NvmlThrottleReasons::SW_POWER_CAP
+                | NvmlThrottleReasons::HW_THERMAL_SLOWDOWN,
+        );
+        assert_eq!(
+            reasons.iter().collect::<Vec<_>>(),
+            [ThrottleReason::PowerCap, ThrottleReason::HwThermal]
+        );
+        assert!(!reasons.contains(ThrottleReason::SwThermal));
+
+        assert!(ThrottleReasons::from_nvml(NvmlThrottleReasons::GPU_IDLE).is_empty());
+        assert!(ThrottleReasons::from_nvml(NvmlThrottleReasons::NONE).is_empty());
+
+        // Every reason has its own flag and bit
+        for reason in ThrottleReason::ALL {
+            let decoded = ThrottleReasons::from_nvml(reason.nvml_flag());
+            assert_eq!(decoded.iter().collect::<Vec<_>>(), [reason]);
+        }
+    }
+
+    #[test]
+    fn describes_the_reasons() {
+        let describe = |reasons: &[ThrottleReason]| {
+            reasons
+                .iter()
+                .copied()
+                .collect::<ThrottleReasons>()
+                .describe()
+        };
+
+        assert_eq!(describe(&[]), "Not throttled");
+        assert_eq!(
+            describe(&[ThrottleReason::PowerCap]),
+            "Throttled because of power cap"
+        );
+        assert_eq!(
+            describe(&[ThrottleReason::HwSlowdown, ThrottleReason::PowerCap]),
+            "Throttled because of power cap and HW slowdown"
+        );
+        assert_eq!(
+            describe(&[
+                ThrottleReason::PowerCap,
+                ThrottleReason::SwThermal,
+                ThrottleReason::DisplayClocks
+            ]),
+            "Throttled because of power cap, thermal slowdown and display clocks"
+        );
+    }
+
+    #[test]
+    fn are_sent_as_a_list_of_names() {
+        let reasons = [ThrottleReason::SwThermal, ThrottleReason::SyncBoost]
+            .into_iter()
+            .collect::<ThrottleReasons>();
+        let json = serde_json::to_string(&reasons).unwrap();
+        assert_eq!(json, r#"["sw_thermal","sync_boost"]"#);
+        assert_eq!(
+            serde_json::from_str::<ThrottleReasons>(&json).unwrap(),
+            reasons
+        );
+    }
+
+    #[test]
+    fn history_collects_the_reasons_seen_recently() {
+        let mut history = ThrottleHistory::new();
+        history.update(Some([ThrottleReason::HwSlowdown].into_iter().collect()));
+        history.update(None);
+        history.update(Some([ThrottleReason::PowerCap].into_iter().collect()));
+        history.update(Some(ThrottleReasons::default()));
+
+        assert_eq!(history.current(), Some(ThrottleReasons::default()));
+        assert_eq!(history.get_at(2), None);
+        assert_eq!(history.reasons_seen(2), [ThrottleReason::PowerCap]);
+        assert_eq!(
+            history.reasons_seen(10),
+            [ThrottleReason::PowerCap, ThrottleReason::HwSlowdown]
+        );
+        assert!(history.is_known(1));
+
+        let mut unknown = ThrottleHistory::new();
+        unknown.update(None);
+        assert!(!unknown.is_known(10));
+    }
+}
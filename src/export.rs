@@ -0,0 +1,505 @@
+//! Writing recordings out as CSV or Parquet, for analysis in tools like pandas.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+
+use crate::{recording::Recording, utils::quote_field};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    /// Only available when built with the `parquet` feature.
+    Parquet,
+}
+
+impl ExportFormat {
+    /// Picks the format from a file's extension, defaulting to CSV.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("parquet") => ExportFormat::Parquet,
+            _ => ExportFormat::Csv,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, ValueEnum)]
+pub enum ExportLayout {
+    /// One row per timestamp, GPU (or process) and metric.
+    #[default]
+    Long,
+    /// One row per timestamp and GPU (or process), with a column for each metric.
+    Wide,
+}
+
+enum ColumnValues {
+    /// Unix time in seconds.
+    Time(Vec<f64>),
+    Float(Vec<Option<f64>>),
+    UInt(Vec<Option<u64>>),
+    Text(Vec<Option<String>>),
+}
+
+struct Column {
+    name: &'static str,
+    values: ColumnValues,
+}
+
+/// A table of columns of equal length, the common ground between the output formats.
+struct Table {
+    columns: Vec<Column>,
+}
+
+impl Table {
+    fn row_count(&self) -> usize {
+        match self.columns.first().map(|column| &column.values) {
+            Some(ColumnValues::Time(values)) => values.len(),
+            Some(ColumnValues::Float(values)) => values.len(),
+            Some(ColumnValues::UInt(values)) => values.len(),
+            Some(ColumnValues::Text(values)) => values.len(),
+            None => 0,
+        }
+    }
+}
+
+fn gpu_table(recording: &Recording, layout: ExportLayout) -> Table {
+    let gpus = &recording.gpus;
+
+    match layout {
+        ExportLayout::Wide => Table {
+            columns: vec![
+                Column {
+                    name: "time",
+                    values: ColumnValues::Time(gpus.iter().map(|r| r.time).collect()),
+                },
+                Column {
+                    name: "gpu",
+                    values: ColumnValues::Text(gpus.iter().map(|r| Some(r.gpu.clone())).collect()),
+                },
+                Column {
+                    name: "name",
+                    values: ColumnValues::Text(gpus.iter().map(|r| Some(r.name.clone())).collect()),
+                },
+                Column {
+                    name: "uuid",
+                    values: ColumnValues::Text(gpus.iter().map(|r| Some(r.uuid.clone())).collect()),
+                },
+                Column {
+                    name: "utilization_percent",
+                    values: ColumnValues::Float(
+                        gpus.iter().map(|r| r.utilization.map(f64::from)).collect(),
+                    ),
+                },
+                Column {
+                    name: "memory_used_bytes",
+                    values: ColumnValues::UInt(gpus.iter().map(|r| r.memory_used).collect()),
+                },
+                Column {
+                    name: "memory_total_bytes",
+                    values: ColumnValues::UInt(gpus.iter().map(|r| Some(r.memory_total)).collect()),
+                },
+                Column {
+                    name: "temperature_celsius",
+                    values: ColumnValues::Float(
+                        gpus.iter().map(|r| r.temperature.map(f64::from)).collect(),
+                    ),
+                },
+            ],
+        },
+        ExportLayout::Long => {
+            let mut rows = LongRows::default();
+            for record in gpus {
+                let metrics = [
+                    ("utilization_percent", record.utilization.map(f64::from)),
+                    ("memory_used_bytes", record.memory_used.map(|m| m as f64)),
+                    ("memory_total_bytes", Some(record.memory_total as f64)),
+                    ("temperature_celsius", record.temperature.map(f64::from)),
+                ];
+                for (metric, value) in metrics {
+                    // Missing readings are left out rather than written as empty rows
+                    let Some(value) = value else {
+                        continue;
+                    };
+                    rows.time.push(record.time);
+                    rows.gpu.push(Some(record.gpu.clone()));
+                    rows.name.push(Some(record.name.clone()));
+                    rows.metric.push(Some(metric.to_string()));
+                    rows.value.push(Some(value));
+                }
+            }
+
+            Table {
+                columns: vec![
+                    Column {
+                        name: "time",
+                        values: ColumnValues::Time(rows.time),
+                    },
+                    Column {
+                        name: "gpu",
+                        values: ColumnValues::Text(rows.gpu),
+                    },
+                    Column {
+                        name: "name",
+                        values: ColumnValues::Text(rows.name),
+                    },
+                    Column {
+                        name: "metric",
+                        values: ColumnValues::Text(rows.metric),
+                    },
+                    Column {
+                        name: "value",
+                        values: ColumnValues::Float(rows.value),
+                    },
+                ],
+            }
+        }
+    }
+}
+
+fn process_table(recording: &Recording, layout: ExportLayout) -> Table {
+    let processes = &recording.processes;
+
+    match layout {
+        ExportLayout::Wide => Table {
+            columns: vec![
+                Column {
+                    name: "time",
+                    values: ColumnValues::Time(processes.iter().map(|r| r.time).collect()),
+                },
+                Column {
+                    name: "gpu",
+                    values: ColumnValues::Text(
+                        processes.iter().map(|r| Some(r.gpu.clone())).collect(),
+                    ),
+                },
+                Column {
+                    name: "pid",
+                    values: ColumnValues::UInt(
+                        processes.iter().map(|r| Some(r.pid as u64)).collect(),
+                    ),
+                },
+                Column {
+                    name: "name",
+                    values: ColumnValues::Text(
+                        processes.iter().map(|r| Some(r.name.clone())).collect(),
+                    ),
+                },
+                Column {
+                    name: "user",
+                    values: ColumnValues::Text(processes.iter().map(|r| r.user.clone()).collect()),
+                },
+                Column {
+                    name: "memory_used_bytes",
+                    values: ColumnValues::UInt(processes.iter().map(|r| r.memory_used).collect()),
+                },
+                Column {
+                    name: "gpu_usage_percent",
+                    values: ColumnValues::Float(
+                        processes
+                            .iter()
+                            .map(|r| r.gpu_usage.map(f64::from))
+                            .collect(),
+                    ),
+                },
+            ],
+        },
+        ExportLayout::Long => {
+            let mut rows = LongRows::default();
+            let mut pids = Vec::new();
+            let mut users = Vec::new();
+            for record in processes {
+                let metrics = [
+                    ("memory_used_bytes", record.memory_used.map(|m| m as f64)),
+                    ("gpu_usage_percent", record.gpu_usage.map(f64::from)),
+                ];
+                for (metric, value) in metrics {
+                    let Some(value) = value else {
+                        continue;
+                    };
+                    rows.time.push(record.time);
+                    rows.gpu.push(Some(record.gpu.clone()));
+                    rows.name.push(Some(record.name.clone()));
+                    rows.metric.push(Some(metric.to_string()));
+                    rows.value.push(Some(value));
+                    pids.push(Some(record.pid as u64));
+                    users.push(record.user.clone());
+                }
+            }
+
+            Table {
+                columns: vec![
+                    Column {
+                        name: "time",
+                        values: ColumnValues::Time(rows.time),
+                    },
+                    Column {
+                        name: "gpu",
+                        values: ColumnValues::Text(rows.gpu),
+                    },
+                    Column {
+                        name: "pid",
+                        values: ColumnValues::UInt(pids),
+                    },
+                    Column {
+                        name: "name",
+                        values: ColumnValues::Text(rows.name),
+                    },
+                    Column {
+                        name: "user",
+                        values: ColumnValues::Text(users),
+                    },
+                    Column {
+                        name: "metric",
+                        values: ColumnValues::Text(rows.metric),
+                    },
+                    Column {
+                        name: "value",
+                        values: ColumnValues::Float(rows.value),
+                    },
+                ],
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct LongRows {
+    time: Vec<f64>,
+    gpu: Vec<Option<String>>,
+    name: Vec<Option<String>>,
+    metric: Vec<Option<String>>,
+    value: Vec<Option<f64>>,
+}
+
+fn write_csv(table: &Table, path: &Path) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write_csv_to(table, &mut file)?;
+    file.flush()
+}
+
+fn write_csv_to(table: &Table, file: &mut impl Write) -> io::Result<()> {
+    let header = table
+        .columns
+        .iter()
+        .map(|column| column.name)
+        .collect::<Vec<_>>();
+    writeln!(file, "{}", header.join(","))?;
+
+    for row in 0..table.row_count() {
+        let fields = table
+            .columns
+            .iter()
+            .map(|column| match &column.values {
+                ColumnValues::Time(values) => format!("{:.3}", values[row]),
+                ColumnValues::Float(values) => {
+                    values[row].map(|v| v.to_string()).unwrap_or_default()
+                }
+                ColumnValues::UInt(values) => {
+                    values[row].map(|v| v.to_string()).unwrap_or_default()
+                }
+                ColumnValues::Text(values) => values[row]
+                    .as_deref()
+                    .map(|text| quote_field(text, ','))
+                    .unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+        writeln!(file, "{}", fields.join(","))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet(table: &Table, path: &Path) -> io::Result<()> {
+    use std::sync::Arc;
+
+    use arrow_array::{
+        ArrayRef, Float64Array, RecordBatch, StringArray, TimestampMillisecondArray, UInt64Array,
+    };
+    use arrow_schema::{DataType, Field, Schema, TimeUnit};
+    use parquet::arrow::ArrowWriter;
+
+    let to_io_error = |err: &dyn std::fmt::Display| io::Error::other(err.to_string());
+
+    let mut fields = Vec::new();
+    let mut arrays = Vec::<ArrayRef>::new();
+    for column in &table.columns {
+        let (data_type, array): (DataType, ArrayRef) = match &column.values {
+            ColumnValues::Time(values) => (
+                DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+                Arc::new(
+                    TimestampMillisecondArray::from(
+                        values
+                            .iter()
+                            .map(|time| (time * 1000.0) as i64)
+                            .collect::<Vec<_>>(),
+                    )
+                    .with_timezone("UTC"),
+                ),
+            ),
+            ColumnValues::Float(values) => (
+                DataType::Float64,
+                Arc::new(Float64Array::from(values.clone())),
+            ),
+            ColumnValues::UInt(values) => (
+                DataType::UInt64,
+                Arc::new(UInt64Array::from(values.clone())),
+            ),
+            ColumnValues::Text(values) => {
+                (DataType::Utf8, Arc::new(StringArray::from(values.clone())))
+            }
+        };
+        fields.push(Field::new(column.name, data_type, true));
+        arrays.push(array);
+    }
+
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(schema.clone(), arrays).map_err(|err| to_io_error(&err))?;
+
+    let mut writer =
+        ArrowWriter::try_new(File::create(path)?, schema, None).map_err(|err| to_io_error(&err))?;
+    writer.write(&batch).map_err(|err| to_io_error(&err))?;
+    writer.close().map_err(|err| to_io_error(&err))?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(_table: &Table, _path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "gpustat was built without Parquet support, rebuild it with `--features parquet`",
+    ))
+}
+
+/// The path the process samples are written to, next to the GPU samples at `path`.
+fn processes_path(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}-processes.{}", stem, extension.to_string_lossy()),
+        None => format!("{}-processes", stem),
+    };
+    path.with_file_name(file_name)
+}
+
+/// Writes the GPU samples to `path` and the process samples to a `-processes` file next to
+/// it, returning the paths written.
+pub fn export_recording(
+    recording: &Recording,
+    path: &Path,
+    format: ExportFormat,
+    layout: ExportLayout,
+) -> io::Result<Vec<PathBuf>> {
+    let outputs = [
+        (gpu_table(recording, layout), path.to_path_buf()),
+        (process_table(recording, layout), processes_path(path)),
+    ];
+
+    let mut written = Vec::new();
+    for (table, path) in outputs {
+        match format {
+            ExportFormat::Csv => write_csv(&table, &path)?,
+            ExportFormat::Parquet => write_parquet(&table, &path)?,
+        }
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Converts a recording file written by `gpustat record`.
+pub fn run_export(
+    input: &Path,
+    output: &Path,
+    format: Option<ExportFormat>,
+    layout: ExportLayout,
+) -> io::Result<()> {
+    let recording = Recording::read(input)?;
+    let format = format.unwrap_or_else(|| ExportFormat::from_path(output));
+
+    for path in export_recording(&recording, output, format, layout)? {
+        eprintln!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORDING: &str = r#"
+{"type":"gpu","time":100.0,"gpu":"gpu0","name":"Test GPU","uuid":"GPU-0","utilization":50.0,"memory_used":1024,"memory_total":4096,"temperature":60.0}
+{"type":"gpu","time":100.5,"gpu":"trainer:gpu1","name":"Test, GPU","uuid":"GPU-1","utilization":null,"memory_used":2048,"memory_total":4096,"temperature":null}
+{"type":"process","time":100.25,"gpu":"gpu0","pid":42,"name":"python \"train\"","user":"alice","memory_used":512,"gpu_usage":25.0}
+{"type":"process","time":100.75,"gpu":"gpu0","pid":43,"name":"idle","user":null,"memory_used":256,"gpu_usage":null}
+"#;
+
+    fn csv(table: &Table) -> String {
+        let mut output = Vec::new();
+        write_csv_to(table, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn exports_the_long_layout() {
+        let recording = Recording::read_from(RECORDING.as_bytes()).unwrap();
+
+        assert_eq!(
+            csv(&gpu_table(&recording, ExportLayout::Long)),
+            "time,gpu,name,metric,value\n\
+             100.000,gpu0,Test GPU,utilization_percent,50\n\
+             100.000,gpu0,Test GPU,memory_used_bytes,1024\n\
+             100.000,gpu0,Test GPU,memory_total_bytes,4096\n\
+             100.000,gpu0,Test GPU,temperature_celsius,60\n\
+             100.500,trainer:gpu1,\"Test, GPU\",memory_used_bytes,2048\n\
+             100.500,trainer:gpu1,\"Test, GPU\",memory_total_bytes,4096\n"
+        );
+        assert_eq!(
+            csv(&process_table(&recording, ExportLayout::Long)),
+            "time,gpu,pid,name,user,metric,value\n\
+             100.250,gpu0,42,\"python \"\"train\"\"\",alice,memory_used_bytes,512\n\
+             100.250,gpu0,42,\"python \"\"train\"\"\",alice,gpu_usage_percent,25\n\
+             100.750,gpu0,43,idle,,memory_used_bytes,256\n"
+        );
+    }
+
+    #[test]
+    fn exports_the_wide_layout() {
+        let recording = Recording::read_from(RECORDING.as_bytes()).unwrap();
+
+        assert_eq!(
+            csv(&gpu_table(&recording, ExportLayout::Wide)),
+            "time,gpu,name,uuid,utilization_percent,memory_used_bytes,memory_total_bytes,\
+             temperature_celsius\n\
+             100.000,gpu0,Test GPU,GPU-0,50,1024,4096,60\n\
+             100.500,trainer:gpu1,\"Test, GPU\",GPU-1,,2048,4096,\n"
+        );
+        assert_eq!(
+            csv(&process_table(&recording, ExportLayout::Wide)),
+            "time,gpu,pid,name,user,memory_used_bytes,gpu_usage_percent\n\
+             100.250,gpu0,42,\"python \"\"train\"\"\",alice,512,25\n\
+             100.750,gpu0,43,idle,,256,\n"
+        );
+    }
+
+    #[test]
+    fn names_the_processes_file_after_the_output() {
+        assert_eq!(
+            processes_path(Path::new("out/run.csv")),
+            Path::new("out/run-processes.csv")
+        );
+        assert_eq!(processes_path(Path::new("run")), Path::new("run-processes"));
+    }
+}
@@ -0,0 +1,22 @@
+use eframe::egui::{self, RichText};
+
+use crate::{data::alerts::AlertEngine, utils::format_elapsed};
+
+pub fn render_alert_banner(ui: &mut egui::Ui, alerts: &AlertEngine) {
+    let mut firing = alerts.firing().collect::<Vec<_>>();
+    firing.sort_by_key(|alert| (alert.device_index, alert.since));
+
+    ui.add_space(4.0);
+    for alert in firing {
+        let text = format!(
+            "⚠ GPU {} {}: {} ({}, {})",
+            alert.device_index,
+            alert.rule,
+            alert.message,
+            alert.device_name,
+            format_elapsed(alert.since.elapsed())
+        );
+        ui.label(RichText::new(text).color(ui.visuals().error_fg_color));
+    }
+    ui.add_space(4.0);
+}
@@ -0,0 +1,154 @@
+use eframe::egui::{self, Label, RichText, Sense};
+use egui_extras::{Column, TableBuilder};
+
+use crate::{
+    data::cluster::{ClusterColumn, ClusterRow, ClusterTableData, HostHealth},
+    remote::client::ConnectionStatus,
+    utils::{bytes_to_mib_gib, format_duration, format_elapsed},
+};
+
+use super::process_table::{draw_table_cell, sortable_column_head};
+
+/// Renders the connection health of every host and a table of every GPU across them,
+/// returning the GPU whose row was clicked, to drill down into.
+pub fn render_cluster_view(
+    ui: &mut egui::Ui,
+    table: &mut ClusterTableData,
+    mut rows: Vec<ClusterRow>,
+    hosts: &[HostHealth],
+) -> Option<usize> {
+    render_host_health(ui, hosts);
+    ui.add_space(8.0);
+
+    table.sort(&mut rows);
+
+    let old_spacing = ui.style_mut().spacing.item_spacing.x;
+    ui.style_mut().spacing.item_spacing.x = 0.0;
+    ui.style_mut().interaction.selectable_labels = false;
+
+    let mut builder = TableBuilder::new(ui)
+        .striped(true)
+        .sense(Sense::click())
+        .cell_layout(egui::Layout::left_to_right(egui::Align::Center));
+    for column in ClusterColumn::ALL {
+        builder = match column {
+            ClusterColumn::TopUser => builder.column(Column::remainder()),
+            ClusterColumn::Host => {
+                builder.column(Column::initial(120.0).clip(true).resizable(true))
+            }
+            _ => builder.column(Column::initial(90.0).clip(true).resizable(true)),
+        };
+    }
+
+    let mut clicked_gpu = None;
+    builder
+        .header(20.0, |mut header| {
+            for column in ClusterColumn::ALL {
+                header.col(|ui| {
+                    sortable_column_head(ui, column, column.label(), table.sorting_mut())
+                });
+            }
+        })
+        .body(|mut body| {
+            for row in &rows {
+                body.row(20.0, |mut table_row| {
+                    table_row.set_selected(table.selected_gpu() == Some(row.gpu));
+
+                    for column in ClusterColumn::ALL {
+                        table_row.col(|ui| {
+                            draw_table_cell(ui, |ui| render_cell(ui, row, column));
+                        });
+                    }
+
+                    if table_row.response().clicked() {
+                        clicked_gpu = Some(row.gpu);
+                    }
+                });
+            }
+        });
+
+    ui.style_mut().spacing.item_spacing.x = old_spacing;
+
+    if let Some(gpu) = clicked_gpu {
+        table.set_selected_gpu(Some(gpu));
+    }
+    clicked_gpu
+}
+
+fn render_cell(ui: &mut egui::Ui, row: &ClusterRow, column: ClusterColumn) {
+    let text = match column {
+        ClusterColumn::Host => row.host.clone(),
+        ClusterColumn::Gpu => format!("{}: {}", row.device_index, row.device_name),
+        ClusterColumn::Utilization => row
+            .utilization
+            .map(|utilization| format!("{:.0}%", utilization))
+            .unwrap_or_else(|| "-".to_string()),
+        ClusterColumn::Memory => match row.memory_used {
+            Some(used) => format!(
+                "{} / {}",
+                bytes_to_mib_gib(used as f32),
+                bytes_to_mib_gib(row.memory_total as f32)
+            ),
+            None => "-".to_string(),
+        },
+        ClusterColumn::Temperature => row
+            .temperature
+            .map(|temperature| format!("{:.0}°C", temperature))
+            .unwrap_or_else(|| "-".to_string()),
+        ClusterColumn::TopUser => match &row.top_user {
+            Some((user, memory)) => format!("{} ({})", user, bytes_to_mib_gib(*memory as f32)),
+            None => "-".to_string(),
+        },
+    };
+
+    // Old readings are greyed out so they aren't mistaken for current ones
+    let text = if row.stale_for.is_some() || row.lost {
+        RichText::new(text).weak()
+    } else {
+        RichText::new(text)
+    };
+    ui.add(Label::new(text).selectable(false));
+
+    if column == ClusterColumn::Host {
+        if row.lost {
+            ui.colored_label(ui.visuals().error_fg_color, "lost");
+        } else if let Some(stale_for) = row.stale_for {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!("stale {}", format_duration(stale_for)),
+            )
+            .on_hover_text("No readings have arrived from this host recently");
+        }
+    }
+}
+
+fn render_host_health(ui: &mut egui::Ui, hosts: &[HostHealth]) {
+    egui::Grid::new("host_health")
+        .num_columns(4)
+        .spacing([16.0, 4.0])
+        .show(ui, |ui| {
+            for host in hosts {
+                let color = match host.status {
+                    ConnectionStatus::Connected if host.is_stale() => ui.visuals().warn_fg_color,
+                    // The theme's primary colour
+                    ConnectionStatus::Connected => ui.visuals().widgets.inactive.bg_fill,
+                    ConnectionStatus::Connecting => ui.visuals().warn_fg_color,
+                    ConnectionStatus::Disconnected(_) => ui.visuals().error_fg_color,
+                };
+
+                ui.horizontal(|ui| {
+                    ui.colored_label(color, "●");
+                    ui.strong(&host.name);
+                });
+                ui.label(host.address.as_deref().unwrap_or("local"));
+                ui.label(format!("{} GPUs", host.gpu_count));
+                ui.horizontal(|ui| {
+                    ui.label(host.describe());
+                    if let Some(last_update) = host.last_update {
+                        ui.weak(format!("updated {}", format_elapsed(last_update)));
+                    }
+                });
+                ui.end_row();
+            }
+        });
+}
@@ -0,0 +1,89 @@
+use eframe::egui::{self, RichText, ScrollArea};
+
+use crate::{data::GpuDeviceMonitor, utils::format_elapsed};
+
+use super::graph::render_graph;
+
+pub fn render_device_info(ui: &mut egui::Ui, monitor: &GpuDeviceMonitor, copy_status: &mut String) {
+    let Some(info) = monitor.info() else {
+        ui.weak("The agent this GPU is on doesn't report device info");
+        return;
+    };
+
+    ui.horizontal(|ui| {
+        if ui.button("Copy to clipboard").clicked() {
+            if let Some(report) = monitor.info_report() {
+                ui.output_mut(|output| output.copied_text = report);
+                *copy_status = "Copied".to_string();
+            }
+        }
+        ui.label(copy_status.as_str());
+    });
+
+    ui.add_space(4.0);
+
+    ScrollArea::vertical()
+        .auto_shrink([false, false])
+        .show(ui, |ui| {
+            egui::Grid::new("device_info")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    for (label, value) in info.rows() {
+                        ui.weak(label);
+                        ui.label(value);
+                        ui.end_row();
+                    }
+                });
+
+            if let Some(ecc) = monitor.ecc() {
+                render_memory_errors(ui, monitor, ecc.rows());
+            }
+        });
+}
+
+/// The ECC counts, when they last went up, and graphs of them over time.
+fn render_memory_errors(
+    ui: &mut egui::Ui,
+    monitor: &GpuDeviceMonitor,
+    rows: Vec<(&'static str, String)>,
+) {
+    ui.add_space(8.0);
+    ui.heading("Memory errors");
+
+    if let Some(increase) = monitor.ecc_increase() {
+        let text = format!(
+            "⚠ {}, {}",
+            increase.description,
+            format_elapsed(increase.time.elapsed())
+        );
+        ui.label(RichText::new(text).color(ui.visuals().error_fg_color));
+    }
+
+    egui::Grid::new("memory_errors")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            for (label, value) in rows {
+                ui.weak(label);
+                ui.label(value);
+                ui.end_row();
+            }
+        });
+
+    let width = ui.available_width();
+    for (name, graph) in monitor.ecc_history().graphs() {
+        ui.label(name);
+        let export_name = format!("gpustat-{}", name.to_lowercase().replace(' ', "-"));
+        ui.allocate_ui(egui::vec2(width, 80.0), |ui| {
+            render_graph(
+                ui,
+                &export_name,
+                graph,
+                graph.max_value().unwrap_or(0.0).max(1.0),
+                None,
+                |v| format!("{:.0}", v),
+            );
+        });
+    }
+}
@@ -0,0 +1,44 @@
+use eframe::egui::{self, ScrollArea};
+
+use crate::{
+    data::events::EventLog,
+    utils::{export_path, format_elapsed},
+};
+
+pub fn render_event_log(ui: &mut egui::Ui, events: &mut EventLog, export_status: &mut String) {
+    ui.horizontal(|ui| {
+        if ui.button("Export…").clicked() {
+            let path = export_path("gpustat-events", "tsv");
+            *export_status = match events.export(&path) {
+                Ok(()) => format!("Exported to {}", path.display()),
+                Err(err) => format!("Failed to export: {}", err),
+            };
+        }
+        if ui.button("Clear").clicked() {
+            events.clear();
+        }
+        ui.label(export_status.as_str());
+    });
+
+    ui.add_space(4.0);
+
+    if events.is_empty() {
+        ui.weak("No processes have started or exited yet");
+        return;
+    }
+
+    ScrollArea::vertical()
+        .auto_shrink([false, false])
+        .show(ui, |ui| {
+            for event in events.events().rev() {
+                let elapsed = event.time.elapsed().unwrap_or_default();
+                ui.horizontal(|ui| {
+                    ui.add_sized(
+                        [60.0, 16.0],
+                        egui::Label::new(egui::RichText::new(format_elapsed(elapsed)).weak()),
+                    );
+                    ui.label(event.to_string());
+                });
+            }
+        });
+}
@@ -1,27 +1,92 @@
 use eframe::{
-    egui::{Sense, TextStyle, Ui},
+    egui::{self, Sense, TextStyle, Ui},
     emath::Align2,
-    epaint::{Color32, Pos2, Rect, Shape, Stroke},
+    epaint::{Color32, Pos2, Rect, Rgba, Shape, Stroke},
 };
 
-use crate::data::graph::GraphViewerData;
+use crate::{
+    data::{
+        graph::GraphViewerData,
+        levels::{GraphLevels, Level},
+    },
+    utils::export_path,
+};
+
+use super::graph_export::{save_graph_image, GraphImageFormat};
 
+/// Renders a graph filling the available space, which can be saved as an image from its
+/// context menu. `name` is used for the file names, and `levels` colours the graph by how
+/// close its values are to their limit.
 pub fn render_graph(
     ui: &mut Ui,
+    name: &str,
     graph_data: &GraphViewerData,
     max_value: f32,
+    levels: Option<GraphLevels>,
     value_to_string: impl Fn(f32) -> String,
 ) {
     let available_space = ui.available_size();
-    let (rect, _) = ui.allocate_exact_size(available_space, Sense::hover());
+    let (rect, response) = ui.allocate_exact_size(available_space, Sense::click());
+    draw_graph(ui, rect, graph_data, max_value, levels, &value_to_string);
+
+    let style = ui.style().clone();
+    response.context_menu(|ui| {
+        let status_id = ui.id().with("save_status");
+        for format in [GraphImageFormat::Png, GraphImageFormat::Svg] {
+            let text = format!("Save as {}", format.extension().to_uppercase());
+            if ui.button(text).clicked() {
+                let path = export_path(name, format.extension());
+                let result = save_graph_image(
+                    &path,
+                    format,
+                    &style,
+                    graph_data,
+                    max_value,
+                    levels,
+                    &value_to_string,
+                    [rect.width() as u32, rect.height() as u32],
+                );
+                let status = match result {
+                    Ok(()) => format!("Saved to {}", path.display()),
+                    Err(err) => format!("Failed to save: {}", err),
+                };
+                ui.data_mut(|data| data.insert_temp(status_id, status));
+            }
+        }
+        if let Some(status) = ui.data(|data| data.get_temp::<String>(status_id)) {
+            ui.weak(status);
+        }
+    });
+}
+
+/// Draws a graph into `rect`, with guide lines and labels either side.
+pub(super) fn draw_graph(
+    ui: &mut Ui,
+    rect: Rect,
+    graph_data: &GraphViewerData,
+    max_value: f32,
+    levels: Option<GraphLevels>,
+    value_to_string: &dyn Fn(f32) -> String,
+) {
     ui.set_clip_rect(rect);
 
     let style = ui.style();
 
     let bg = style.visuals.extreme_bg_color;
     let line_col = style.visuals.widgets.active.bg_fill;
-    let line_col_translucent =
-        Color32::from_rgba_unmultiplied(line_col.r(), line_col.g(), line_col.b(), 30);
+    let line_col_translucent = with_alpha(line_col, 30);
+    let warn_col = style.visuals.warn_fg_color;
+    let critical_col = style.visuals.error_fg_color;
+    let level_col = |level| match level {
+        Level::Normal => line_col,
+        Level::Warning => warn_col,
+        Level::Critical => critical_col,
+    };
+    let text_col = if Rgba::from(bg).intensity() < 0.5 {
+        Color32::WHITE
+    } else {
+        Color32::BLACK
+    };
 
     ui.painter()
         .rect_filled(rect, style.visuals.window_rounding, bg);
@@ -82,12 +147,22 @@ pub fn render_graph(
             Align2::RIGHT_CENTER,
             &line.text,
             font.clone(),
-            Color32::WHITE,
+            text_col,
         );
     }
 
+    let level_of = |value: f32| {
+        levels
+            .map(|levels| levels.thresholds.level(value))
+            .unwrap_or(Level::Normal)
+    };
+    let patterns = levels.is_some_and(|levels| levels.patterns);
+
     if let Some(value) = graph_data.get_value_at(0) {
-        let text = value_to_string(value);
+        let mut text = value_to_string(value);
+        if patterns && level_of(value) != Level::Normal {
+            text = format!("⚠ {}", text);
+        }
         let value = value / max_value;
         ui.painter().text(
             Pos2::new(
@@ -97,7 +172,7 @@ pub fn render_graph(
             Align2::LEFT_CENTER,
             &text,
             font.clone(),
-            Color32::WHITE,
+            text_col,
         );
     }
 
@@ -105,9 +180,30 @@ pub fn render_graph(
 
     ui.set_clip_rect(rect);
 
+    // Dashed lines where the colour changes
+    if let Some(levels) = levels {
+        let thresholds = levels.thresholds;
+        for (threshold, level) in [
+            (thresholds.warning, Level::Warning),
+            (thresholds.critical, Level::Critical),
+        ] {
+            if threshold <= 0.0 || threshold >= max_value {
+                continue;
+            }
+            let y = (rect.bottom() - threshold / max_value * rect.height()).round() - 0.5;
+            ui.painter().extend(Shape::dashed_line(
+                &[Pos2::new(rect.left(), y), Pos2::new(rect.right(), y)],
+                (1.0, with_alpha(level_col(level), 120)),
+                4.0,
+                4.0,
+            ));
+        }
+    }
+
     #[derive(Debug, Clone)]
     struct Point {
         position: Pos2,
+        level: Level,
     }
 
     let mut points = Vec::new();
@@ -121,6 +217,7 @@ pub fn render_graph(
                     x: rect.right() - (i as f32),
                     y: rect.bottom() - (value * rect.height()),
                 },
+                level: level_of(value * max_value),
             });
         } else {
             if !curr_points.is_empty() {
@@ -134,6 +231,15 @@ pub fn render_graph(
         points.push(curr_points);
     }
 
+    if patterns {
+        let columns = points
+            .iter()
+            .flatten()
+            .map(|point| (point.position, point.level))
+            .collect::<Vec<_>>();
+        draw_hatching(ui, rect, &columns, &level_col);
+    }
+
     let painter = ui.painter();
     for mut points in points {
         if points.len() == 1 {
@@ -144,6 +250,14 @@ pub fn render_graph(
         let mut last = points[0].clone();
 
         for point in points.into_iter() {
+            // Each segment takes the colour of the higher level of its ends
+            let col = level_col(last.level.max(point.level));
+            let fill = if col == line_col {
+                line_col_translucent
+            } else {
+                with_alpha(col, 20)
+            };
+
             let shape = Shape::convex_polygon(
                 vec![
                     last.position,
@@ -151,14 +265,123 @@ pub fn render_graph(
                     Pos2::new(point.position.x, rect.bottom()),
                     Pos2::new(last.position.x, rect.bottom()),
                 ],
-                line_col_translucent,
+                fill,
                 Stroke::NONE,
             );
             painter.add(shape);
 
-            painter.line_segment([last.position, point.position], (2.0, line_col));
+            painter.line_segment([last.position, point.position], (2.0, col));
 
             last = point;
         }
     }
 }
+
+pub(super) fn with_alpha(color: Color32, alpha: u8) -> Color32 {
+    Color32::from_rgba_unmultiplied(color.r(), color.g(), color.b(), alpha)
+}
+
+/// Hatches the area under the graph where values are a warning, and cross-hatches it where
+/// they're critical. `columns` are the graph's points, one pixel apart, with their levels.
+fn draw_hatching(
+    ui: &Ui,
+    rect: Rect,
+    columns: &[(Pos2, Level)],
+    level_col: &dyn Fn(Level) -> Color32,
+) {
+    let spacing = 8.0;
+
+    // Lines going up to the right (`x + y` is constant) and down to the right (`y - x` is)
+    for (direction, min_level) in [(-1.0, Level::Warning), (1.0, Level::Critical)] {
+        let line_y = |offset: f32, x: f32| offset + direction * x;
+        let offsets = [
+            rect.top() - direction * rect.left(),
+            rect.top() - direction * rect.right(),
+            rect.bottom() - direction * rect.left(),
+            rect.bottom() - direction * rect.right(),
+        ];
+        let first = offsets.into_iter().fold(f32::INFINITY, f32::min);
+        let last = offsets.into_iter().fold(f32::NEG_INFINITY, f32::max);
+
+        let mut offset = (first / spacing).floor() * spacing;
+        while offset <= last {
+            // Join up the neighbouring columns the line passes under the graph in
+            let mut run: Option<(f32, f32, Level)> = None;
+            let draw_run = |run: (f32, f32, Level)| {
+                let (start, end, level) = run;
+                let (start, end) = (start - 0.5, end + 0.5);
+                ui.painter().line_segment(
+                    [
+                        Pos2::new(start, line_y(offset, start)),
+                        Pos2::new(end, line_y(offset, end)),
+                    ],
+                    (1.0, with_alpha(level_col(level), 70)),
+                );
+            };
+
+            // The columns go from right to left
+            for &(position, level) in columns.iter().rev() {
+                let y = line_y(offset, position.x);
+                let inside = level >= min_level && y >= position.y && y <= rect.bottom();
+                run = match (run, inside) {
+                    (Some((start, end, run_level)), true)
+                        if position.x - end <= 1.5 && run_level == level =>
+                    {
+                        Some((start, position.x, level))
+                    }
+                    (run, true) => {
+                        if let Some(run) = run {
+                            draw_run(run);
+                        }
+                        Some((position.x, position.x, level))
+                    }
+                    (run, false) => {
+                        if let Some(run) = run {
+                            draw_run(run);
+                        }
+                        None
+                    }
+                };
+            }
+            if let Some(run) = run {
+                draw_run(run);
+            }
+
+            offset += spacing;
+        }
+    }
+}
+
+/// Renders a small line graph of the most recent values, without any labels or guide lines.
+pub fn render_sparkline(ui: &mut Ui, graph_data: &GraphViewerData, max_value: f32) {
+    let available_space = ui.available_size();
+    let (rect, _) = ui.allocate_exact_size(available_space, Sense::hover());
+    let rect = rect.shrink2(egui::vec2(2.0, 3.0));
+
+    let line_col = ui.style().visuals.widgets.active.bg_fill;
+    let max_value = max_value.max(1.0);
+
+    // Each sample takes up a few pixels so that short histories are still readable
+    let step = 3.0;
+    let sample_count = (rect.width() / step) as usize + 1;
+
+    let painter = ui.painter();
+    let mut last: Option<Pos2> = None;
+    for i in 0..sample_count {
+        let Some(value) = graph_data.get_value_at(i) else {
+            last = None;
+            continue;
+        };
+
+        let value = (value / max_value).clamp(0.0, 1.0);
+        let point = Pos2::new(
+            rect.right() - i as f32 * step,
+            rect.bottom() - value * rect.height(),
+        );
+
+        if let Some(last) = last {
+            painter.line_segment([last, point], (1.0, line_col));
+        }
+        last = Some(point);
+    }
+}
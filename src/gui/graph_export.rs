@@ -0,0 +1,601 @@
+//! Rendering graphs to PNG and SVG files without a window. The graph is drawn by
+//! `draw_graph` in an off-screen egui context with the app's style, so exported images
+//! look the same as the app.
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    io::{self, Write as _},
+    path::Path,
+};
+
+use eframe::{
+    egui::{self, Frame, RawInput, Sense, Style},
+    epaint::{
+        ClippedPrimitive, ClippedShape, Color32, ImageData, Pos2, Primitive, Rect, Shape, Stroke,
+        TextureId, Vertex,
+    },
+};
+
+use crate::data::{graph::GraphViewerData, levels::GraphLevels};
+
+use super::graph::draw_graph;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum GraphImageFormat {
+    Png,
+    Svg,
+}
+
+impl GraphImageFormat {
+    /// Picks the format from a file's extension, defaulting to PNG.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("svg") => GraphImageFormat::Svg,
+            _ => GraphImageFormat::Png,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            GraphImageFormat::Png => "png",
+            GraphImageFormat::Svg => "svg",
+        }
+    }
+}
+
+/// Draws a graph the size of an image, returning what egui painted and the font texture.
+fn paint_graph(
+    style: &Style,
+    graph_data: &GraphViewerData,
+    max_value: f32,
+    levels: Option<GraphLevels>,
+    value_to_string: &dyn Fn(f32) -> String,
+    [width, height]: [u32; 2],
+) -> (egui::FullOutput, egui::Context) {
+    let ctx = egui::Context::default();
+    ctx.set_style(style.clone());
+
+    let input = RawInput {
+        screen_rect: Some(Rect::from_min_size(
+            Pos2::ZERO,
+            egui::vec2(width as f32, height as f32),
+        )),
+        ..Default::default()
+    };
+
+    let output = ctx.run(input, |ctx| {
+        egui::CentralPanel::default()
+            .frame(Frame::none())
+            .show(ctx, |ui| {
+                let (rect, _) = ui.allocate_exact_size(ui.available_size(), Sense::hover());
+                draw_graph(ui, rect, graph_data, max_value, levels, value_to_string);
+            });
+    });
+
+    (output, ctx)
+}
+
+/// Saves a graph drawn in `style` as an image of `[width, height]` pixels.
+#[allow(clippy::too_many_arguments)]
+pub fn save_graph_image(
+    path: &Path,
+    format: GraphImageFormat,
+    style: &Style,
+    graph_data: &GraphViewerData,
+    max_value: f32,
+    levels: Option<GraphLevels>,
+    value_to_string: &dyn Fn(f32) -> String,
+    [width, height]: [u32; 2],
+) -> io::Result<()> {
+    let (output, ctx) = paint_graph(
+        style,
+        graph_data,
+        max_value,
+        levels,
+        value_to_string,
+        [width, height],
+    );
+    let background = style.visuals.panel_fill;
+
+    match format {
+        GraphImageFormat::Png => rasterize(output, &ctx, [width, height], background)
+            .save(path)
+            .map_err(|err| io::Error::other(err.to_string())),
+        GraphImageFormat::Svg => {
+            let svg = shapes_to_svg(&output.shapes, width, height, background);
+            fs::File::create(path)?.write_all(svg.as_bytes())
+        }
+    }
+}
+
+/// Draws what egui painted onto an image of `[width, height]` pixels.
+fn rasterize(
+    output: egui::FullOutput,
+    ctx: &egui::Context,
+    [width, height]: [u32; 2],
+    background: Color32,
+) -> image::RgbaImage {
+    let mut canvas = Canvas::new(width, height, background);
+    let mut textures = HashMap::new();
+    for (id, delta) in &output.textures_delta.set {
+        canvas_texture_update(&mut textures, *id, delta);
+    }
+
+    let primitives = ctx.tessellate(output.shapes, output.pixels_per_point);
+    for primitive in &primitives {
+        canvas.draw(primitive, &textures);
+    }
+    canvas.into_image()
+}
+
+struct Texture {
+    size: [usize; 2],
+    pixels: Vec<Color32>,
+}
+
+impl Texture {
+    fn sample(&self, u: f32, v: f32) -> Color32 {
+        let x = ((u * self.size[0] as f32) as usize).min(self.size[0] - 1);
+        let y = ((v * self.size[1] as f32) as usize).min(self.size[1] - 1);
+        self.pixels[y * self.size[0] + x]
+    }
+}
+
+fn canvas_texture_update(
+    textures: &mut HashMap<TextureId, Texture>,
+    id: TextureId,
+    delta: &egui::epaint::ImageDelta,
+) {
+    let (size, pixels) = match &delta.image {
+        ImageData::Color(image) => (image.size, image.pixels.clone()),
+        ImageData::Font(image) => (image.size, image.srgba_pixels(None).collect()),
+    };
+
+    match delta.pos {
+        None => {
+            textures.insert(id, Texture { size, pixels });
+        }
+        Some([x, y]) => {
+            let Some(texture) = textures.get_mut(&id) else {
+                return;
+            };
+            for row in 0..size[1] {
+                let start = (y + row) * texture.size[0] + x;
+                texture.pixels[start..start + size[0]]
+                    .copy_from_slice(&pixels[row * size[0]..(row + 1) * size[0]]);
+            }
+        }
+    }
+}
+
+/// A minimal triangle rasterizer for egui's meshes, blending premultiplied colours the same
+/// way egui's own renderers do.
+struct Canvas {
+    width: u32,
+    height: u32,
+    /// Premultiplied RGBA, from 0 to 1.
+    pixels: Vec<[f32; 4]>,
+}
+
+fn color_to_f32(color: Color32) -> [f32; 4] {
+    color.to_array().map(|channel| channel as f32 / 255.0)
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `p`.
+fn edge(a: Pos2, b: Pos2, p: Pos2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+/// Whether pixels exactly on the edge from `a` to `b` belong to this triangle, so that
+/// triangles sharing an edge don't both draw it.
+fn owns_edge(a: Pos2, b: Pos2) -> bool {
+    b.y > a.y || (b.y == a.y && b.x > a.x)
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32, background: Color32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![color_to_f32(background); (width * height) as usize],
+        }
+    }
+
+    fn draw(&mut self, primitive: &ClippedPrimitive, textures: &HashMap<TextureId, Texture>) {
+        let Primitive::Mesh(mesh) = &primitive.primitive else {
+            return;
+        };
+        let Some(texture) = textures.get(&mesh.texture_id) else {
+            return;
+        };
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            let vertices = [0, 1, 2].map(|corner| &mesh.vertices[triangle[corner] as usize]);
+            self.fill_triangle(vertices, primitive.clip_rect, texture);
+        }
+    }
+
+    fn fill_triangle(&mut self, vertices: [&Vertex; 3], clip_rect: Rect, texture: &Texture) {
+        let [a, mut b, mut c] = vertices;
+        if edge(a.pos, b.pos, c.pos) < 0.0 {
+            std::mem::swap(&mut b, &mut c);
+        }
+        let area = edge(a.pos, b.pos, c.pos);
+        if area <= 0.0 {
+            return;
+        }
+
+        let bounds = Rect::from_points(&[a.pos, b.pos, c.pos]).intersect(clip_rect);
+        let min_x = bounds.min.x.floor().max(0.0) as u32;
+        let min_y = bounds.min.y.floor().max(0.0) as u32;
+        let max_x = (bounds.max.x.ceil().max(0.0) as u32).min(self.width);
+        let max_y = (bounds.max.y.ceil().max(0.0) as u32).min(self.height);
+
+        let colors = [a, b, c].map(|vertex| color_to_f32(vertex.color));
+        let edges = [(b.pos, c.pos), (c.pos, a.pos), (a.pos, b.pos)];
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let p = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+                if !clip_rect.contains(p) {
+                    continue;
+                }
+
+                let weights = edges.map(|(from, to)| edge(from, to, p));
+                let inside = weights.iter().zip(edges).all(|(weight, (from, to))| {
+                    *weight > 0.0 || (*weight == 0.0 && owns_edge(from, to))
+                });
+                if !inside {
+                    continue;
+                }
+
+                let [wa, wb, wc] = weights.map(|weight| weight / area);
+                let u = wa * a.uv.x + wb * b.uv.x + wc * c.uv.x;
+                let v = wa * a.uv.y + wb * b.uv.y + wc * c.uv.y;
+                let texel = color_to_f32(texture.sample(u, v));
+
+                let source: [f32; 4] = std::array::from_fn(|channel| {
+                    (wa * colors[0][channel] + wb * colors[1][channel] + wc * colors[2][channel])
+                        * texel[channel]
+                });
+
+                let pixel = &mut self.pixels[(y * self.width + x) as usize];
+                for channel in 0..4 {
+                    pixel[channel] = source[channel] + pixel[channel] * (1.0 - source[3]);
+                }
+            }
+        }
+    }
+
+    fn into_image(self) -> image::RgbaImage {
+        let bytes = self
+            .pixels
+            .iter()
+            .flat_map(|pixel| pixel.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8))
+            .collect();
+        image::RgbaImage::from_raw(self.width, self.height, bytes).unwrap()
+    }
+}
+
+/// An SVG paint attribute, e.g. `fill="rgb(1,2,3)" fill-opacity="0.5"`.
+fn svg_paint(attribute: &str, color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    if a == 255 {
+        format!(r#"{}="rgb({},{},{})""#, attribute, r, g, b)
+    } else {
+        format!(
+            r#"{}="rgb({},{},{})" {}-opacity="{:.3}""#,
+            attribute,
+            r,
+            g,
+            b,
+            attribute,
+            a as f32 / 255.0
+        )
+    }
+}
+
+fn svg_stroke(stroke: Stroke) -> String {
+    if stroke.is_empty() {
+        return r#"stroke="none""#.to_string();
+    }
+    format!(
+        r#"{} stroke-width="{}""#,
+        svg_paint("stroke", stroke.color),
+        stroke.width
+    )
+}
+
+fn svg_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn shape_to_svg(svg: &mut String, shape: &Shape, clip: &str) {
+    match shape {
+        Shape::Vec(shapes) => {
+            for shape in shapes {
+                shape_to_svg(svg, shape, clip);
+            }
+        }
+        Shape::Rect(rect) => {
+            if rect.fill == Color32::TRANSPARENT && rect.stroke.is_empty() {
+                return;
+            }
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" rx="{}" {} {} {}/>"#,
+                rect.rect.min.x,
+                rect.rect.min.y,
+                rect.rect.width(),
+                rect.rect.height(),
+                rect.rounding.nw,
+                svg_paint("fill", rect.fill),
+                svg_stroke(rect.stroke),
+                clip
+            );
+        }
+        Shape::LineSegment { points, stroke } => {
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}" {} {}/>"#,
+                points[0].x,
+                points[0].y,
+                points[1].x,
+                points[1].y,
+                svg_stroke(*stroke),
+                clip
+            );
+        }
+        Shape::Path(path) => {
+            let points = path
+                .points
+                .iter()
+                .map(|point| format!("{},{}", point.x, point.y))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let element = if path.closed { "polygon" } else { "polyline" };
+            let fill = if path.closed {
+                svg_paint("fill", path.fill)
+            } else {
+                r#"fill="none""#.to_string()
+            };
+            let _ = writeln!(
+                svg,
+                r#"<{} points="{}" {} {} {}/>"#,
+                element,
+                points,
+                fill,
+                svg_stroke(path.stroke),
+                clip
+            );
+        }
+        Shape::Text(text) => {
+            // Each row is placed where egui laid it out, in the format of its first glyph
+            let galley = &text.galley;
+            for row in &galley.rows {
+                let Some(glyph) = row.glyphs.first() else {
+                    continue;
+                };
+                let Some(section) = galley.job.sections.get(glyph.section_index as usize) else {
+                    continue;
+                };
+                let color = text.override_text_color.unwrap_or(section.format.color);
+                let row_text = row.glyphs.iter().map(|glyph| glyph.chr).collect::<String>();
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-family="Ubuntu, sans-serif" font-size="{}" {} {}>{}</text>"#,
+                    text.pos.x + glyph.pos.x,
+                    text.pos.y + glyph.pos.y,
+                    section.format.font_id.size,
+                    svg_paint("fill", color),
+                    clip,
+                    svg_escape(&row_text)
+                );
+            }
+        }
+        // `render_graph` doesn't draw anything else
+        _ => {}
+    }
+}
+
+fn shapes_to_svg(shapes: &[ClippedShape], width: u32, height: u32, background: Color32) -> String {
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        width, height
+    );
+    svg.push('\n');
+    let _ = writeln!(
+        svg,
+        r#"<rect width="100%" height="100%" {}/>"#,
+        svg_paint("fill", background)
+    );
+
+    let mut clip_rects = Vec::<Rect>::new();
+    let mut body = String::new();
+    for clipped in shapes {
+        let index = match clip_rects
+            .iter()
+            .position(|rect| *rect == clipped.clip_rect)
+        {
+            Some(index) => index,
+            None => {
+                clip_rects.push(clipped.clip_rect);
+                clip_rects.len() - 1
+            }
+        };
+        shape_to_svg(
+            &mut body,
+            &clipped.shape,
+            &format!(r#"clip-path="url(#clip{})""#, index),
+        );
+    }
+
+    svg.push_str("<defs>\n");
+    for (index, rect) in clip_rects.iter().enumerate() {
+        let rect = rect.intersect(Rect::from_min_size(
+            Pos2::ZERO,
+            egui::vec2(width as f32, height as f32),
+        ));
+        let _ = writeln!(
+            svg,
+            r#"<clipPath id="clip{}"><rect x="{}" y="{}" width="{}" height="{}"/></clipPath>"#,
+            index,
+            rect.min.x,
+            rect.min.y,
+            rect.width(),
+            rect.height()
+        );
+    }
+    svg.push_str("</defs>\n");
+    svg.push_str(&body);
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::epaint::{Mesh, TextShape};
+
+    use super::*;
+
+    const SIZE: [u32; 2] = [400, 200];
+
+    fn percent(value: f32) -> String {
+        format!("{:.0}%", value)
+    }
+
+    /// A graph full of samples at `value` out of 100.
+    fn series(value: Option<f32>) -> GraphViewerData {
+        let mut graph = GraphViewerData::with_max_len(SIZE[0] as usize);
+        for _ in 0..SIZE[0] {
+            graph.update(value);
+        }
+        graph
+    }
+
+    fn paint(graph: &GraphViewerData) -> (egui::FullOutput, egui::Context) {
+        paint_graph(&Style::default(), graph, 100.0, None, &percent, SIZE)
+    }
+
+    fn png(graph: &GraphViewerData) -> image::RgbaImage {
+        let style = Style::default();
+        let (output, ctx) = paint(graph);
+        rasterize(output, &ctx, SIZE, style.visuals.panel_fill)
+    }
+
+    fn rgba(color: Color32) -> image::Rgba<u8> {
+        image::Rgba(color.to_srgba_unmultiplied())
+    }
+
+    #[test]
+    fn fills_the_graph_up_to_its_value() {
+        let graph_background = rgba(Style::default().visuals.extreme_bg_color);
+        let half = png(&series(Some(50.0)));
+        let empty = png(&series(None));
+
+        // The plot area runs from 10.5 to 189.5 pixels down, so these are an eighth of
+        // the way in from its top and bottom, away from the guide lines
+        let (x, above, below) = (200, 33, 167);
+        assert_eq!(*half.get_pixel(x, above), graph_background);
+        assert_ne!(*half.get_pixel(x, below), graph_background);
+        assert_eq!(*empty.get_pixel(x, below), graph_background);
+
+        // Every column of the plot is filled the same way
+        assert_eq!(half.get_pixel(x, below), half.get_pixel(x + 50, below));
+    }
+
+    #[test]
+    fn draws_shared_edges_once() {
+        let background = Color32::BLACK;
+        let color = Color32::from_rgba_premultiplied(100, 0, 0, 128);
+        let mut mesh = Mesh::default();
+        mesh.add_colored_rect(
+            Rect::from_min_max(Pos2::new(2.0, 2.0), Pos2::new(8.0, 8.0)),
+            color,
+        );
+        let primitive = ClippedPrimitive {
+            clip_rect: Rect::EVERYTHING,
+            primitive: Primitive::Mesh(mesh),
+        };
+        let white = Texture {
+            size: [1, 1],
+            pixels: vec![Color32::WHITE],
+        };
+        let textures = HashMap::from([(TextureId::default(), white)]);
+
+        let mut canvas = Canvas::new(10, 10, background);
+        canvas.draw(&primitive, &textures);
+        let image = canvas.into_image();
+
+        // The square is two triangles, and the pixels on their diagonal are only blended once
+        let inside = *image.get_pixel(3, 6);
+        assert_eq!(*image.get_pixel(5, 5), inside);
+        assert_eq!(*image.get_pixel(2, 2), inside);
+        assert_eq!(inside, image::Rgba([100, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(1, 5), rgba(background));
+        assert_eq!(*image.get_pixel(8, 8), rgba(background));
+    }
+
+    #[test]
+    fn writes_a_well_formed_svg() {
+        let (output, _) = paint(&series(Some(50.0)));
+        let svg = shapes_to_svg(&output.shapes, SIZE[0], SIZE[1], Color32::BLACK);
+
+        assert!(svg.starts_with(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="400" height="200" viewBox="0 0 400 200">"#
+        ));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<text").count(), svg.matches("</text>").count());
+        for label in ["0%", "25%", "50%", "75%", "100%"] {
+            assert!(
+                svg.contains(&format!(">{}</text>", label)),
+                "no {} label",
+                label
+            );
+        }
+        assert!(svg.contains("<polyline") || svg.contains("<polygon"));
+
+        // Every clip path that's used is defined
+        let defined = svg.matches("<clipPath id=").count();
+        assert!(defined > 0);
+        for index in 0..defined {
+            assert!(svg.contains(&format!(r#"<clipPath id="clip{}">"#, index)));
+        }
+        assert!(!svg.contains(&format!(r#"url(#clip{})"#, defined)));
+    }
+
+    #[test]
+    fn places_each_row_of_text() {
+        let (_, ctx) = paint(&series(None));
+        let galley = ctx.fonts(|fonts| {
+            fonts.layout_no_wrap(
+                "first\nsecond & more".to_string(),
+                egui::FontId::proportional(12.0),
+                Color32::WHITE,
+            )
+        });
+        let shape = Shape::Text(TextShape::new(
+            Pos2::new(10.0, 20.0),
+            galley,
+            Color32::WHITE,
+        ));
+
+        let mut svg = String::new();
+        shape_to_svg(&mut svg, &shape, "");
+        let rows = svg.lines().collect::<Vec<_>>();
+
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].ends_with(">first</text>"));
+        assert!(rows[1].ends_with(">second &amp; more</text>"));
+        let y = |row: &str| {
+            let start = row.find(r#" y=""#).unwrap() + 4;
+            let end = start + row[start..].find('"').unwrap();
+            row[start..end].parse::<f32>().unwrap()
+        };
+        assert!(y(rows[1]) > y(rows[0]));
+    }
+}
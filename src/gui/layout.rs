@@ -0,0 +1,321 @@
+//! The GUI's dashboard of metric graphs, which users can pick, reorder, resize and arrange,
+//! and pop out into their own windows.
+
+use eframe::{
+    egui::{self, CursorIcon, Sense, Ui},
+    emath::Align,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{levels::GraphConfig, GpuDeviceMonitor},
+    utils::bytes_to_mib_gib,
+};
+
+use super::graph::render_graph;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricPanel {
+    Usage,
+    Memory,
+    Temperature,
+}
+
+impl MetricPanel {
+    pub const ALL: [MetricPanel; 3] = [
+        MetricPanel::Usage,
+        MetricPanel::Memory,
+        MetricPanel::Temperature,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MetricPanel::Usage => "GPU Usage %",
+            MetricPanel::Memory => "VRAM Usage",
+            MetricPanel::Temperature => "GPU Temperature",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Arrangement {
+    #[default]
+    Vertical,
+    Grid,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PanelLayout {
+    pub metric: MetricPanel,
+    /// The height of the graph, in points.
+    pub height: f32,
+}
+
+impl PanelLayout {
+    const DEFAULT_HEIGHT: f32 = 100.0;
+    const MIN_HEIGHT: f32 = 40.0;
+    const MAX_HEIGHT: f32 = 600.0;
+
+    fn new(metric: MetricPanel) -> Self {
+        Self {
+            metric,
+            height: Self::DEFAULT_HEIGHT,
+        }
+    }
+}
+
+/// The `[gui_layout]` config section, which the GUI saves back when it's changed.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct GuiLayout {
+    pub arrangement: Arrangement,
+    /// How many panels are side by side in the grid arrangement.
+    pub columns: usize,
+    /// The panels that are shown, in order.
+    pub panels: Vec<PanelLayout>,
+}
+
+impl GuiLayout {
+    /// The most panels that fit side by side in the grid arrangement.
+    const MAX_COLUMNS: usize = 4;
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=Self::MAX_COLUMNS).contains(&self.columns) {
+            return Err(format!(
+                "GUI layout columns must be between 1 and {}",
+                Self::MAX_COLUMNS
+            ));
+        }
+        for (index, panel) in self.panels.iter().enumerate() {
+            if self.panels[..index]
+                .iter()
+                .any(|other| other.metric == panel.metric)
+            {
+                return Err(format!(
+                    "GUI layout has more than one {:?} panel",
+                    panel.metric.label()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for GuiLayout {
+    fn default() -> Self {
+        Self {
+            arrangement: Arrangement::Vertical,
+            columns: 2,
+            panels: MetricPanel::ALL.into_iter().map(PanelLayout::new).collect(),
+        }
+    }
+}
+
+/// Renders a metric's graph filling the available space.
+pub fn render_metric(
+    ui: &mut Ui,
+    metric: MetricPanel,
+    monitor: &mut GpuDeviceMonitor,
+    graphs: &GraphConfig,
+) {
+    match metric {
+        MetricPanel::Usage => render_graph(
+            ui,
+            "gpustat-usage",
+            monitor.usage_graph_mut(),
+            100.0,
+            None,
+            |v| format!("{:.0}%", v),
+        ),
+        MetricPanel::Memory => {
+            let max_memory = monitor.max_memory();
+            render_graph(
+                ui,
+                "gpustat-vram",
+                monitor.memory_graph_mut(),
+                max_memory as f32,
+                graphs.memory_levels(max_memory),
+                bytes_to_mib_gib,
+            )
+        }
+        MetricPanel::Temperature => render_graph(
+            ui,
+            "gpustat-temperature",
+            monitor.temperature_graph_mut(),
+            100.0,
+            graphs.temperature_levels(),
+            |v| format!("{:.0}°C", v),
+        ),
+    }
+}
+
+/// What the user did to the dashboard.
+#[derive(Default)]
+pub struct DashboardResponse {
+    /// Whether the layout was changed and should be saved.
+    pub changed: bool,
+    pub popped_out: Option<MetricPanel>,
+}
+
+/// Renders a panel's title, graph and resize handle.
+fn render_panel(
+    ui: &mut Ui,
+    panel: &mut PanelLayout,
+    monitor: &mut GpuDeviceMonitor,
+    graphs: &GraphConfig,
+    response: &mut DashboardResponse,
+) {
+    let width = ui.available_width();
+
+    ui.horizontal(|ui| {
+        ui.label(panel.metric.label());
+        ui.with_layout(egui::Layout::right_to_left(Align::Center), |ui| {
+            if ui
+                .small_button("⬈")
+                .on_hover_text("Pop out into a window")
+                .clicked()
+            {
+                response.popped_out = Some(panel.metric);
+            }
+        });
+    });
+
+    ui.allocate_ui(egui::vec2(width, panel.height), |ui| {
+        render_metric(ui, panel.metric, monitor, graphs);
+    });
+
+    // A handle under the graph to drag it taller or shorter
+    let (rect, handle) = ui.allocate_exact_size(egui::vec2(width, 6.0), Sense::drag());
+    let handle = handle.on_hover_cursor(CursorIcon::ResizeVertical);
+    if handle.hovered() || handle.dragged() {
+        let stroke = ui.visuals().widgets.hovered.fg_stroke;
+        ui.painter().hline(rect.x_range(), rect.center().y, stroke);
+    }
+    if handle.dragged() {
+        panel.height = (panel.height + handle.drag_delta().y)
+            .clamp(PanelLayout::MIN_HEIGHT, PanelLayout::MAX_HEIGHT);
+    }
+    if handle.drag_released() {
+        response.changed = true;
+    }
+}
+
+/// Renders the panels that aren't popped out, in the layout's arrangement.
+pub fn render_dashboard(
+    ui: &mut Ui,
+    layout: &mut GuiLayout,
+    popped_out: &[MetricPanel],
+    monitor: &mut GpuDeviceMonitor,
+    graphs: &GraphConfig,
+) -> DashboardResponse {
+    let mut response = DashboardResponse::default();
+    let mut panels = layout
+        .panels
+        .iter_mut()
+        .filter(|panel| !popped_out.contains(&panel.metric))
+        .collect::<Vec<_>>();
+
+    match layout.arrangement {
+        Arrangement::Vertical => {
+            for panel in panels {
+                render_panel(ui, panel, monitor, graphs, &mut response);
+            }
+        }
+        Arrangement::Grid => {
+            // The layout was checked when the config was loaded
+            for row in panels.chunks_mut(layout.columns) {
+                ui.columns(layout.columns, |uis| {
+                    for (ui, panel) in uis.iter_mut().zip(row) {
+                        render_panel(ui, panel, monitor, graphs, &mut response);
+                    }
+                });
+            }
+        }
+    }
+
+    response
+}
+
+/// Renders the contents of the layout menu, returning whether the layout was changed.
+pub fn render_layout_menu(ui: &mut Ui, layout: &mut GuiLayout) -> bool {
+    let mut changed = false;
+
+    ui.horizontal(|ui| {
+        changed |= ui
+            .selectable_value(&mut layout.arrangement, Arrangement::Vertical, "Vertical")
+            .changed();
+        changed |= ui
+            .selectable_value(&mut layout.arrangement, Arrangement::Grid, "Grid")
+            .changed();
+    });
+    if layout.arrangement == Arrangement::Grid {
+        changed |= ui
+            .add(egui::Slider::new(&mut layout.columns, 1..=GuiLayout::MAX_COLUMNS).text("columns"))
+            .changed();
+    }
+
+    ui.separator();
+
+    // The shown panels in order, followed by the hidden ones
+    let hidden = MetricPanel::ALL
+        .into_iter()
+        .filter(|metric| !layout.panels.iter().any(|panel| panel.metric == *metric))
+        .collect::<Vec<_>>();
+
+    let mut action = None;
+    let count = layout.panels.len();
+    for (index, panel) in layout.panels.iter().enumerate() {
+        ui.horizontal(|ui| {
+            let mut shown = true;
+            if ui.checkbox(&mut shown, panel.metric.label()).changed() {
+                action = Some(PanelAction::Hide(index));
+            }
+            ui.with_layout(egui::Layout::right_to_left(Align::Center), |ui| {
+                if ui
+                    .add_enabled(index + 1 < count, egui::Button::new("⏷").small())
+                    .clicked()
+                {
+                    action = Some(PanelAction::Swap(index, index + 1));
+                }
+                if ui
+                    .add_enabled(index > 0, egui::Button::new("⏶").small())
+                    .clicked()
+                {
+                    action = Some(PanelAction::Swap(index - 1, index));
+                }
+            });
+        });
+    }
+    for metric in hidden {
+        let mut shown = false;
+        if ui.checkbox(&mut shown, metric.label()).changed() {
+            action = Some(PanelAction::Show(metric));
+        }
+    }
+
+    changed |= action.is_some();
+    match action {
+        Some(PanelAction::Hide(index)) => {
+            layout.panels.remove(index);
+        }
+        Some(PanelAction::Swap(a, b)) => layout.panels.swap(a, b),
+        Some(PanelAction::Show(metric)) => layout.panels.push(PanelLayout::new(metric)),
+        None => {}
+    }
+
+    if ui.button("Reset").clicked() {
+        *layout = GuiLayout::default();
+        changed = true;
+    }
+
+    changed
+}
+
+enum PanelAction {
+    Hide(usize),
+    Swap(usize, usize),
+    Show(MetricPanel),
+}
@@ -6,15 +6,42 @@ use eframe::{
 
 use style::make_style;
 
-use crate::{data::GpuMonitoringData, utils::bytes_to_mib_gib};
+use crate::{
+    config::Config,
+    data::{cluster::ClusterTableData, GpuMonitoringData},
+    export::{export_recording, ExportFormat, ExportLayout},
+    processes::ProcessKind,
+    recording::Recording,
+    theme::{Palette, SystemThemeWatcher},
+    utils::export_path,
+};
 
-use self::{graph::render_graph, process_table::render_process_table};
+use self::{
+    alerts::render_alert_banner,
+    cluster::render_cluster_view,
+    device_info::render_device_info,
+    event_log::render_event_log,
+    layout::{render_dashboard, render_layout_menu, render_metric, MetricPanel},
+    overlay::OverlayApp,
+    process_table::{render_process_detail, render_process_table},
+    remotes::render_waiting_for_gpus,
+    throttle::render_throttle_strip,
+};
 
+mod alerts;
+mod cluster;
+mod device_info;
+mod event_log;
 mod graph;
+pub mod graph_export;
+pub mod layout;
+pub mod overlay;
 mod process_table;
-mod style;
+mod remotes;
+pub mod style;
+mod throttle;
 
-const ICON: &[u8] = include_bytes!("../../assets/gpustat_icon_64px.png");
+pub const ICON: &[u8] = include_bytes!("../../assets/gpustat_icon_64px.png");
 
 fn load_icon(data: &[u8]) -> IconData {
     let (icon_rgba, icon_width, icon_height) = {
@@ -39,23 +66,56 @@ enum SelectedProcessTab {
     All,
     Graphics,
     Compute,
+    Events,
+    DeviceInfo,
+}
+
+impl SelectedProcessTab {
+    /// The kind of processes the tab lists, or `None` if the tab isn't a process table.
+    fn kind_filter(&self) -> Option<Option<ProcessKind>> {
+        match self {
+            SelectedProcessTab::All => Some(None),
+            SelectedProcessTab::Graphics => Some(Some(ProcessKind::Graphics)),
+            SelectedProcessTab::Compute => Some(Some(ProcessKind::Compute)),
+            SelectedProcessTab::Events | SelectedProcessTab::DeviceInfo => None,
+        }
+    }
 }
 
-pub fn run_gpu_app() {
-    let options = eframe::NativeOptions {
+/// The options of the main window.
+pub fn native_options() -> eframe::NativeOptions {
+    eframe::NativeOptions {
         window_builder: Some(Box::new(|w| {
             w.with_icon(load_icon(ICON))
                 .with_inner_size(egui::vec2(500.0, 720.0))
                 .with_min_inner_size(egui::vec2(380.0, 600.0))
         })),
         ..Default::default()
-    };
+    }
+}
 
-    eframe::run_native(
-        "gpustat",
-        options,
-        Box::new(|_cc| Box::new(GpuApp::default())),
-    )
+/// How the GUI is shown.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AppMode {
+    /// The full window, with graphs and process tables.
+    Window,
+    /// A few readings in a small window on top of the others, set up in `[overlay]`.
+    Overlay,
+}
+
+pub fn run_gpu_app(config: Config, mode: AppMode) {
+    match mode {
+        AppMode::Window => eframe::run_native(
+            "gpustat",
+            native_options(),
+            Box::new(|_cc| Box::new(GpuApp::new(config))),
+        ),
+        AppMode::Overlay => eframe::run_native(
+            "gpustat overlay",
+            overlay::native_options(&config.overlay),
+            Box::new(|_cc| Box::new(OverlayApp::new(config))),
+        ),
+    }
     .unwrap();
 }
 
@@ -63,86 +123,260 @@ pub struct GpuApp {
     data: GpuMonitoringData,
 
     selected_process_tab: SelectedProcessTab,
-    updated_style: bool,
+    config: Config,
+    /// Set when the theme follows the desktop's dark/light preference.
+    theme_watcher: Option<SystemThemeWatcher>,
+    /// The palette the style was last made from.
+    palette: Option<Palette>,
 
     selected_gpu: usize,
+    /// Whether every GPU across all hosts is shown instead of the selected GPU.
+    show_cluster: bool,
+    cluster_table: ClusterTableData,
+
+    /// Graphs shown in their own windows rather than the dashboard.
+    popped_out: Vec<MetricPanel>,
+    layout_status: String,
+
+    event_export_status: String,
+    metrics_export_status: String,
+    info_copy_status: String,
 }
 
-impl Default for GpuApp {
-    fn default() -> Self {
+impl GpuApp {
+    pub fn new(config: Config) -> Self {
         Self {
-            data: GpuMonitoringData::new(),
-            updated_style: false,
+            data: GpuMonitoringData::new(&config),
+            theme_watcher: (config.theme == "system").then(SystemThemeWatcher::spawn),
+            palette: None,
             selected_process_tab: Default::default(),
             selected_gpu: 0,
+            show_cluster: !config.remotes.is_empty(),
+            cluster_table: ClusterTableData::new(),
+            popped_out: Vec::new(),
+            layout_status: String::new(),
+            event_export_status: String::new(),
+            metrics_export_status: String::new(),
+            info_copy_status: String::new(),
+            config,
+        }
+    }
+
+    /// The GPUs being monitored, for sampling them while the window is closed.
+    pub fn data_mut(&mut self) -> &mut GpuMonitoringData {
+        &mut self.data
+    }
+}
+
+impl GpuApp {
+    /// Shows each popped out graph in its own window, putting it back in the dashboard when
+    /// the window is closed.
+    fn show_popped_out_graphs(&mut self, ctx: &egui::Context) {
+        let monitor = &mut self.data.gpus()[self.selected_gpu];
+        let gpu_label = monitor.label();
+
+        let mut closed = Vec::new();
+        for &metric in &self.popped_out {
+            let builder = egui::ViewportBuilder::default()
+                .with_title(format!("{} - {}", metric.label(), gpu_label))
+                .with_inner_size(egui::vec2(500.0, 200.0));
+
+            ctx.show_viewport_immediate(
+                egui::ViewportId::from_hash_of(("gpustat-graph", metric)),
+                builder,
+                |ctx, class| {
+                    // Backends without multiple windows show it inside the main one instead
+                    if class == egui::ViewportClass::Embedded {
+                        let mut open = true;
+                        egui::Window::new(metric.label())
+                            .open(&mut open)
+                            .show(ctx, |ui| {
+                                render_metric(ui, metric, monitor, &self.config.graphs)
+                            });
+                        if !open {
+                            closed.push(metric);
+                        }
+                        return;
+                    }
+
+                    egui::CentralPanel::default().show(ctx, |ui| {
+                        render_metric(ui, metric, monitor, &self.config.graphs)
+                    });
+                    if ctx.input(|input| input.viewport().close_requested()) {
+                        closed.push(metric);
+                    }
+                    ctx.request_repaint();
+                },
+            );
         }
+        self.popped_out.retain(|metric| !closed.contains(metric));
     }
 }
 
 impl eframe::App for GpuApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        if !self.updated_style {
-            ctx.set_style(make_style());
-            self.updated_style = true;
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let prefers_dark = self
+            .theme_watcher
+            .as_ref()
+            .and_then(SystemThemeWatcher::prefers_dark)
+            .or(frame
+                .info()
+                .system_theme
+                .map(|theme| theme == eframe::Theme::Dark));
+        let palette = self.config.palette(prefers_dark);
+        if self.palette.as_ref() != Some(&palette) {
+            ctx.set_style(make_style(&palette));
+            self.palette = Some(palette);
         }
 
         self.data.update();
 
-        egui::TopBottomPanel::top("top")
-            .exact_height(400.0)
-            .show(ctx, |ui| {
-                let width = ui.available_width();
+        if self.data.gpus().is_empty() {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                render_waiting_for_gpus(ui, self.data.remotes());
+            });
+            ctx.request_repaint();
+            return;
+        }
 
-                ui.add_space(6.0);
+        if self.data.alerts().firing().next().is_some() {
+            egui::TopBottomPanel::top("alerts").show(ctx, |ui| {
+                render_alert_banner(ui, self.data.alerts());
+            });
+        }
 
-                let gpus = self.data.gpus();
+        if !self.data.remotes().is_empty() {
+            egui::TopBottomPanel::top("view").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.show_cluster, true, "Cluster");
+                    ui.selectable_value(&mut self.show_cluster, false, "GPU");
+                });
+            });
+        }
 
-                ui.allocate_ui_with_layout(
-                    Vec2::new(width, 80.0),
-                    Layout::right_to_left(Align::Min),
-                    |ui| {
-                        egui::ComboBox::from_label("Selected GPU")
-                            .width(250.0)
-                            .wrap(false)
-                            .show_index(ui, &mut self.selected_gpu, gpus.len(), |i| {
-                                gpus[i].device_name().to_string()
-                            });
-                    },
-                );
+        if self.show_cluster {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                let rows = self.data.cluster_rows();
+                let hosts = self.data.hosts();
+                if let Some(gpu) = render_cluster_view(ui, &mut self.cluster_table, rows, &hosts) {
+                    self.selected_gpu = gpu;
+                    self.show_cluster = false;
+                }
+            });
+            ctx.request_repaint();
+            return;
+        }
 
-                let monitor = &mut gpus[self.selected_gpu];
+        let mut requested_export = None;
 
-                ui.label("GPU Usage %");
+        let mut layout_changed = false;
 
-                ui.allocate_ui(Vec2::new(width, 100.0), |ui| {
-                    render_graph(ui, monitor.usage_graph_mut(), 100.0, |v| {
-                        format!("{:.0}%", v)
-                    })
-                });
+        egui::TopBottomPanel::top("top").show(ctx, |ui| {
+            let width = ui.available_width();
 
-                ui.add_space(5.0);
+            ui.add_space(6.0);
 
-                ui.label("VRAM Usage");
+            let alerting = (0..self.data.gpus().len())
+                .map(|i| self.data.is_alerting(i))
+                .collect::<Vec<_>>();
 
-                ui.allocate_ui(Vec2::new(width, 100.0), |ui| {
-                    let max_memory = monitor.max_memory();
-                    render_graph(ui, monitor.memory_graph_mut(), max_memory as f32, |v| {
-                        bytes_to_mib_gib(v)
+            let gpus = self.data.gpus();
+
+            ui.allocate_ui_with_layout(
+                Vec2::new(width, 80.0),
+                Layout::right_to_left(Align::Min),
+                |ui| {
+                    egui::ComboBox::from_label("Selected GPU")
+                        .width(250.0)
+                        .wrap(false)
+                        .show_index(ui, &mut self.selected_gpu, gpus.len(), |i| {
+                            if alerting[i] {
+                                format!("⚠ {}", gpus[i].label())
+                            } else {
+                                gpus[i].label()
+                            }
+                        });
+
+                    ui.menu_button("Export…", |ui| {
+                        let mut formats = vec![
+                            ("CSV (long)", ExportFormat::Csv, ExportLayout::Long),
+                            ("CSV (wide)", ExportFormat::Csv, ExportLayout::Wide),
+                        ];
+                        if cfg!(feature = "parquet") {
+                            formats.push(("Parquet", ExportFormat::Parquet, ExportLayout::Wide));
+                        }
+                        for (text, format, layout) in formats {
+                            if ui.button(text).clicked() {
+                                requested_export = Some((format, layout));
+                                ui.close_menu();
+                            }
+                        }
                     });
+                    ui.menu_button("Layout…", |ui| {
+                        layout_changed |= render_layout_menu(ui, &mut self.config.gui_layout);
+                        if !self.layout_status.is_empty() {
+                            ui.weak(self.layout_status.as_str());
+                        }
+                    });
+                    ui.label(self.metrics_export_status.as_str());
+                },
+            );
+
+            let monitor = &mut gpus[self.selected_gpu];
+
+            // Leave room for the process table when the graphs are made tall
+            egui::ScrollArea::vertical()
+                .max_height(ctx.screen_rect().height() * 0.7)
+                .show(ui, |ui| {
+                    let response = render_dashboard(
+                        ui,
+                        &mut self.config.gui_layout,
+                        &self.popped_out,
+                        monitor,
+                        &self.config.graphs,
+                    );
+                    layout_changed |= response.changed;
+                    if let Some(metric) = response.popped_out {
+                        self.popped_out.push(metric);
+                    }
+
+                    render_throttle_strip(ui, monitor);
                 });
 
-                ui.add_space(5.0);
+            ui.add_space(6.0);
+        });
 
-                ui.label("GPU Temperature");
+        if layout_changed {
+            self.layout_status = match Config::save_gui_layout(&self.config.gui_layout) {
+                Ok(()) => String::new(),
+                Err(err) => format!("Failed to save the layout: {}", err),
+            };
+        }
 
-                ui.allocate_ui(Vec2::new(width, 100.0), |ui| {
-                    render_graph(ui, monitor.temperature_graph_mut(), 100.0, |v| {
-                        format!("{:.0}°C", v)
-                    });
-                });
+        self.show_popped_out_graphs(ctx);
 
-                ui.add_space(6.0);
-            });
+        if let Some((format, layout)) = requested_export {
+            let recording = Recording::from_live(&mut self.data);
+            let path = export_path("gpustat-metrics", format.extension());
+            self.metrics_export_status = match export_recording(&recording, &path, format, layout) {
+                Ok(_) => format!("Exported to {}", path.display()),
+                Err(err) => format!("Failed to export: {}", err),
+            };
+        }
+
+        let monitor = &mut self.data.gpus()[self.selected_gpu];
+        let max_memory = monitor.max_memory();
+        if self.selected_process_tab.kind_filter().is_some() {
+            let table = monitor.processes_mut();
+            if table.selected_history().is_some() {
+                egui::TopBottomPanel::bottom("process_detail")
+                    .exact_height(230.0)
+                    .show(ctx, |ui| {
+                        ui.add_space(6.0);
+                        render_process_detail(ui, table, max_memory);
+                    });
+            }
+        }
 
         egui::CentralPanel::default().show(ctx, |ui| {
             let width = ui.available_width();
@@ -165,40 +399,51 @@ impl eframe::App for GpuApp {
                         SelectedProcessTab::Compute,
                         "Compute",
                     );
+                    ui.selectable_value(
+                        &mut self.selected_process_tab,
+                        SelectedProcessTab::Events,
+                        "Events",
+                    );
+                    ui.selectable_value(
+                        &mut self.selected_process_tab,
+                        SelectedProcessTab::DeviceInfo,
+                        "Device info",
+                    );
                 },
             );
 
             let monitor = &mut self.data.gpus()[self.selected_gpu];
 
-            match self.selected_process_tab {
-                SelectedProcessTab::All => {
-                    let mut ui = ui.child_ui_with_id_source(
-                        ui.available_rect_before_wrap(),
-                        Layout::top_down(Align::Min),
-                        "all",
-                    );
+            if self.selected_process_tab == SelectedProcessTab::Events {
+                let mut ui = ui.child_ui_with_id_source(
+                    ui.available_rect_before_wrap(),
+                    Layout::top_down(Align::Min),
+                    "events",
+                );
 
-                    render_process_table(&mut ui, monitor.all_processes_mut());
-                }
-                SelectedProcessTab::Graphics => {
-                    let mut ui = ui.child_ui_with_id_source(
-                        ui.available_rect_before_wrap(),
-                        Layout::top_down(Align::Min),
-                        "graphics",
-                    );
+                render_event_log(
+                    &mut ui,
+                    self.data.events_mut(),
+                    &mut self.event_export_status,
+                );
+            } else if self.selected_process_tab == SelectedProcessTab::DeviceInfo {
+                let mut ui = ui.child_ui_with_id_source(
+                    ui.available_rect_before_wrap(),
+                    Layout::top_down(Align::Min),
+                    "device_info",
+                );
 
-                    render_process_table(&mut ui, monitor.graphics_processes_mut());
-                }
-                SelectedProcessTab::Compute => {
-                    let mut ui = ui.child_ui_with_id_source(
-                        ui.available_rect_before_wrap(),
-                        Layout::top_down(Align::Min),
-                        "compute",
-                    );
+                render_device_info(&mut ui, monitor, &mut self.info_copy_status);
+            } else {
+                let mut ui = ui.child_ui_with_id_source(
+                    ui.available_rect_before_wrap(),
+                    Layout::top_down(Align::Min),
+                    "processes",
+                );
 
-                    render_process_table(&mut ui, monitor.compute_processes_mut());
-                }
-            };
+                let kind = self.selected_process_tab.kind_filter().flatten();
+                render_process_table(&mut ui, monitor.processes_mut(), kind);
+            }
         });
 
         ctx.request_repaint();
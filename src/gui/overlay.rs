@@ -0,0 +1,260 @@
+//! A small borderless window that stays on top of others, such as a game, showing a few
+//! readings of each GPU.
+
+use std::time::Duration;
+
+use eframe::{
+    egui::{self, RichText, Ui},
+    epaint::{Pos2, Vec2},
+};
+use serde::Deserialize;
+
+use crate::{
+    config::Config,
+    data::{
+        levels::{GraphLevels, Level},
+        GpuDeviceMonitor, GpuMonitoringData,
+    },
+    theme::Palette,
+    utils::bytes_to_mib_gib,
+};
+
+use super::{
+    graph::{render_sparkline, with_alpha},
+    layout::MetricPanel,
+    load_icon,
+    style::make_style,
+    ICON,
+};
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlayCorner {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// The `[overlay]` config section.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct OverlayConfig {
+    /// The corner of the screen the overlay sits in.
+    pub corner: OverlayCorner,
+    /// The distance from the corner, in points.
+    pub margin: f32,
+    /// How opaque the background is, from 0 to 1. The text is always opaque.
+    pub opacity: f32,
+    /// The readings shown for each GPU, in order.
+    pub metrics: Vec<MetricPanel>,
+    /// Whether each reading has a sparkline of its recent values next to it.
+    pub sparklines: bool,
+    /// Whether clicks go through to the window underneath, where the platform supports it.
+    /// Otherwise the overlay can be dragged somewhere else.
+    pub click_through: bool,
+}
+
+impl Default for OverlayConfig {
+    fn default() -> Self {
+        Self {
+            corner: OverlayCorner::TopLeft,
+            margin: 16.0,
+            opacity: 0.6,
+            metrics: MetricPanel::ALL.to_vec(),
+            sparklines: true,
+            click_through: true,
+        }
+    }
+}
+
+impl OverlayConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.opacity) {
+            return Err("overlay opacity must be between 0 and 1".to_string());
+        }
+        if self.metrics.is_empty() {
+            return Err("overlay has no metrics".to_string());
+        }
+        Ok(())
+    }
+
+    /// Where the overlay's top left goes for it to be `size` and in its corner of `monitor`.
+    fn position(&self, size: Vec2, monitor: Vec2) -> Pos2 {
+        let right = monitor.x - size.x - self.margin;
+        let bottom = monitor.y - size.y - self.margin;
+        match self.corner {
+            OverlayCorner::TopLeft => Pos2::new(self.margin, self.margin),
+            OverlayCorner::TopRight => Pos2::new(right, self.margin),
+            OverlayCorner::BottomLeft => Pos2::new(self.margin, bottom),
+            OverlayCorner::BottomRight => Pos2::new(right, bottom),
+        }
+    }
+}
+
+pub fn native_options(overlay: &OverlayConfig) -> eframe::NativeOptions {
+    let overlay = overlay.clone();
+    eframe::NativeOptions {
+        window_builder: Some(Box::new(move |w| {
+            // The size and corner are set once the contents and the monitor's size are known
+            w.with_icon(load_icon(ICON))
+                .with_decorations(false)
+                .with_transparent(true)
+                .with_resizable(false)
+                .with_always_on_top()
+                .with_mouse_passthrough(overlay.click_through)
+                .with_inner_size(egui::vec2(200.0, 40.0))
+                .with_position(Pos2::new(overlay.margin, overlay.margin))
+        })),
+        ..Default::default()
+    }
+}
+
+pub struct OverlayApp {
+    data: GpuMonitoringData,
+    config: Config,
+    /// The palette the style was last made from.
+    palette: Option<Palette>,
+    /// The size the window was last given, to follow the contents as GPUs come and go.
+    size: Option<Vec2>,
+    /// Whether the user has dragged the overlay away from its corner.
+    moved: bool,
+}
+
+impl OverlayApp {
+    pub fn new(config: Config) -> Self {
+        Self {
+            data: GpuMonitoringData::new(&config),
+            config,
+            palette: None,
+            size: None,
+            moved: false,
+        }
+    }
+}
+
+/// Renders a reading, coloured by its level, and optionally a sparkline of its history.
+fn render_reading(
+    ui: &mut Ui,
+    metric: MetricPanel,
+    monitor: &mut GpuDeviceMonitor,
+    overlay: &OverlayConfig,
+    levels: Option<GraphLevels>,
+) {
+    let sample = monitor.last_sample();
+    let value = match metric {
+        MetricPanel::Usage => sample.and_then(|sample| sample.utilization),
+        MetricPanel::Memory => sample.and_then(|sample| sample.memory_used.map(|m| m as f32)),
+        MetricPanel::Temperature => sample.and_then(|sample| sample.temperature),
+    };
+    let text = match (metric, value) {
+        (_, None) => "-".to_string(),
+        (MetricPanel::Usage, Some(value)) => format!("{:.0}%", value),
+        (MetricPanel::Memory, Some(value)) => bytes_to_mib_gib(value),
+        (MetricPanel::Temperature, Some(value)) => format!("{:.0}°C", value),
+    };
+    let color = match value
+        .zip(levels)
+        .map(|(v, levels)| levels.thresholds.level(v))
+    {
+        Some(Level::Critical) => ui.visuals().error_fg_color,
+        Some(Level::Warning) => ui.visuals().warn_fg_color,
+        _ => ui.visuals().strong_text_color(),
+    };
+    ui.label(RichText::new(text).monospace().color(color));
+
+    if overlay.sparklines {
+        let (graph, max_value) = match metric {
+            MetricPanel::Usage => (monitor.usage_graph_mut(), 100.0),
+            MetricPanel::Memory => {
+                let max_memory = monitor.max_memory() as f32;
+                (monitor.memory_graph_mut(), max_memory)
+            }
+            MetricPanel::Temperature => (monitor.temperature_graph_mut(), 100.0),
+        };
+        ui.allocate_ui(egui::vec2(48.0, 16.0), |ui| {
+            render_sparkline(ui, graph, max_value)
+        });
+    }
+}
+
+impl eframe::App for OverlayApp {
+    fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
+        // The background is painted with the configured opacity instead
+        [0.0; 4]
+    }
+
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let prefers_dark = frame
+            .info()
+            .system_theme
+            .map(|theme| theme == eframe::Theme::Dark);
+        let palette = self.config.palette(prefers_dark);
+        if self.palette.as_ref() != Some(&palette) {
+            ctx.set_style(make_style(&palette));
+            self.palette = Some(palette);
+        }
+
+        self.data.update();
+
+        let overlay = &self.config.overlay;
+        let fill = with_alpha(
+            ctx.style().visuals.panel_fill,
+            (overlay.opacity * 255.0).round() as u8,
+        );
+        let panel = egui::Frame::none()
+            .fill(fill)
+            .rounding(6.0)
+            .inner_margin(8.0);
+
+        let mut content_size = Vec2::ZERO;
+        egui::CentralPanel::default().frame(panel).show(ctx, |ui| {
+            let gpu_count = self.data.gpus().len();
+            if gpu_count == 0 {
+                ui.label("Waiting for GPUs...");
+            }
+
+            egui::Grid::new("overlay").show(ui, |ui| {
+                for index in 0..gpu_count {
+                    let monitor = &mut self.data.gpus()[index];
+                    let max_memory = monitor.max_memory();
+                    ui.label(RichText::new(monitor.label()).small());
+                    for &metric in &overlay.metrics {
+                        let levels = match metric {
+                            MetricPanel::Usage => None,
+                            MetricPanel::Memory => self.config.graphs.memory_levels(max_memory),
+                            MetricPanel::Temperature => self.config.graphs.temperature_levels(),
+                        };
+                        ui.horizontal(|ui| render_reading(ui, metric, monitor, overlay, levels));
+                    }
+                    ui.end_row();
+                }
+            });
+            content_size = ui.min_rect().size() + Vec2::splat(16.0);
+
+            if !overlay.click_through {
+                let drag = ui.interact(ui.max_rect(), ui.id().with("drag"), egui::Sense::drag());
+                if drag.drag_started() {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::StartDrag);
+                    self.moved = true;
+                }
+            }
+        });
+
+        // Fit the window to its contents, keeping it in its corner
+        let monitor = ctx.input(|input| input.viewport().monitor_size);
+        if self.size != Some(content_size) {
+            if let Some(monitor) = monitor {
+                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(content_size));
+                if !self.moved {
+                    let position = overlay.position(content_size, monitor);
+                    ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(position));
+                }
+                self.size = Some(content_size);
+            }
+        }
+
+        ctx.request_repaint_after(Duration::from_millis(250));
+    }
+}
@@ -4,58 +4,84 @@ use eframe::{
     epaint::Rect,
 };
 use egui_extras::{Column, TableBuilder};
+
+use crate::utils::{bytes_to_mib_gib, format_duration};
+
+use super::graph::{render_graph, render_sparkline};
 use nvml_wrapper::enums::device::UsedGpuMemory;
 
-use crate::data::process_table::{
-    ProcessTableData, ProcessTableSorting, SortingDirection, TableColumn,
+use crate::{
+    data::{
+        process_filter::ProcessFilter,
+        process_history::ProcessHistory,
+        process_table::{ProcessTableData, SortingDirection, TableColumn, TableSorting},
+    },
+    processes::ProcessKind,
 };
 
-pub fn render_process_table(ui: &mut egui::Ui, data: &mut ProcessTableData) {
+/// Renders the processes matching the table's filter, and only those of `kind` if given.
+/// The kind comes from the selected tab, so it's kept out of the filter shared with the
+/// other views.
+pub fn render_process_table(
+    ui: &mut egui::Ui,
+    data: &mut ProcessTableData,
+    kind: Option<ProcessKind>,
+) {
     if let Err(err) = &data.processes() {
         ui.label("Failed to fetch process list");
         ui.label(format!("Error: {}", err));
         return;
     }
 
+    render_filter_bar(ui, data.filter_mut());
+
     let old_spacing = ui.style_mut().spacing.item_spacing.x;
     ui.style_mut().spacing.item_spacing.x = 0.0;
 
+    // Labels would otherwise swallow the clicks used to select rows
+    ui.style_mut().interaction.selectable_labels = false;
+
     let table = TableBuilder::new(ui)
         .striped(true)
+        .sense(Sense::click())
         .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
         .column(Column::auto())
         .column(Column::initial(150.0).clip(true).resizable(true))
-        .column(Column::initial(150.0).clip(true).resizable(true))
+        .column(Column::initial(70.0).clip(true).resizable(true))
+        .column(Column::initial(110.0).clip(true).resizable(true))
+        .column(Column::initial(80.0).clip(true).resizable(true))
         .column(Column::remainder())
         .header(20.0, |mut header| {
+            header.col(|ui| sortable_column_head(ui, TableColumn::Pid, "PID", data.sorting_mut()));
             header
-                .col(|ui| table_column_head(ui, TableColumn::Pid, "PID", &mut data.sorting_mut()));
+                .col(|ui| sortable_column_head(ui, TableColumn::Name, "Name", data.sorting_mut()));
+            header
+                .col(|ui| sortable_column_head(ui, TableColumn::Kind, "Type", data.sorting_mut()));
             header.col(|ui| {
-                table_column_head(ui, TableColumn::Name, "Name", &mut data.sorting_mut())
+                sortable_column_head(ui, TableColumn::GpuMemory, "GPU Memory", data.sorting_mut())
             });
             header.col(|ui| {
-                table_column_head(
-                    ui,
-                    TableColumn::GpuMemory,
-                    "GPU Memory",
-                    &mut data.sorting_mut(),
-                )
+                sortable_column_head(ui, TableColumn::GpuUsage, "GPU Usage", data.sorting_mut())
             });
             header.col(|ui| {
-                table_column_head(
-                    ui,
-                    TableColumn::GpuUsage,
-                    "GPU Usage",
-                    &mut data.sorting_mut(),
-                )
+                draw_table_cell(ui, |ui| {
+                    ui.add(Label::new("History").selectable(false));
+                });
             });
         });
 
+    let mut clicked_pid = None;
     table.body(|mut body| {
         let processes = data.get_processes_sorted();
         if let Ok(processes) = processes {
+            let processes = processes
+                .into_iter()
+                .filter(|process| kind.is_none_or(|kind| kind.includes(process.kind)));
             for process in processes {
+                let pid = process.info.pid;
                 body.row(20.0, |mut row| {
+                    row.set_selected(data.selected_pid() == Some(pid));
+
                     row.col(|ui| {
                         draw_table_cell(ui, |ui| {
                             ui.label(process.info.pid.to_string());
@@ -66,6 +92,11 @@ pub fn render_process_table(ui: &mut egui::Ui, data: &mut ProcessTableData) {
                             ui.label(&process.name);
                         });
                     });
+                    row.col(|ui| {
+                        draw_table_cell(ui, |ui| {
+                            ui.label(process.kind.label());
+                        });
+                    });
                     row.col(|ui| {
                         draw_table_cell(ui, |ui| {
                             ui.label(format_used_gpu_memory(&process.info.used_gpu_memory));
@@ -73,17 +104,86 @@ pub fn render_process_table(ui: &mut egui::Ui, data: &mut ProcessTableData) {
                     });
                     row.col(|ui| {
                         draw_table_cell(ui, |ui| {
-                            ui.label(format!("{}%", process.gpu_usage));
+                            ui.label(process.gpu_usage_label());
+                            if let Some(idle_for) = data.flagged_idle(process) {
+                                ui.colored_label(
+                                    ui.visuals().warn_fg_color,
+                                    format!("idle {}", format_duration(idle_for)),
+                                )
+                                .on_hover_text("Holding memory without using the GPU");
+                            }
                         });
                     });
+                    row.col(|ui| {
+                        if let Some(history) = data.history(pid) {
+                            render_history_sparkline(ui, history);
+                        }
+                    });
+
+                    if row.response().clicked() {
+                        clicked_pid = Some(pid);
+                    }
                 });
             }
         }
     });
 
+    if let Some(pid) = clicked_pid {
+        data.toggle_selected_pid(pid);
+    }
+
     ui.style_mut().spacing.item_spacing.x = old_spacing;
 }
 
+fn render_history_sparkline(ui: &mut egui::Ui, history: &ProcessHistory) {
+    let memory_graph = history.memory_graph();
+    let max_memory = memory_graph.max_value().unwrap_or(0.0);
+    render_sparkline(ui, memory_graph, max_memory);
+}
+
+fn render_filter_bar(ui: &mut egui::Ui, filter: &mut ProcessFilter) {
+    ui.horizontal(|ui| {
+        let mut query = filter.query().to_string();
+        let response = ui.add(
+            egui::TextEdit::singleline(&mut query)
+                .hint_text("Search name, pid, user or command")
+                .desired_width(220.0),
+        );
+        if response.changed() {
+            filter.set_query(query);
+        }
+
+        let mut use_regex = filter.use_regex();
+        if ui.checkbox(&mut use_regex, "Regex").changed() {
+            filter.set_use_regex(use_regex);
+        }
+
+        ui.checkbox(&mut filter.only_current_user, "Only mine");
+
+        let mut limit_memory = filter.min_memory_mib.is_some();
+        ui.checkbox(&mut limit_memory, "Min MiB");
+        if limit_memory {
+            let min_memory_mib = filter.min_memory_mib.get_or_insert(100);
+            ui.add(egui::DragValue::new(min_memory_mib).speed(10.0));
+        } else {
+            filter.min_memory_mib = None;
+        }
+
+        if filter.is_active() && ui.button("Clear").clicked() {
+            filter.clear();
+        }
+    });
+
+    if let Some(err) = filter.regex_error() {
+        ui.colored_label(
+            ui.visuals().error_fg_color,
+            format!("Invalid regex: {}", err),
+        );
+    }
+
+    ui.add_space(4.0);
+}
+
 fn make_cell_ui_in_cell_rect(
     ui: &mut egui::Ui,
     rect: Rect,
@@ -96,17 +196,18 @@ fn make_cell_ui_in_cell_rect(
     draw_contents(&mut child);
 }
 
-fn draw_table_cell(ui: &mut egui::Ui, draw_contents: impl FnOnce(&mut egui::Ui)) {
+pub(super) fn draw_table_cell(ui: &mut egui::Ui, draw_contents: impl FnOnce(&mut egui::Ui)) {
     let size = ui.available_size();
     let (rect, _) = ui.allocate_at_least(size, Sense::hover());
     make_cell_ui_in_cell_rect(ui, rect, draw_contents);
 }
 
-fn table_column_head(
+/// A column header that sorts the table by its column when clicked.
+pub(super) fn sortable_column_head<C: Copy + PartialEq>(
     ui: &mut egui::Ui,
-    column: TableColumn,
+    column: C,
     text: &str,
-    sorting: &mut ProcessTableSorting,
+    sorting: &mut TableSorting<C>,
 ) {
     let current_column_sorting = if sorting.column == column {
         Some(sorting.direction)
@@ -152,3 +253,51 @@ fn format_used_gpu_memory(memory: &UsedGpuMemory) -> String {
         UsedGpuMemory::Used(bytes) => format!("{:.2} MiB", *bytes as f64 / 1024.0 / 1024.0),
     }
 }
+
+/// Renders full graphs for the selected process, if there is one.
+pub fn render_process_detail(ui: &mut egui::Ui, data: &mut ProcessTableData, max_memory: u64) {
+    let Some((pid, history)) = data.selected_history() else {
+        return;
+    };
+
+    let mut close = false;
+    ui.horizontal(|ui| {
+        ui.strong(format!("{} ({})", history.name(), pid));
+        if history.has_exited() {
+            ui.weak("exited");
+        }
+        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+            close = ui.button("Close").clicked();
+        });
+    });
+
+    let width = ui.available_width();
+
+    ui.label("VRAM Usage");
+    ui.allocate_ui(egui::vec2(width, 80.0), |ui| {
+        render_graph(
+            ui,
+            "gpustat-process-vram",
+            history.memory_graph(),
+            max_memory as f32,
+            None,
+            bytes_to_mib_gib,
+        );
+    });
+
+    ui.label("GPU Usage %");
+    ui.allocate_ui(egui::vec2(width, 80.0), |ui| {
+        render_graph(
+            ui,
+            "gpustat-process-usage",
+            history.usage_graph(),
+            100.0,
+            None,
+            |v| format!("{:.0}%", v),
+        );
+    });
+
+    if close {
+        data.set_selected_pid(None);
+    }
+}
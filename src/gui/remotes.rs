@@ -0,0 +1,24 @@
+use eframe::egui;
+
+use crate::remote::client::{ConnectionStatus, RemoteConnection};
+
+/// Shown in place of the graphs until there's a GPU to show, e.g. while the only GPUs are
+/// on agents that haven't sent anything yet.
+pub fn render_waiting_for_gpus(ui: &mut egui::Ui, remotes: &[RemoteConnection]) {
+    ui.add_space(6.0);
+    if remotes.is_empty() {
+        ui.label("No GPUs found.");
+        return;
+    }
+
+    ui.label("Waiting for remote agents...");
+    ui.add_space(4.0);
+    for remote in remotes {
+        let status = match remote.status() {
+            ConnectionStatus::Connecting => "connecting".to_string(),
+            ConnectionStatus::Connected => "connected".to_string(),
+            ConnectionStatus::Disconnected(err) => err,
+        };
+        ui.label(format!("{}: {}", remote.address(), status));
+    }
+}
@@ -8,6 +8,8 @@ use eframe::{
     epaint::{Color32, FontFamily, FontId, Vec2},
 };
 
+use crate::theme::{Palette, Rgb};
+
 fn color_lerp(a: Color32, b: Color32, t: f32) -> Color32 {
     let t = t.clamp(0.0, 1.0);
     let inv_t = 1.0 - t;
@@ -18,17 +20,34 @@ fn color_lerp(a: Color32, b: Color32, t: f32) -> Color32 {
     )
 }
 
-pub fn make_style() -> Style {
-    let lighten_color = Color32::from_rgb(215, 204, 204);
+pub fn color(rgb: Rgb) -> Color32 {
+    Color32::from_rgb(rgb.0, rgb.1, rgb.2)
+}
+
+pub fn make_style(palette: &Palette) -> Style {
+    // Light themes are "lightened" towards black, for stripes and hovers that stand out
+    let lighten_color = if palette.dark {
+        Color32::from_rgb(215, 204, 204)
+    } else {
+        Color32::from_rgb(40, 40, 40)
+    };
 
     let lighten_by = |color: Color32, amount: f32| color_lerp(color, lighten_color, amount);
 
-    let primary = Color32::from_rgb(30, 164, 48);
-    let primary_hover = Color32::from_rgb(24, 149, 48);
-    let primary_interact = Color32::from_rgb(20, 130, 46);
-    let primary_text = Color32::from_rgb(194, 255, 215);
-    let bg = Color32::from_rgb(23, 18, 18);
-    let bg_dark = Color32::from_rgb(16, 10, 10);
+    let primary = color(palette.primary);
+    let primary_hover = color(palette.primary_hover);
+    let primary_interact = color(palette.primary_active);
+    let primary_text = color(palette.primary_text);
+    let bg = color(palette.background);
+    let bg_dark = color(palette.background_dark);
+
+    let base = if palette.dark {
+        Visuals::dark()
+    } else {
+        Visuals::light()
+    };
+    let mut noninteractive = base.widgets.noninteractive;
+    noninteractive.fg_stroke.color = color(palette.text);
 
     Style {
         visuals: Visuals {
@@ -57,12 +76,16 @@ pub fn make_style() -> Style {
                     fg_stroke: (1.0, primary_text).into(),
                     weak_bg_fill: lighten_by(primary, 0.1),
                 },
-                ..Default::default()
+                noninteractive,
+                ..base.widgets
             },
             faint_bg_color: lighten_by(bg, 0.1),
             extreme_bg_color: bg_dark,
             panel_fill: bg,
-            ..Default::default()
+            hyperlink_color: color(palette.accent),
+            warn_fg_color: color(palette.warning),
+            error_fg_color: color(palette.error),
+            ..base
         },
         spacing: Spacing {
             button_padding: Vec2::new(8.0, 4.0),
@@ -0,0 +1,174 @@
+use std::time::SystemTime;
+
+use eframe::{
+    egui::{self, RichText, Sense, TextStyle, Ui},
+    emath::Align2,
+    epaint::{Color32, Pos2, Rect},
+};
+
+use crate::{
+    data::{throttle::ThrottleReason, GpuDeviceMonitor},
+    utils::format_elapsed,
+};
+
+use super::graph::with_alpha;
+
+const LANE_HEIGHT: f32 = 14.0;
+
+/// Whether a reason means the hardware stepped in, rather than the driver keeping within a
+/// limit or setting.
+fn is_severe(reason: ThrottleReason) -> bool {
+    matches!(
+        reason,
+        ThrottleReason::HwThermal | ThrottleReason::HwSlowdown | ThrottleReason::HwPowerBrake
+    )
+}
+
+fn reason_color(ui: &Ui, reason: ThrottleReason) -> Color32 {
+    let visuals = ui.visuals();
+    if is_severe(reason) {
+        visuals.error_fg_color
+    } else if reason == ThrottleReason::SwThermal {
+        visuals.warn_fg_color
+    } else {
+        visuals.widgets.active.bg_fill
+    }
+}
+
+/// Renders a badge saying whether and why the GPU is throttled, the latest XID error, and a
+/// lane under the graphs for each reason it's been throttled for. Each sample is a pixel wide,
+/// like in the graphs, and XID errors are marked across the lanes.
+pub fn render_throttle_strip(ui: &mut Ui, monitor: &GpuDeviceMonitor) {
+    // Lined up with the plot area of the graphs, which have room for labels either side
+    let margin = 10.5 + 50.0;
+    let width = ui.available_width();
+    let sample_count = (width - margin * 2.0).max(0.0) as usize + 1;
+
+    // GPUs that don't report throttle reasons or XID errors get no strip
+    let history = monitor.throttle_history();
+    let known = history.is_known(sample_count);
+    if !known && monitor.health_events().is_empty() {
+        return;
+    }
+    let current = history.current().unwrap_or_default();
+    let reasons = history.reasons_seen(sample_count);
+
+    ui.horizontal(|ui| {
+        if known && current.is_empty() {
+            ui.weak(current.describe());
+        } else if known {
+            let color = match current.iter().any(is_severe) {
+                true => ui.visuals().error_fg_color,
+                false => ui.visuals().warn_fg_color,
+            };
+            ui.label(RichText::new(format!("⚠ {}", current.describe())).color(color));
+        }
+
+        if let Some(event) = monitor.health_events().back() {
+            let elapsed = event.time.elapsed().unwrap_or_default();
+            if known {
+                ui.separator();
+            }
+            ui.label(
+                RichText::new(format!("{}, {}", event, format_elapsed(elapsed)))
+                    .color(ui.visuals().error_fg_color),
+            );
+        }
+    });
+
+    if reasons.is_empty() {
+        return;
+    }
+
+    let height = LANE_HEIGHT * reasons.len() as f32;
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(width, height), Sense::hover());
+    let rect = Rect::from_min_max(
+        Pos2::new(rect.left() + margin, rect.top()),
+        Pos2::new(rect.right() - margin, rect.bottom()),
+    );
+
+    let painter = ui.painter_at(rect);
+    let visuals = ui.visuals();
+    painter.rect_filled(rect, 0.0, visuals.extreme_bg_color);
+
+    let history = monitor.throttle_history();
+    let font = ui.style().text_styles[&TextStyle::Small].clone();
+    for (lane, &reason) in reasons.iter().enumerate() {
+        let top = rect.top() + lane as f32 * LANE_HEIGHT;
+        let color = with_alpha(reason_color(ui, reason), 160);
+
+        // Join up neighbouring samples with the reason into one bar, newest at the right
+        let mut run: Option<(usize, usize)> = None;
+        let draw_run = |(newest, oldest): (usize, usize)| {
+            painter.rect_filled(
+                Rect::from_min_max(
+                    Pos2::new(rect.right() - oldest as f32 - 1.0, top + 1.0),
+                    Pos2::new(rect.right() - newest as f32, top + LANE_HEIGHT - 1.0),
+                ),
+                0.0,
+                color,
+            );
+        };
+        for index in 0..sample_count {
+            let throttled = history
+                .get_at(index)
+                .is_some_and(|reasons| reasons.contains(reason));
+            run = match (run, throttled) {
+                (Some((newest, _)), true) => Some((newest, index)),
+                (None, true) => Some((index, index)),
+                (run, false) => {
+                    if let Some(run) = run {
+                        draw_run(run);
+                    }
+                    None
+                }
+            };
+        }
+        if let Some(run) = run {
+            draw_run(run);
+        }
+
+        painter.text(
+            Pos2::new(rect.left() + 4.0, top + LANE_HEIGHT / 2.0),
+            Align2::LEFT_CENTER,
+            reason.label(),
+            font.clone(),
+            visuals.text_color(),
+        );
+    }
+
+    // XID errors, at the sample taken around the time they happened
+    let error_color = visuals.error_fg_color;
+    let usage_graph = monitor.usage_graph();
+    for event in monitor.health_events() {
+        let index = (0..sample_count).find(|&index| {
+            usage_graph
+                .get_sample_at(index)
+                .is_some_and(|(time, _)| time <= event.time)
+        });
+        if let Some(index) = index {
+            let x = rect.right() - index as f32 - 0.5;
+            painter.line_segment(
+                [Pos2::new(x, rect.top()), Pos2::new(x, rect.bottom())],
+                (2.0, error_color),
+            );
+        }
+    }
+
+    if let Some(pointer) = ui
+        .ctx()
+        .pointer_hover_pos()
+        .filter(|pos| rect.contains(*pos))
+    {
+        let index = (rect.right() - pointer.x).max(0.0) as usize;
+        let time = usage_graph.get_sample_at(index).map(|(time, _)| time);
+        let reasons = history.get_at(index).unwrap_or_default();
+        egui::show_tooltip_at_pointer(ui.ctx(), ui.id().with("throttle_tooltip"), |ui| {
+            if let Some(time) = time {
+                let elapsed = SystemTime::now().duration_since(time).unwrap_or_default();
+                ui.weak(format_elapsed(elapsed));
+            }
+            ui.label(reasons.describe());
+        });
+    }
+}
@@ -0,0 +1,793 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    process::Command,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::data::{
+    alerts::{Alert, AlertTransition},
+    events::{ProcessEvent, ProcessEventKind},
+};
+
+/// Something that happened which hooks can be notified about.
+#[derive(Clone, Debug)]
+pub enum HookEvent {
+    Alert(AlertTransition),
+    Process(ProcessEvent),
+}
+
+impl HookEvent {
+    fn is_alert(&self) -> bool {
+        matches!(self, HookEvent::Alert(_))
+    }
+
+    /// A one line, human readable description of the event.
+    fn summary(&self) -> String {
+        match self {
+            HookEvent::Alert(AlertTransition::Fired(alert)) => format!(
+                "[firing] {} on GPU {} ({}): {}",
+                alert.rule, alert.device_index, alert.device_name, alert.message
+            ),
+            HookEvent::Alert(AlertTransition::Resolved(alert)) => format!(
+                "[resolved] {} on GPU {} ({})",
+                alert.rule, alert.device_index, alert.device_name
+            ),
+            HookEvent::Process(event) => event.to_string(),
+        }
+    }
+
+    /// What the event is about, and the state it reports.
+    fn dedup_key(&self) -> DedupKey {
+        match self {
+            HookEvent::Alert(transition) => {
+                let (alert, state) = alert_state(transition);
+                DedupKey {
+                    subject: format!("alert:{}:{}", alert.device_uuid, alert.rule),
+                    state,
+                    ongoing: matches!(transition, AlertTransition::Fired(_)),
+                }
+            }
+            HookEvent::Process(event) => {
+                let state = match event.kind {
+                    ProcessEventKind::Started { .. } => "started",
+                    ProcessEventKind::Exited { .. } => "exited",
+                    ProcessEventKind::ShortLived { .. } => "short_lived",
+                };
+                DedupKey {
+                    subject: format!(
+                        "process:{}:{}:{}",
+                        event.host.as_deref().unwrap_or_default(),
+                        event.gpu_index,
+                        event.pid
+                    ),
+                    state,
+                    ongoing: matches!(event.kind, ProcessEventKind::Started { .. }),
+                }
+            }
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+
+        match self {
+            HookEvent::Alert(transition) => {
+                let (alert, state) = alert_state(transition);
+                json!({
+                    "type": "alert",
+                    "time": now,
+                    "state": state,
+                    "rule": alert.rule,
+                    "message": alert.message,
+                    "summary": self.summary(),
+                    "gpu": {
+                        "index": alert.device_index,
+                        "uuid": alert.device_uuid,
+                        "name": alert.device_name,
+                    },
+                })
+            }
+            HookEvent::Process(event) => {
+                let time = event
+                    .time
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                let mut value = json!({
+                    "type": "process",
+                    "time": time,
+                    "pid": event.pid,
+                    "name": event.name,
+                    "summary": self.summary(),
+                    "gpu": { "index": event.gpu_index, "host": event.host },
+                });
+                let details = match &event.kind {
+                    ProcessEventKind::Started { memory } => json!({
+                        "event": "started",
+                        "memory_bytes": memory,
+                    }),
+                    ProcessEventKind::Exited {
+                        peak_memory,
+                        duration,
+                    } => json!({
+                        "event": "exited",
+                        "peak_memory_bytes": peak_memory,
+                        "duration_secs": duration.as_secs_f64(),
+                    }),
+                    ProcessEventKind::ShortLived {
+                        peak_memory,
+                        duration,
+                    } => json!({
+                        "event": "short_lived",
+                        "peak_memory_bytes": peak_memory,
+                        "duration_secs": duration.as_secs_f64(),
+                    }),
+                };
+                if let (Some(value), Value::Object(details)) = (value.as_object_mut(), details) {
+                    value.extend(details);
+                }
+                value
+            }
+        }
+    }
+
+    /// Environment variables describing the event, for exec hooks.
+    fn env_vars(&self) -> Vec<(&'static str, String)> {
+        let mut vars = vec![
+            ("GPUSTAT_EVENT_SUMMARY", self.summary()),
+            ("GPUSTAT_EVENT_JSON", self.to_json().to_string()),
+        ];
+
+        match self {
+            HookEvent::Alert(transition) => {
+                let (alert, state) = alert_state(transition);
+                vars.extend([
+                    ("GPUSTAT_EVENT_TYPE", "alert".to_string()),
+                    ("GPUSTAT_ALERT_STATE", state.to_string()),
+                    ("GPUSTAT_ALERT_RULE", alert.rule.clone()),
+                    ("GPUSTAT_ALERT_MESSAGE", alert.message.clone()),
+                    ("GPUSTAT_GPU_INDEX", alert.device_index.to_string()),
+                    ("GPUSTAT_GPU_UUID", alert.device_uuid.clone()),
+                    ("GPUSTAT_GPU_NAME", alert.device_name.clone()),
+                ]);
+            }
+            HookEvent::Process(event) => {
+                vars.extend([
+                    ("GPUSTAT_EVENT_TYPE", "process".to_string()),
+                    ("GPUSTAT_PROCESS_PID", event.pid.to_string()),
+                    ("GPUSTAT_PROCESS_NAME", event.name.clone()),
+                    ("GPUSTAT_GPU_INDEX", event.gpu_index.to_string()),
+                ]);
+                if let Some(host) = &event.host {
+                    vars.push(("GPUSTAT_GPU_HOST", host.clone()));
+                }
+            }
+        }
+
+        vars
+    }
+}
+
+fn alert_state(transition: &AlertTransition) -> (&Alert, &'static str) {
+    match transition {
+        AlertTransition::Fired(alert) => (alert, "firing"),
+        AlertTransition::Resolved(alert) => (alert, "resolved"),
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    /// The full event as JSON.
+    #[default]
+    Json,
+    /// A `{"text": ...}` payload accepted by Slack and compatible incoming webhooks.
+    Slack,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HookSink {
+    /// POSTs each event to a URL.
+    Webhook {
+        url: String,
+        #[serde(default)]
+        format: WebhookFormat,
+    },
+    /// Runs a shell command with the event described in environment variables.
+    Exec { command: String },
+    /// Writes each event to the local syslog socket, which journald also listens on.
+    Syslog,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_per_minute() -> usize {
+    10
+}
+
+fn default_dedup_secs() -> u64 {
+    300
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct HookConfig {
+    #[serde(flatten)]
+    pub sink: HookSink,
+    /// Whether alerts firing and resolving are delivered.
+    #[serde(default = "default_true")]
+    pub alerts: bool,
+    /// Whether processes starting and exiting are delivered.
+    #[serde(default = "default_true")]
+    pub processes: bool,
+    /// Events beyond this many in a minute are dropped.
+    #[serde(default = "default_max_per_minute")]
+    pub max_per_minute: usize,
+    /// Repeats of the same event within this many seconds are dropped.
+    #[serde(default = "default_dedup_secs")]
+    pub dedup_secs: u64,
+    /// How many times a failed delivery is retried.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+impl HookSink {
+    fn deliver(&self, event: &HookEvent) -> Result<(), String> {
+        match self {
+            HookSink::Webhook { url, format } => {
+                let payload = match format {
+                    WebhookFormat::Json => event.to_json(),
+                    WebhookFormat::Slack => json!({ "text": event.summary() }),
+                };
+                ureq::post(url)
+                    .timeout(Duration::from_secs(10))
+                    .set("Content-Type", "application/json")
+                    .send_string(&payload.to_string())
+                    .map(|_| ())
+                    .map_err(|err| err.to_string())
+            }
+            HookSink::Exec { command } => {
+                let status = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .envs(event.env_vars())
+                    .status()
+                    .map_err(|err| err.to_string())?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(format!("command exited with {}", status))
+                }
+            }
+            HookSink::Syslog => {
+                send_to_syslog(&event.summary(), event.is_alert()).map_err(|err| err.to_string())
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn send_to_syslog(message: &str, is_alert: bool) -> io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    // Facility "user" (1), with severity "warning" (4) for alerts and "info" (6) otherwise
+    let priority = if is_alert { 8 + 4 } else { 8 + 6 };
+    let line = format!("<{}>gpustat[{}]: {}", priority, std::process::id(), message);
+
+    let socket = UnixDatagram::unbound()?;
+    socket.send_to(line.as_bytes(), "/dev/log")?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send_to_syslog(_message: &str, _is_alert: bool) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "syslog hooks are only supported on unix",
+    ))
+}
+
+struct DedupKey {
+    /// e.g. an alert rule on a GPU, or a process on a GPU.
+    subject: String,
+    /// e.g. `firing` or `resolved`.
+    state: &'static str,
+    /// Whether a later event is expected to end the state, like an alert resolving.
+    ongoing: bool,
+}
+
+/// The last state delivered about a subject.
+struct Delivered {
+    state: &'static str,
+    ongoing: bool,
+    time: Instant,
+}
+
+/// Decides which events a hook should actually deliver.
+///
+/// Repeats of the state last delivered about a subject are dropped within the dedup
+/// window, and other events past the per-minute limit. Changes of state, like an alert
+/// resolving after it fired, are always delivered, so the hook never shows a stale state.
+struct DeliveryLimiter {
+    max_per_minute: usize,
+    dedup_window: Duration,
+    recent_deliveries: VecDeque<Instant>,
+    last_delivered: HashMap<String, Delivered>,
+}
+
+impl DeliveryLimiter {
+    fn new(config: &HookConfig) -> Self {
+        Self {
+            max_per_minute: config.max_per_minute,
+            dedup_window: Duration::from_secs(config.dedup_secs),
+            recent_deliveries: VecDeque::new(),
+            last_delivered: HashMap::new(),
+        }
+    }
+
+    /// Returns true if the event should be delivered, recording it if so.
+    fn allow(&mut self, event: &HookEvent, now: Instant) -> bool {
+        let minute = Duration::from_secs(60);
+        while self
+            .recent_deliveries
+            .front()
+            .is_some_and(|time| now.duration_since(*time) >= minute)
+        {
+            self.recent_deliveries.pop_front();
+        }
+        // Ongoing states are kept until they end, so that the change is recognised
+        self.last_delivered.retain(|_, delivered| {
+            delivered.ongoing || now.duration_since(delivered.time) < self.dedup_window
+        });
+
+        let key = event.dedup_key();
+        let last = self.last_delivered.get(&key.subject);
+        let changes_state = last.is_some_and(|last| last.state != key.state);
+        let is_repeat = last.is_some_and(|last| {
+            last.state == key.state && now.duration_since(last.time) < self.dedup_window
+        });
+        if is_repeat {
+            return false;
+        }
+        if !changes_state && self.recent_deliveries.len() >= self.max_per_minute {
+            return false;
+        }
+
+        self.last_delivered.insert(
+            key.subject,
+            Delivered {
+                state: key.state,
+                ongoing: key.ongoing,
+                time: now,
+            },
+        );
+        self.recent_deliveries.push_back(now);
+        true
+    }
+}
+
+fn run_hook_worker(config: HookConfig, receiver: Receiver<HookEvent>) {
+    let mut limiter = DeliveryLimiter::new(&config);
+
+    for event in receiver {
+        if !limiter.allow(&event, Instant::now()) {
+            continue;
+        }
+
+        if let Err(err) =
+            deliver_with_retries(&config.sink, &event, config.retries, Duration::from_secs(1))
+        {
+            eprintln!("Failed to deliver event to hook: {}", err);
+        }
+    }
+}
+
+/// Delivers an event, retrying failures with a backoff that doubles after each attempt.
+fn deliver_with_retries(
+    sink: &HookSink,
+    event: &HookEvent,
+    retries: u32,
+    mut backoff: Duration,
+) -> Result<(), String> {
+    let mut attempt = 0;
+    loop {
+        match sink.deliver(event) {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt == retries => return Err(err),
+            Err(_) => {
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Sends events to every configured hook. Each hook delivers on its own thread, so a slow
+/// or unreachable endpoint doesn't hold up the UI or the other hooks.
+pub struct HookDispatcher {
+    hooks: Vec<(HookConfig, Sender<HookEvent>)>,
+}
+
+impl HookDispatcher {
+    pub fn new(configs: &[HookConfig]) -> Self {
+        let hooks = configs
+            .iter()
+            .map(|config| {
+                let (sender, receiver) = mpsc::channel();
+                let worker_config = config.clone();
+                thread::spawn(move || run_hook_worker(worker_config, receiver));
+                (config.clone(), sender)
+            })
+            .collect();
+
+        Self { hooks }
+    }
+
+    pub fn dispatch(&self, event: HookEvent) {
+        for (config, sender) in &self.hooks {
+            let wanted = match event {
+                HookEvent::Alert(_) => config.alerts,
+                HookEvent::Process(_) => config.processes,
+            };
+            if wanted {
+                // The worker only goes away if it panicked, in which case there's nothing to do
+                let _ = sender.send(event.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    fn alert_event() -> HookEvent {
+        HookEvent::Alert(AlertTransition::Fired(Alert {
+            rule: "High temperature".to_string(),
+            message: "temperature 90°C is above 85°C".to_string(),
+            device_index: 1,
+            device_uuid: "GPU-1".to_string(),
+            device_name: "Test GPU".to_string(),
+            command: None,
+            since: Instant::now(),
+        }))
+    }
+
+    fn resolved_alert_event() -> HookEvent {
+        let HookEvent::Alert(AlertTransition::Fired(alert)) = alert_event() else {
+            unreachable!();
+        };
+        HookEvent::Alert(AlertTransition::Resolved(alert))
+    }
+
+    fn process_event(host: Option<&str>, pid: u32) -> HookEvent {
+        HookEvent::Process(ProcessEvent {
+            time: SystemTime::now(),
+            host: host.map(str::to_string),
+            gpu_index: 0,
+            pid,
+            name: "python".to_string(),
+            kind: ProcessEventKind::Started { memory: 1024 },
+        })
+    }
+
+    fn hook_config(sink: HookSink) -> HookConfig {
+        HookConfig {
+            sink,
+            alerts: true,
+            processes: true,
+            max_per_minute: default_max_per_minute(),
+            dedup_secs: default_dedup_secs(),
+            retries: default_retries(),
+        }
+    }
+
+    /// A local HTTP server that answers each request with the next status in `statuses`,
+    /// then 200, and records the bodies it's sent.
+    struct StubServer {
+        url: String,
+        bodies: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl StubServer {
+        fn start(statuses: Vec<u16>) -> Self {
+            Self::start_on(TcpListener::bind("127.0.0.1:0").unwrap(), statuses)
+        }
+
+        fn start_on(listener: TcpListener, statuses: Vec<u16>) -> Self {
+            let url = format!("http://{}/hook", listener.local_addr().unwrap());
+            let bodies = Arc::new(Mutex::new(Vec::new()));
+
+            let recorded = bodies.clone();
+            thread::spawn(move || {
+                let mut statuses = statuses.into_iter();
+                for stream in listener.incoming() {
+                    let Ok(mut stream) = stream else {
+                        return;
+                    };
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        let line = line.trim_end();
+                        if line.is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                content_length = value.trim().parse().unwrap();
+                            }
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+                    recorded
+                        .lock()
+                        .unwrap()
+                        .push(String::from_utf8(body).unwrap());
+
+                    let status = statuses.next().unwrap_or(200);
+                    let response = format!(
+                        "HTTP/1.1 {} Stub\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        status
+                    );
+                    stream.write_all(response.as_bytes()).unwrap();
+                }
+            });
+
+            Self { url, bodies }
+        }
+
+        fn bodies(&self) -> Vec<String> {
+            self.bodies.lock().unwrap().clone()
+        }
+
+        fn webhook(&self, format: WebhookFormat) -> HookSink {
+            HookSink::Webhook {
+                url: self.url.clone(),
+                format,
+            }
+        }
+    }
+
+    #[test]
+    fn webhooks_post_the_event_as_json() {
+        let server = StubServer::start(vec![]);
+        server
+            .webhook(WebhookFormat::Json)
+            .deliver(&alert_event())
+            .unwrap();
+
+        let bodies = server.bodies();
+        assert_eq!(bodies.len(), 1);
+        let body: Value = serde_json::from_str(&bodies[0]).unwrap();
+        assert_eq!(body["type"], "alert");
+        assert_eq!(body["state"], "firing");
+        assert_eq!(body["rule"], "High temperature");
+        assert_eq!(body["message"], "temperature 90°C is above 85°C");
+        assert_eq!(body["gpu"]["index"], 1);
+        assert_eq!(body["gpu"]["uuid"], "GPU-1");
+        assert_eq!(body["gpu"]["name"], "Test GPU");
+    }
+
+    #[test]
+    fn slack_webhooks_post_only_the_summary() {
+        let server = StubServer::start(vec![]);
+        server
+            .webhook(WebhookFormat::Slack)
+            .deliver(&alert_event())
+            .unwrap();
+
+        let body: Value = serde_json::from_str(&server.bodies()[0]).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "text": "[firing] High temperature on GPU 1 (Test GPU): temperature 90°C is above 85°C"
+            })
+        );
+    }
+
+    #[test]
+    fn retries_server_errors() {
+        let server = StubServer::start(vec![500, 503]);
+        let sink = server.webhook(WebhookFormat::Json);
+
+        let result = deliver_with_retries(&sink, &alert_event(), 3, Duration::from_millis(10));
+        assert!(result.is_ok());
+        assert_eq!(server.bodies().len(), 3);
+    }
+
+    #[test]
+    fn gives_up_after_the_last_retry() {
+        let server = StubServer::start(vec![500, 500, 500]);
+        let sink = server.webhook(WebhookFormat::Json);
+
+        let result = deliver_with_retries(&sink, &alert_event(), 1, Duration::from_millis(10));
+        assert!(result.is_err());
+        assert_eq!(server.bodies().len(), 2);
+    }
+
+    #[test]
+    fn retries_refused_connections_with_backoff() {
+        // Nothing is listening on the port until after the first couple of attempts
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let sink = HookSink::Webhook {
+            url: format!("http://{}/hook", address),
+            format: WebhookFormat::Json,
+        };
+
+        let server = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(250));
+            StubServer::start_on(TcpListener::bind(address).unwrap(), vec![])
+        });
+
+        let started = Instant::now();
+        let result = deliver_with_retries(&sink, &alert_event(), 5, Duration::from_millis(100));
+        assert!(result.is_ok(), "{:?}", result);
+        // At least the 100ms and 200ms backoffs passed before the server came up
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        assert_eq!(server.join().unwrap().bodies().len(), 1);
+    }
+
+    #[test]
+    fn refused_connections_fail_once_retries_run_out() {
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let sink = HookSink::Webhook {
+            url: format!("http://{}/hook", address),
+            format: WebhookFormat::Json,
+        };
+
+        let started = Instant::now();
+        let result = deliver_with_retries(&sink, &alert_event(), 2, Duration::from_millis(50));
+        assert!(result.is_err());
+        // Backed off for 50ms, then 100ms
+        assert!(started.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[test]
+    fn limits_deliveries_per_minute() {
+        let mut limiter = DeliveryLimiter::new(&HookConfig {
+            max_per_minute: 2,
+            ..hook_config(HookSink::Syslog)
+        });
+        let now = Instant::now();
+
+        assert!(limiter.allow(&process_event(None, 1), now));
+        assert!(limiter.allow(&process_event(None, 2), now));
+        assert!(!limiter.allow(&process_event(None, 3), now + Duration::from_secs(30)));
+        // A minute after the first deliveries there's room again
+        assert!(limiter.allow(&process_event(None, 4), now + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn drops_repeats_within_the_dedup_window() {
+        let mut limiter = DeliveryLimiter::new(&HookConfig {
+            dedup_secs: 300,
+            ..hook_config(HookSink::Syslog)
+        });
+        let now = Instant::now();
+
+        assert!(limiter.allow(&alert_event(), now));
+        assert!(!limiter.allow(&alert_event(), now + Duration::from_secs(299)));
+        assert!(limiter.allow(&alert_event(), now + Duration::from_secs(300)));
+    }
+
+    #[test]
+    fn delivers_every_change_of_state() {
+        let mut limiter = DeliveryLimiter::new(&HookConfig {
+            dedup_secs: 300,
+            ..hook_config(HookSink::Syslog)
+        });
+        let now = Instant::now();
+        let at = |secs| now + Duration::from_secs(secs);
+
+        // Firing again right after resolving isn't a repeat of the first firing
+        assert!(limiter.allow(&alert_event(), at(0)));
+        assert!(limiter.allow(&resolved_alert_event(), at(1)));
+        assert!(limiter.allow(&alert_event(), at(2)));
+        assert!(!limiter.allow(&alert_event(), at(3)));
+        assert!(limiter.allow(&resolved_alert_event(), at(4)));
+        assert!(!limiter.allow(&resolved_alert_event(), at(5)));
+
+        // An alert that fires for longer than the dedup window still resolves
+        assert!(limiter.allow(&alert_event(), at(1000)));
+        assert!(limiter.allow(&resolved_alert_event(), at(5000)));
+    }
+
+    #[test]
+    fn changes_of_state_are_not_rate_limited() {
+        let mut limiter = DeliveryLimiter::new(&HookConfig {
+            max_per_minute: 1,
+            ..hook_config(HookSink::Syslog)
+        });
+        let now = Instant::now();
+
+        assert!(limiter.allow(&alert_event(), now));
+        assert!(!limiter.allow(&process_event(None, 1), now));
+        assert!(limiter.allow(&resolved_alert_event(), now));
+    }
+
+    #[test]
+    fn processes_on_different_hosts_are_told_apart() {
+        let mut limiter = DeliveryLimiter::new(&hook_config(HookSink::Syslog));
+        let now = Instant::now();
+
+        assert!(limiter.allow(&process_event(None, 1), now));
+        assert!(limiter.allow(&process_event(Some("trainer"), 1), now));
+        assert!(!limiter.allow(&process_event(Some("trainer"), 1), now));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_hooks_get_the_event_in_environment_variables() {
+        let path = std::env::temp_dir().join(format!("gpustat-hook-test-{}", std::process::id()));
+        let sink = HookSink::Exec {
+            command: format!("env > '{}'", path.display()),
+        };
+        sink.deliver(&alert_event()).unwrap();
+
+        let output = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let vars = output
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .filter(|(name, _)| name.starts_with("GPUSTAT_"))
+            .collect::<HashMap<_, _>>();
+
+        assert_eq!(vars["GPUSTAT_EVENT_TYPE"], "alert");
+        assert_eq!(vars["GPUSTAT_ALERT_STATE"], "firing");
+        assert_eq!(vars["GPUSTAT_ALERT_RULE"], "High temperature");
+        assert_eq!(
+            vars["GPUSTAT_ALERT_MESSAGE"],
+            "temperature 90°C is above 85°C"
+        );
+        assert_eq!(vars["GPUSTAT_GPU_INDEX"], "1");
+        assert_eq!(vars["GPUSTAT_GPU_UUID"], "GPU-1");
+        assert_eq!(vars["GPUSTAT_GPU_NAME"], "Test GPU");
+        assert_eq!(
+            vars["GPUSTAT_EVENT_SUMMARY"],
+            "[firing] High temperature on GPU 1 (Test GPU): temperature 90°C is above 85°C"
+        );
+        let json: Value = serde_json::from_str(vars["GPUSTAT_EVENT_JSON"]).unwrap();
+        assert_eq!(json["rule"], "High temperature");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_exec_hooks_are_errors() {
+        let sink = HookSink::Exec {
+            command: "exit 3".to_string(),
+        };
+        assert!(sink.deliver(&alert_event()).is_err());
+    }
+}
@@ -0,0 +1,437 @@
+//! `gpustat run`, which runs a command and reports how much GPU its process tree used.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    process::{Command, ExitStatus},
+    sync::{atomic::AtomicBool, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use nvml_wrapper::enums::device::UsedGpuMemory;
+use serde::Serialize;
+use sysinfo::{ProcessRefreshKind, System};
+
+use crate::{
+    config::Config,
+    data::{GpuDeviceMonitor, GpuMonitoringData},
+    utils::bytes_to_mib_gib,
+};
+
+#[derive(Serialize)]
+struct GpuUsage {
+    gpu: String,
+    name: String,
+    peak_memory_bytes: u64,
+    /// SM usage averaged over the time since the command first used the GPU.
+    average_sm_percent: f64,
+    peak_sm_percent: u32,
+    /// SM usage integrated over time, so a GPU fully used for a minute is 60.
+    gpu_seconds: f64,
+}
+
+#[derive(Serialize)]
+struct JobReport {
+    command: Vec<String>,
+    /// `None` if the command was killed by a signal.
+    exit_code: Option<i32>,
+    duration_secs: f64,
+    gpu_seconds: f64,
+    gpus: Vec<GpuUsage>,
+}
+
+/// What's been seen of the command on one GPU.
+struct GpuTracker {
+    usage: GpuUsage,
+    last_refresh: Option<Instant>,
+    /// When the command was last sampled on the GPU, once it has started using it.
+    last_sample: Option<Instant>,
+    /// How long the command has been using the GPU, in seconds.
+    observed_secs: f64,
+}
+
+impl GpuTracker {
+    fn new(gpu: &GpuDeviceMonitor) -> Self {
+        Self {
+            usage: GpuUsage {
+                gpu: gpu.gpu_id(),
+                name: gpu.device_name().to_string(),
+                peak_memory_bytes: 0,
+                average_sm_percent: 0.0,
+                peak_sm_percent: 0,
+                gpu_seconds: 0.0,
+            },
+            last_refresh: None,
+            last_sample: None,
+            observed_secs: 0.0,
+        }
+    }
+
+    /// Adds the latest process list's numbers for the process tree, if the list is new.
+    fn update(&mut self, gpu: &GpuDeviceMonitor, tree: &ProcessTree) {
+        let refresh = gpu.processes().last_refresh();
+        if refresh.is_none() || refresh == self.last_refresh {
+            return;
+        }
+        self.last_refresh = refresh;
+
+        let Ok(processes) = gpu.processes().all_processes() else {
+            return;
+        };
+        let ours = processes
+            .iter()
+            .filter(|process| tree.contains_key(&process.info.pid))
+            .collect::<Vec<_>>();
+        if ours.is_empty() && self.last_sample.is_none() {
+            return;
+        }
+
+        let memory = ours
+            .iter()
+            .map(|process| match process.info.used_gpu_memory {
+                UsedGpuMemory::Used(bytes) => bytes,
+                UsedGpuMemory::Unavailable => 0,
+            })
+            .sum::<u64>();
+        let sm = ours
+            .iter()
+            .filter_map(|process| process.gpu_usage)
+            .sum::<u32>()
+            .min(100);
+
+        self.add_sample(memory, sm, Instant::now());
+    }
+
+    /// Adds a sample of the memory and SM usage of the process tree, counting the SM usage
+    /// as having held since the last sample.
+    fn add_sample(&mut self, memory: u64, sm: u32, now: Instant) {
+        if let Some(last_sample) = self.last_sample {
+            let elapsed = now.duration_since(last_sample).as_secs_f64();
+            self.observed_secs += elapsed;
+            self.usage.gpu_seconds += sm as f64 / 100.0 * elapsed;
+        }
+        self.last_sample = Some(now);
+
+        let usage = &mut self.usage;
+        usage.peak_memory_bytes = usage.peak_memory_bytes.max(memory);
+        usage.peak_sm_percent = usage.peak_sm_percent.max(sm);
+        if self.observed_secs > 0.0 {
+            usage.average_sm_percent = usage.gpu_seconds * 100.0 / self.observed_secs;
+        } else {
+            usage.average_sm_percent = sm as f64;
+        }
+    }
+}
+
+/// The command's processes by pid, with when each started, so that a process which reuses
+/// the pid of one that exited isn't mistaken for it.
+type ProcessTree = HashMap<u32, u64>;
+
+/// A running process, as far as the process tree is concerned.
+#[derive(Clone, Copy)]
+struct RunningProcess {
+    pid: u32,
+    parent: Option<u32>,
+    /// In seconds since the epoch.
+    start_time: u64,
+}
+
+fn update_process_tree(sys: &mut System, root: u32, tree: &mut ProcessTree) {
+    sys.refresh_processes_specifics(ProcessRefreshKind::new());
+
+    let running = sys
+        .processes()
+        .values()
+        .map(|process| RunningProcess {
+            pid: process.pid().as_u32(),
+            parent: process.parent().map(|parent| parent.as_u32()),
+            start_time: process.start_time(),
+        })
+        .collect::<Vec<_>>();
+    refresh_process_tree(&running, root, tree);
+}
+
+/// Updates `tree` to every running descendant of `root`, and `root` itself. Processes that
+/// have exited are dropped, so their pids can't be counted again once they're reused.
+fn refresh_process_tree(running: &[RunningProcess], root: u32, tree: &mut ProcessTree) {
+    let by_pid = running
+        .iter()
+        .map(|process| (process.pid, *process))
+        .collect::<HashMap<_, _>>();
+    let is_running = |pid: u32, start_time: u64| {
+        by_pid
+            .get(&pid)
+            .is_some_and(|process| process.start_time == start_time)
+    };
+
+    tree.retain(|pid, start_time| is_running(*pid, *start_time));
+    if let Some(root) = by_pid.get(&root) {
+        tree.insert(root.pid, root.start_time);
+    }
+
+    for process in running {
+        let mut ancestors = Vec::new();
+        let mut current = Some(*process);
+        while let Some(process) = current {
+            if tree.get(&process.pid) == Some(&process.start_time) {
+                tree.extend(ancestors);
+                break;
+            }
+            // A parent that was itself an ancestor would loop forever
+            if ancestors.len() > running.len() {
+                break;
+            }
+            ancestors.push((process.pid, process.start_time));
+            current = process
+                .parent
+                .and_then(|parent| by_pid.get(&parent).copied());
+        }
+    }
+}
+
+/// The exit code to exit with, following the shell convention of 128 plus the signal for
+/// commands killed by one.
+fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    1
+}
+
+fn print_summary(report: &JobReport) {
+    let command = report.command.join(" ");
+    match report.exit_code {
+        Some(code) => eprintln!(
+            "\n`{}` exited with code {} after {:.1}s",
+            command, code, report.duration_secs
+        ),
+        None => eprintln!(
+            "\n`{}` was killed after {:.1}s",
+            command, report.duration_secs
+        ),
+    }
+
+    if report.gpus.is_empty() {
+        eprintln!("It didn't use any GPU");
+        return;
+    }
+
+    eprintln!(
+        "{:<12} {:>10} {:>7} {:>8} {:>12}  NAME",
+        "GPU", "PEAK VRAM", "AVG SM", "PEAK SM", "GPU-SECONDS"
+    );
+    for gpu in &report.gpus {
+        eprintln!(
+            "{:<12} {:>10} {:>6.0}% {:>7}% {:>12.1}  {}",
+            gpu.gpu,
+            bytes_to_mib_gib(gpu.peak_memory_bytes as f32),
+            gpu.average_sm_percent,
+            gpu.peak_sm_percent,
+            gpu.gpu_seconds,
+            gpu.name
+        );
+    }
+    eprintln!("Total GPU-seconds: {:.1}", report.gpu_seconds);
+}
+
+/// Runs a command while sampling the GPU usage of it and its children, then prints a
+/// summary and returns the command's exit code.
+pub fn run_command(config: Config, command: &[String], report_path: Option<&Path>) -> i32 {
+    // The command's GPUs are local, so there's no need to connect to any agents
+    let config = Config {
+        remotes: Vec::new(),
+        ..config.without_alert_delivery()
+    };
+    let mut data = GpuMonitoringData::new(&config);
+
+    // Ctrl+C goes to the command as well, so wait for it to exit and still print the summary
+    if let Err(err) = signal_hook::flag::register(
+        signal_hook::consts::SIGINT,
+        Arc::new(AtomicBool::new(false)),
+    ) {
+        eprintln!("Failed to handle Ctrl+C: {}", err);
+    }
+
+    let start = Instant::now();
+    let mut child = match Command::new(&command[0]).args(&command[1..]).spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            eprintln!("Error: failed to run {}: {}", command[0], err);
+            return 127;
+        }
+    };
+
+    let mut sys = System::new();
+    let mut tree = ProcessTree::new();
+    let mut last_tree_update: Option<Instant> = None;
+    let mut trackers = Vec::<GpuTracker>::new();
+
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => {}
+            Err(err) => {
+                eprintln!("Error: failed to wait for {}: {}", command[0], err);
+                return 1;
+            }
+        }
+
+        data.update();
+
+        // The process lists are refreshed once a second, so the tree only needs to be too
+        if last_tree_update.is_none_or(|time| time.elapsed() >= Duration::from_secs(1)) {
+            last_tree_update = Some(Instant::now());
+            update_process_tree(&mut sys, child.id(), &mut tree);
+        }
+
+        let gpus = data.gpus();
+        for gpu in &gpus[trackers.len()..] {
+            trackers.push(GpuTracker::new(gpu));
+        }
+        for (tracker, gpu) in trackers.iter_mut().zip(gpus.iter()) {
+            tracker.update(gpu, &tree);
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    };
+
+    let gpus = trackers
+        .into_iter()
+        .filter(|tracker| tracker.last_sample.is_some())
+        .map(|tracker| tracker.usage)
+        .collect::<Vec<_>>();
+    let report = JobReport {
+        command: command.to_vec(),
+        exit_code: status.code(),
+        duration_secs: start.elapsed().as_secs_f64(),
+        gpu_seconds: gpus.iter().fold(0.0, |total, gpu| total + gpu.gpu_seconds),
+        gpus,
+    };
+
+    print_summary(&report);
+    if let Some(path) = report_path {
+        let json = serde_json::to_string_pretty(&report).unwrap();
+        if let Err(err) = fs::write(path, json) {
+            eprintln!("Failed to write the report to {}: {}", path.display(), err);
+        }
+    }
+
+    exit_code(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> GpuTracker {
+        GpuTracker {
+            usage: GpuUsage {
+                gpu: "gpu0".to_string(),
+                name: "Test GPU".to_string(),
+                peak_memory_bytes: 0,
+                average_sm_percent: 0.0,
+                peak_sm_percent: 0,
+                gpu_seconds: 0.0,
+            },
+            last_refresh: None,
+            last_sample: None,
+            observed_secs: 0.0,
+        }
+    }
+
+    #[test]
+    fn integrates_sm_usage_over_time() {
+        let mut tracker = tracker();
+        let start = Instant::now();
+
+        tracker.add_sample(1024, 50, start);
+        assert_eq!(tracker.usage.gpu_seconds, 0.0);
+        assert_eq!(tracker.usage.average_sm_percent, 50.0);
+
+        // Fully used for two seconds, then idle for one
+        tracker.add_sample(4096, 100, start + Duration::from_secs(2));
+        tracker.add_sample(2048, 0, start + Duration::from_secs(3));
+
+        let usage = &tracker.usage;
+        assert_eq!(usage.gpu_seconds, 2.0);
+        assert!((usage.average_sm_percent - 200.0 / 3.0).abs() < 1e-9);
+        assert_eq!(usage.peak_sm_percent, 100);
+        assert_eq!(usage.peak_memory_bytes, 4096);
+    }
+
+    fn process(pid: u32, parent: Option<u32>, start_time: u64) -> RunningProcess {
+        RunningProcess {
+            pid,
+            parent,
+            start_time,
+        }
+    }
+
+    #[test]
+    fn tracks_the_descendants_of_the_command() {
+        let mut tree = ProcessTree::new();
+        let running = [
+            process(1, None, 0),
+            process(10, Some(1), 100),
+            process(11, Some(10), 101),
+            process(12, Some(11), 102),
+            process(20, Some(1), 50),
+        ];
+        refresh_process_tree(&running, 10, &mut tree);
+
+        let mut pids = tree.keys().copied().collect::<Vec<_>>();
+        pids.sort();
+        assert_eq!(pids, [10, 11, 12]);
+    }
+
+    #[test]
+    fn forgets_processes_that_exit_even_if_their_pid_is_reused() {
+        let mut tree = ProcessTree::new();
+        refresh_process_tree(
+            &[
+                process(1, None, 0),
+                process(10, Some(1), 100),
+                process(11, Some(10), 101),
+            ],
+            10,
+            &mut tree,
+        );
+        assert!(tree.contains_key(&11));
+
+        // 11 exits, and an unrelated process is started with its pid
+        refresh_process_tree(
+            &[
+                process(1, None, 0),
+                process(10, Some(1), 100),
+                process(11, Some(1), 200),
+            ],
+            10,
+            &mut tree,
+        );
+        assert!(!tree.contains_key(&11));
+        assert!(tree.contains_key(&10));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exits_with_the_commands_code_or_128_plus_its_signal() {
+        use std::os::unix::process::ExitStatusExt;
+
+        assert_eq!(exit_code(ExitStatus::from_raw(0)), 0);
+        assert_eq!(exit_code(ExitStatus::from_raw(3 << 8)), 3);
+        // Killed by SIGKILL and SIGTERM
+        assert_eq!(exit_code(ExitStatus::from_raw(9)), 137);
+        assert_eq!(exit_code(ExitStatus::from_raw(15)), 143);
+    }
+}
@@ -1,12 +1,322 @@
-use gui::run_gpu_app;
+use std::{path::PathBuf, time::Duration};
 
+use clap::{Parser, Subcommand};
+use config::Config;
+use data::process_filter::{ProcessFilter, ProcessFilterArgs};
+use export::{ExportFormat, ExportLayout};
+use gui::graph_export::GraphImageFormat;
+use gui::{run_gpu_app, AppMode};
+use plot::{PlotMetric, PlotOptions};
+use remote::{client::RemoteConfig, protocol::DEFAULT_PORT};
+use watch::{ColorChoice, WatchOptions};
+
+mod config;
 mod data;
+mod export;
 mod gui;
+mod hooks;
+mod job;
+mod notify;
+mod plot;
 mod processes;
+mod recording;
+mod remote;
+mod report;
+mod theme;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod tray;
 mod tui;
 mod utils;
+mod watch;
+mod web;
+
+#[derive(Parser)]
+#[command(version, about = "A simple utility for viewing GPU utilization")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Also show the GPUs of the agent at this address, as `host` or `host:port`
+    #[arg(long, global = true)]
+    remote: Vec<String>,
+    /// The token for agents given with --remote
+    #[arg(long, global = true, env = "GPUSTAT_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Open the graphical interface (the default)
+    Gui {
+        /// Show a small always-on-top overlay instead, as set up in the config's `[overlay]`
+        #[arg(long)]
+        overlay: bool,
+    },
+    /// Run the terminal interface
+    Tui,
+    /// Run in the system tray, opening the graphical interface from its menu
+    Tray,
+    /// List processes holding GPU memory without using the GPU
+    ///
+    /// With --remote or agents configured under [[remotes]], the agents are asked for the
+    /// processes they've seen idle, which covers as long as they've been running. Otherwise,
+    /// or with --local, this machine's GPUs are watched for --minutes first, so only idle
+    /// time from then on is counted.
+    IdleReport {
+        /// Report processes that have been idle for at least this many minutes
+        #[arg(long, default_value_t = 10)]
+        minutes: u64,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+        /// Watch this machine's GPUs even if agents are configured
+        #[arg(long)]
+        local: bool,
+        #[command(flatten)]
+        filter: ProcessFilterArgs,
+    },
+    /// Serve this machine's GPU stats to gpustat instances on other machines
+    Agent {
+        /// The address to listen on
+        #[arg(long, default_value_t = format!("0.0.0.0:{}", DEFAULT_PORT))]
+        listen: String,
+        /// Only accept clients that send this token
+        #[arg(long, env = "GPUSTAT_AGENT_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+    },
+    /// Serve a web page with live graphs and the process table
+    Serve {
+        /// Serve the web interface over HTTP
+        #[arg(long, required = true)]
+        http: bool,
+        /// The address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: String,
+    },
+    /// Record GPU and process samples to a file, one JSON object per line
+    Record {
+        /// The file to append samples to
+        #[arg(long)]
+        output: PathBuf,
+        /// Stop after this many seconds
+        #[arg(long)]
+        duration: Option<u64>,
+        /// Only record processes matching these options, GPU samples are always recorded
+        #[command(flatten)]
+        filter: ProcessFilterArgs,
+    },
+    /// Convert a recording to CSV or Parquet
+    Export {
+        /// A recording written by `gpustat record`
+        #[arg(long)]
+        input: PathBuf,
+        /// Where to write the GPU samples, the process samples go next to it
+        #[arg(long)]
+        output: PathBuf,
+        /// The output format, guessed from the output's extension if not given
+        #[arg(long, value_enum)]
+        format: Option<ExportFormat>,
+        /// One row per metric (long) or one column per metric (wide)
+        #[arg(long, value_enum, default_value_t = ExportLayout::Long)]
+        layout: ExportLayout,
+    },
+    /// Render a metric from a recording to a PNG or SVG graph like the app's
+    Plot {
+        /// A recording written by `gpustat record`
+        #[arg(long)]
+        input: PathBuf,
+        /// The image file to write
+        #[arg(long)]
+        output: PathBuf,
+        #[arg(long, value_enum, default_value_t = PlotMetric::Utilization)]
+        metric: PlotMetric,
+        /// The GPU to plot, as `gpuN` or `host:gpuN`, the first in the recording by default
+        #[arg(long)]
+        gpu: Option<String>,
+        /// The image format, guessed from the output's extension if not given
+        #[arg(long, value_enum)]
+        format: Option<GraphImageFormat>,
+        /// The image width, one pixel per sample
+        #[arg(long, default_value_t = 800)]
+        width: u32,
+        #[arg(long, default_value_t = 200)]
+        height: u32,
+    },
+    /// Run a command, then print how much GPU memory and time it and its children used
+    Run {
+        /// Also write the summary to this file as JSON
+        #[arg(long)]
+        report: Option<PathBuf>,
+        /// The command to run, after `--`
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Print a compact line per GPU, refreshed every interval
+    Watch {
+        /// Also print a line for every process
+        #[arg(short, long)]
+        processes: bool,
+        /// Print each update below the last instead of redrawing it
+        #[arg(long)]
+        append: bool,
+        /// Print once and exit, e.g. for use with `watch`
+        #[arg(long)]
+        once: bool,
+        /// Seconds between updates
+        #[arg(short = 'n', long, default_value_t = 1.0)]
+        interval: f64,
+        #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+        color: ColorChoice,
+        /// Only list processes matching these options, which implies --processes
+        #[command(flatten)]
+        filter: ProcessFilterArgs,
+    },
+}
 
 fn main() {
-    // tui::run_tui_app();
-    run_gpu_app();
+    let cli = Cli::parse();
+
+    let mut config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+    config
+        .remotes
+        .extend(cli.remote.into_iter().map(|address| RemoteConfig {
+            address,
+            token: cli.token.clone(),
+            name: None,
+        }));
+
+    match cli.command.unwrap_or(Command::Gui { overlay: false }) {
+        Command::Gui { overlay } => {
+            let mode = if overlay {
+                AppMode::Overlay
+            } else {
+                AppMode::Window
+            };
+            run_gpu_app(config, mode)
+        }
+        Command::Tui => {
+            if let Err(err) = tui::run_tui_app(config) {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Command::Tray => {
+            #[cfg(all(unix, not(target_os = "macos")))]
+            let result = tray::run_tray(config);
+            #[cfg(not(all(unix, not(target_os = "macos"))))]
+            let result: Result<(), String> =
+                Err("the system tray isn't supported on this platform".into());
+            if let Err(err) = result {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Command::IdleReport {
+            minutes,
+            json,
+            local,
+            filter,
+        } => {
+            let filter = process_filter(&filter);
+            if let Err(err) = report::run_idle_report(config, minutes, json, local, &filter) {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Command::Agent { listen, token } => {
+            if let Err(err) = remote::agent::run_agent(config, &listen, token) {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Command::Serve { http: _, listen } => {
+            if let Err(err) = web::run_http_server(config, &listen) {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Command::Record {
+            output,
+            duration,
+            filter,
+        } => {
+            let duration = duration.map(Duration::from_secs);
+            let filter = process_filter(&filter);
+            if let Err(err) = recording::run_record(config, &output, duration, &filter) {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Command::Export {
+            input,
+            output,
+            format,
+            layout,
+        } => {
+            if let Err(err) = export::run_export(&input, &output, format, layout) {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Command::Plot {
+            input,
+            output,
+            metric,
+            gpu,
+            format,
+            width,
+            height,
+        } => {
+            let options = PlotOptions {
+                metric,
+                gpu,
+                format,
+                width,
+                height,
+            };
+            if let Err(err) = plot::run_plot(&config, &input, &output, options) {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Command::Watch {
+            processes,
+            append,
+            once,
+            interval,
+            color,
+            filter,
+        } => {
+            let filter = process_filter(&filter);
+            let options = WatchOptions {
+                processes: processes || filter.is_active(),
+                append,
+                once,
+                interval: Duration::from_secs_f64(interval.max(0.1)),
+                color,
+                filter,
+            };
+            if let Err(err) = watch::run_watch(config, options) {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Command::Run { report, command } => {
+            let code = job::run_command(config, &command, report.as_deref());
+            std::process::exit(code);
+        }
+    }
+}
+
+/// Builds the process filter from the command line, exiting if it's invalid.
+fn process_filter(args: &ProcessFilterArgs) -> ProcessFilter {
+    args.to_filter().unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    })
 }
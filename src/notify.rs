@@ -0,0 +1,74 @@
+use std::{process::Command, thread};
+
+use notify_rust::{Notification, Urgency};
+
+use crate::data::alerts::{Alert, AlertTransition};
+
+/// Delivers alert transitions as desktop notifications and user commands.
+pub struct AlertNotifier {
+    desktop_notifications: bool,
+}
+
+impl AlertNotifier {
+    pub fn new(desktop_notifications: bool) -> Self {
+        Self {
+            desktop_notifications,
+        }
+    }
+
+    pub fn notify(&self, transition: &AlertTransition) {
+        let (alert, state) = match transition {
+            AlertTransition::Fired(alert) => (alert, "firing"),
+            AlertTransition::Resolved(alert) => (alert, "resolved"),
+        };
+
+        if self.desktop_notifications {
+            if let AlertTransition::Fired(alert) = transition {
+                show_desktop_notification(alert);
+            }
+        }
+
+        if let Some(command) = &alert.command {
+            run_alert_command(command, alert, state);
+        }
+    }
+}
+
+fn show_desktop_notification(alert: &Alert) {
+    let summary = format!("{} on GPU {}", alert.rule, alert.device_index);
+    let body = format!("{}: {}", alert.device_name, alert.message);
+
+    // Talking to the notification daemon can block, so it's kept off the UI thread
+    thread::spawn(move || {
+        let result = Notification::new()
+            .appname("gpustat")
+            .summary(&summary)
+            .body(&body)
+            .urgency(Urgency::Critical)
+            .show();
+        if let Err(err) = result {
+            eprintln!("Failed to show notification: {}", err);
+        }
+    });
+}
+
+fn run_alert_command(command: &str, alert: &Alert, state: &str) {
+    let result = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("GPUSTAT_ALERT_STATE", state)
+        .env("GPUSTAT_ALERT_RULE", &alert.rule)
+        .env("GPUSTAT_ALERT_MESSAGE", &alert.message)
+        .env("GPUSTAT_GPU_INDEX", alert.device_index.to_string())
+        .env("GPUSTAT_GPU_UUID", &alert.device_uuid)
+        .env("GPUSTAT_GPU_NAME", &alert.device_name)
+        .spawn();
+
+    match result {
+        // Reap the child in the background so it doesn't become a zombie
+        Ok(mut child) => {
+            thread::spawn(move || child.wait());
+        }
+        Err(err) => eprintln!("Failed to run alert command: {}", err),
+    }
+}
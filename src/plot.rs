@@ -0,0 +1,161 @@
+use std::{
+    io,
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use clap::ValueEnum;
+
+use crate::{
+    config::Config,
+    data::graph::GraphViewerData,
+    gui::{
+        graph_export::{save_graph_image, GraphImageFormat},
+        style::make_style,
+    },
+    recording::{GpuRecord, Recording},
+    theme::terminal_prefers_dark,
+    utils::bytes_to_mib_gib,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum PlotMetric {
+    Utilization,
+    Memory,
+    Temperature,
+}
+
+impl PlotMetric {
+    fn value(self, record: &GpuRecord) -> Option<f32> {
+        match self {
+            PlotMetric::Utilization => record.utilization,
+            PlotMetric::Memory => record.memory_used.map(|used| used as f32),
+            PlotMetric::Temperature => record.temperature,
+        }
+    }
+}
+
+pub struct PlotOptions {
+    pub metric: PlotMetric,
+    /// `gpuN` or `host:gpuN`, or the first GPU in the recording if not given.
+    pub gpu: Option<String>,
+    pub format: Option<GraphImageFormat>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Renders one GPU's history of a metric from a recording to an image. The graph has one
+/// pixel per sample like in the app, so only the newest samples that fit are drawn.
+pub fn run_plot(
+    config: &Config,
+    input: &Path,
+    output: &Path,
+    options: PlotOptions,
+) -> io::Result<()> {
+    let recording = Recording::read(input)?;
+
+    let gpu = match options.gpu {
+        Some(gpu) => gpu,
+        None => recording
+            .gpus
+            .first()
+            .map(|record| record.gpu.clone())
+            .ok_or_else(|| io::Error::other("the recording has no GPU samples"))?,
+    };
+
+    let records = recording
+        .gpus
+        .iter()
+        .filter(|record| record.gpu == gpu)
+        .collect::<Vec<_>>();
+    if records.is_empty() {
+        return Err(io::Error::other(format!(
+            "the recording has no samples for {}",
+            gpu
+        )));
+    }
+
+    let mut graph = GraphViewerData::with_max_len(records.len());
+    for record in &records {
+        // The recording may have been edited by hand, or cut off partway through
+        let since_epoch = Duration::try_from_secs_f64(record.time).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("a sample of {} has an invalid time: {}", gpu, record.time),
+            )
+        })?;
+        graph.push(UNIX_EPOCH + since_epoch, options.metric.value(record));
+    }
+
+    let memory_total = records
+        .iter()
+        .map(|record| record.memory_total)
+        .max()
+        .unwrap_or_default();
+    let (max_value, value_to_string): (f32, &dyn Fn(f32) -> String) = match options.metric {
+        PlotMetric::Utilization => (100.0, &|v| format!("{:.0}%", v)),
+        PlotMetric::Memory => (memory_total as f32, &bytes_to_mib_gib),
+        PlotMetric::Temperature => (100.0, &|v| format!("{:.0}°C", v)),
+    };
+    let levels = match options.metric {
+        PlotMetric::Utilization => None,
+        PlotMetric::Memory => config.graphs.memory_levels(memory_total),
+        PlotMetric::Temperature => config.graphs.temperature_levels(),
+    };
+
+    let format = options
+        .format
+        .unwrap_or_else(|| GraphImageFormat::from_path(output));
+    let style = make_style(&config.palette(terminal_prefers_dark()));
+    save_graph_image(
+        output,
+        format,
+        &style,
+        &graph,
+        max_value,
+        levels,
+        value_to_string,
+        [options.width, options.height],
+    )?;
+
+    eprintln!("Wrote {}", output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn rejects_invalid_sample_times() {
+        for time in ["-5.0", "1e300"] {
+            let dir = std::env::temp_dir();
+            let input = dir.join(format!("gpustat-plot-test-{}.jsonl", std::process::id()));
+            let output = dir.join(format!("gpustat-plot-test-{}.png", std::process::id()));
+            fs::write(
+                &input,
+                format!(
+                    r#"{{"type":"gpu","time":{},"gpu":"gpu0","name":"Test GPU","uuid":"GPU-0","utilization":50.0,"memory_used":1,"memory_total":2,"temperature":60.0}}"#,
+                    time
+                ),
+            )
+            .unwrap();
+
+            let options = PlotOptions {
+                metric: PlotMetric::Utilization,
+                gpu: None,
+                format: None,
+                width: 100,
+                height: 50,
+            };
+            let result = run_plot(&Config::default(), &input, &output, options);
+            fs::remove_file(&input).unwrap();
+
+            let err = result.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", err);
+            assert!(!output.exists());
+        }
+    }
+}
@@ -1,18 +1,186 @@
-use nvml_wrapper::struct_wrappers::device::{ProcessInfo, ProcessUtilizationSample};
-use sysinfo::{ProcessRefreshKind, RefreshKind, System};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 
+use nvml_wrapper::{
+    enums::device::UsedGpuMemory,
+    error::NvmlError,
+    struct_wrappers::device::{ProcessInfo, ProcessUtilizationSample},
+    Device,
+};
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, ProcessRefreshKind, System, Uid, UpdateKind, Users};
+
+/// Which kinds of GPU context a process holds.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProcessKind {
+    Graphics,
+    Compute,
+    Both,
+}
+
+impl ProcessKind {
+    pub fn is_graphics(self) -> bool {
+        matches!(self, ProcessKind::Graphics | ProcessKind::Both)
+    }
+
+    pub fn is_compute(self) -> bool {
+        matches!(self, ProcessKind::Compute | ProcessKind::Both)
+    }
+
+    /// Whether a process of `kind` belongs in a list of this kind of processes. Lists of
+    /// graphics or compute processes include the processes holding both.
+    pub fn includes(self, kind: ProcessKind) -> bool {
+        match self {
+            ProcessKind::Graphics => kind.is_graphics(),
+            ProcessKind::Compute => kind.is_compute(),
+            ProcessKind::Both => kind == ProcessKind::Both,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ProcessKind::Graphics => "Graphics",
+            ProcessKind::Compute => "Compute",
+            ProcessKind::Both => "Both",
+        }
+    }
+}
+
+/// Fetches both the graphics and compute process lists, merging processes that appear in
+/// both into a single entry. Only fails if neither list could be fetched.
+pub fn fetch_device_processes(
+    device: &Device,
+) -> Result<Vec<(ProcessInfo, ProcessKind)>, NvmlError> {
+    let graphics = device.running_graphics_processes();
+    let compute = device.running_compute_processes();
+
+    let (graphics, compute) = match (graphics, compute) {
+        (Err(err), Err(_)) => return Err(err),
+        (graphics, compute) => (graphics.unwrap_or_default(), compute.unwrap_or_default()),
+    };
+
+    let mut result = graphics
+        .into_iter()
+        .map(|info| (info, ProcessKind::Graphics))
+        .collect::<Vec<_>>();
+
+    for info in compute {
+        match result
+            .iter_mut()
+            .find(|(existing, _)| existing.pid == info.pid)
+        {
+            Some((existing, kind)) => {
+                *kind = ProcessKind::Both;
+                // Both lists report the process's total usage, so summing would double count
+                existing.used_gpu_memory = match (&existing.used_gpu_memory, &info.used_gpu_memory)
+                {
+                    (UsedGpuMemory::Used(a), UsedGpuMemory::Used(b)) => {
+                        UsedGpuMemory::Used(*a.max(b))
+                    }
+                    (UsedGpuMemory::Used(a), UsedGpuMemory::Unavailable)
+                    | (UsedGpuMemory::Unavailable, UsedGpuMemory::Used(a)) => {
+                        UsedGpuMemory::Used(*a)
+                    }
+                    (UsedGpuMemory::Unavailable, UsedGpuMemory::Unavailable) => {
+                        UsedGpuMemory::Unavailable
+                    }
+                };
+            }
+            None => result.push((info, ProcessKind::Compute)),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Caches process metadata (name, command line and owner) from the OS.
+///
+/// A single bank is shared by every GPU, and it only ever looks up the pids that NVML
+/// reports, rather than scanning every process on the system.
 pub struct ProcessDataBank {
     sys: sysinfo::System,
-    last_refresh: std::time::Instant,
+    users: Users,
+    current_user: Option<Uid>,
+    refresh_interval: Duration,
+    /// When each pid's metadata was last refreshed.
+    refreshed_at: HashMap<Pid, Instant>,
+}
+
+fn process_refresh_kind() -> ProcessRefreshKind {
+    ProcessRefreshKind::new()
+        .with_user(UpdateKind::OnlyIfNotSet)
+        .with_cmd(UpdateKind::OnlyIfNotSet)
 }
 
 impl ProcessDataBank {
     pub fn new() -> Self {
+        // Refresh the process data every 2 seconds
+        Self::with_refresh_interval(Duration::from_secs(2))
+    }
+
+    pub fn with_refresh_interval(refresh_interval: Duration) -> Self {
+        let mut sys = System::new();
+
+        let current_pid = sysinfo::get_current_pid().ok();
+        if let Some(pid) = current_pid {
+            sys.refresh_pids_specifics(&[pid], process_refresh_kind());
+        }
+        let current_user = current_pid
+            .and_then(|pid| sys.process(pid))
+            .and_then(|process| process.user_id())
+            .cloned();
+
         Self {
-            sys: System::new_with_specifics(
-                RefreshKind::new().with_processes(ProcessRefreshKind::new()),
-            ),
-            last_refresh: std::time::Instant::now(),
+            sys,
+            users: Users::new_with_refreshed_list(),
+            current_user,
+            refresh_interval,
+            refreshed_at: HashMap::new(),
+        }
+    }
+
+    /// Refreshes the metadata of any of the given pids that are unknown or out of date.
+    fn refresh_pids(&mut self, pids: &[Pid]) {
+        let now = Instant::now();
+
+        let stale = pids
+            .iter()
+            .filter(|pid| match self.refreshed_at.get(pid) {
+                Some(refreshed_at) => now.duration_since(*refreshed_at) >= self.refresh_interval,
+                None => true,
+            })
+            .copied()
+            .collect::<Vec<_>>();
+
+        if stale.is_empty() {
+            return;
+        }
+
+        for pid in &stale {
+            self.refreshed_at.insert(*pid, now);
+        }
+
+        // Forget about processes that haven't been reported by any GPU in a while
+        let forget_after = self.refresh_interval * 10;
+        self.refreshed_at
+            .retain(|_, refreshed_at| now.duration_since(*refreshed_at) < forget_after);
+
+        // Refreshing a set of pids drops every other process, so the whole set that's still
+        // known is refreshed together. Otherwise one GPU's refresh would drop another's
+        // processes, and this is also what drops the forgotten ones.
+        let known = self.refreshed_at.keys().copied().collect::<Vec<_>>();
+        self.sys
+            .refresh_pids_specifics(&known, process_refresh_kind());
+
+        // Only reload the user list when a process is owned by someone we don't know about
+        let has_unknown_user = stale
+            .iter()
+            .filter_map(|pid| self.sys.process(*pid)?.user_id())
+            .any(|uid| self.users.get_user_by_id(uid).is_none());
+        if has_unknown_user {
+            self.users.refresh_list();
         }
     }
 
@@ -23,33 +191,64 @@ impl ProcessDataBank {
         process.name()
     }
 
+    fn get_process_command(&self, pid: u32) -> String {
+        let Some(process) = self.sys.process((pid as usize).into()) else {
+            return String::new();
+        };
+        process.cmd().join(" ")
+    }
+
+    fn get_process_start_time(&self, pid: u32) -> Option<u64> {
+        Some(self.sys.process((pid as usize).into())?.start_time())
+    }
+
+    fn get_process_user(&self, pid: u32) -> Option<&Uid> {
+        self.sys.process((pid as usize).into())?.user_id()
+    }
+
     pub fn map_process_list(
         &mut self,
-        process_list: Vec<ProcessInfo>,
-        utilization_list: Vec<ProcessUtilizationSample>,
+        process_list: Vec<(ProcessInfo, ProcessKind)>,
+        utilization_list: Option<Vec<ProcessUtilizationSample>>,
     ) -> Vec<ProcessData> {
-        // Refresh the process data every 2 seconds
-        if self.last_refresh.elapsed().as_secs() > 2 {
-            self.sys.refresh_all();
-            self.last_refresh = std::time::Instant::now();
-        }
+        let pids = process_list
+            .iter()
+            .map(|(process, _)| Pid::from_u32(process.pid))
+            .collect::<Vec<_>>();
+        self.refresh_pids(&pids);
 
         let mut result = Vec::new();
-        for process in process_list {
+        for (process, kind) in process_list {
             // We skip system processes that often clog up the list without using any resources
             if process.pid == 0 {
                 continue;
             }
 
-            let gpu_usage = utilization_list
-                .iter()
-                .find(|util| util.pid == process.pid)
-                .map(|util| util.sm_util)
-                .unwrap_or(0);
+            // NVML only has samples for processes that used the GPU during the sample
+            // period, so a process without one hasn't used it
+            let gpu_usage = utilization_list.as_ref().map(|utilization_list| {
+                utilization_list
+                    .iter()
+                    .find(|util| util.pid == process.pid)
+                    .map(|util| util.sm_util)
+                    .unwrap_or(0)
+            });
+
+            let uid = self.get_process_user(process.pid);
+            let user = uid
+                .and_then(|uid| self.users.get_user_by_id(uid))
+                .map(|user| user.name().to_string());
+            let is_current_user = uid.is_some() && uid == self.current_user.as_ref();
 
             result.push(ProcessData {
                 name: self.get_process_name(process.pid).to_string(),
+                command: self.get_process_command(process.pid),
+                start_time: self.get_process_start_time(process.pid),
+                user,
+                is_current_user,
+                kind,
                 gpu_usage,
+                idle_for: None,
                 info: process,
             });
         }
@@ -57,9 +256,84 @@ impl ProcessDataBank {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ProcessData {
     pub info: ProcessInfo,
     pub name: String,
-    /// The percentage GPU utilization of the process.
-    pub gpu_usage: u32,
+    /// The full command line of the process, with arguments separated by spaces.
+    pub command: String,
+    /// When the process started, in seconds since the epoch, if it's known.
+    #[serde(default)]
+    pub start_time: Option<u64>,
+    /// The name of the user owning the process, if it could be resolved.
+    pub user: Option<String>,
+    /// Whether the process is owned by the user running gpustat.
+    pub is_current_user: bool,
+    pub kind: ProcessKind,
+    /// The percentage GPU utilization of the process, if the GPU can measure it.
+    pub gpu_usage: Option<u32>,
+    /// How long the process has held memory without using the GPU, if it's idle.
+    pub idle_for: Option<Duration>,
+}
+
+impl ProcessData {
+    /// The GPU utilization as shown in tables, e.g. `42%`, or `N/A` if it isn't known.
+    pub fn gpu_usage_label(&self) -> String {
+        match self.gpu_usage {
+            Some(usage) => format!("{}%", usage),
+            None => "N/A".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::{Child, Command};
+
+    use super::*;
+
+    fn spawn_sleeper() -> Child {
+        Command::new("sleep").arg("30").spawn().unwrap()
+    }
+
+    #[test]
+    fn refreshing_some_pids_keeps_the_others() {
+        let mut first = spawn_sleeper();
+        let mut second = spawn_sleeper();
+        let first_pid = Pid::from_u32(first.id());
+        let second_pid = Pid::from_u32(second.id());
+
+        // Long enough that nothing goes stale during the test
+        let mut bank = ProcessDataBank::with_refresh_interval(Duration::from_secs(60));
+        bank.refresh_pids(&[first_pid]);
+        bank.refresh_pids(&[second_pid]);
+
+        assert_eq!(bank.get_process_name(first.id()), "sleep");
+        assert_eq!(bank.get_process_name(second.id()), "sleep");
+        assert!(bank.get_process_command(first.id()).starts_with("sleep"));
+
+        first.kill().unwrap();
+        second.kill().unwrap();
+        first.wait().unwrap();
+        second.wait().unwrap();
+    }
+
+    #[test]
+    fn forgotten_pids_are_dropped() {
+        let mut child = spawn_sleeper();
+        let pid = Pid::from_u32(child.id());
+        let own_pid = sysinfo::get_current_pid().unwrap();
+
+        let mut bank = ProcessDataBank::with_refresh_interval(Duration::from_millis(10));
+        bank.refresh_pids(&[pid]);
+        assert_eq!(bank.get_process_name(child.id()), "sleep");
+
+        // Long after it was last reported, another pid's refresh drops it
+        std::thread::sleep(Duration::from_millis(150));
+        bank.refresh_pids(&[own_pid]);
+        assert_eq!(bank.get_process_name(child.id()), "Unknown");
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
 }
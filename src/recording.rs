@@ -0,0 +1,251 @@
+//! GPU and process samples over time, either recorded to a file by `gpustat record` or
+//! taken from the history held in memory.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use nvml_wrapper::enums::device::UsedGpuMemory;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    data::{process_filter::ProcessFilter, GpuDeviceMonitor, GpuMonitoringData},
+};
+
+/// One reading of a GPU.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GpuRecord {
+    /// Unix time in seconds.
+    pub time: f64,
+    /// `gpuN`, or `host:gpuN` for remote GPUs.
+    pub gpu: String,
+    pub name: String,
+    pub uuid: String,
+    pub utilization: Option<f32>,
+    pub memory_used: Option<u64>,
+    pub memory_total: u64,
+    pub temperature: Option<f32>,
+}
+
+/// One reading of a process on a GPU.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProcessRecord {
+    /// Unix time in seconds.
+    pub time: f64,
+    pub gpu: String,
+    pub pid: u32,
+    pub name: String,
+    pub user: Option<String>,
+    pub memory_used: Option<u64>,
+    pub gpu_usage: Option<f32>,
+}
+
+/// A line of a recording file.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum RecordLine {
+    Gpu(GpuRecord),
+    Process(ProcessRecord),
+}
+
+#[derive(Default)]
+pub struct Recording {
+    pub gpus: Vec<GpuRecord>,
+    pub processes: Vec<ProcessRecord>,
+}
+
+fn unix_time(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+impl Recording {
+    /// Collects the history held in memory for every GPU and every recently seen process.
+    pub fn from_live(data: &mut GpuMonitoringData) -> Self {
+        let mut recording = Self::default();
+
+        for gpu in data.gpus().iter_mut() {
+            let id = gpu.gpu_id();
+            let name = gpu.device_name().to_string();
+            let uuid = gpu.device_uuid().to_string();
+            let memory_total = gpu.max_memory();
+
+            // The three graphs are always updated together, so their samples line up
+            let usage = gpu.usage_graph_mut().samples().collect::<Vec<_>>();
+            let memory = gpu.memory_graph_mut().samples().collect::<Vec<_>>();
+            let temperature = gpu.temperature_graph_mut().samples().collect::<Vec<_>>();
+
+            for ((time, utilization), ((_, memory_used), (_, temperature))) in
+                usage.into_iter().zip(memory.into_iter().zip(temperature))
+            {
+                recording.gpus.push(GpuRecord {
+                    time: unix_time(time),
+                    gpu: id.clone(),
+                    name: name.clone(),
+                    uuid: uuid.clone(),
+                    utilization,
+                    memory_used: memory_used.map(|used| used as u64),
+                    memory_total,
+                    temperature,
+                });
+            }
+
+            for (pid, history) in gpu.processes().histories() {
+                let samples = history
+                    .memory_graph()
+                    .samples()
+                    .zip(history.usage_graph().samples());
+                for ((time, memory_used), (_, gpu_usage)) in samples {
+                    // Gaps are recorded while a process is missing from the list
+                    if memory_used.is_none() && gpu_usage.is_none() {
+                        continue;
+                    }
+                    recording.processes.push(ProcessRecord {
+                        time: unix_time(time),
+                        gpu: id.clone(),
+                        pid,
+                        name: history.name().to_string(),
+                        user: history.user().map(str::to_string),
+                        memory_used: memory_used.map(|used| used as u64),
+                        gpu_usage,
+                    });
+                }
+            }
+        }
+
+        recording
+            .processes
+            .sort_by(|a, b| a.time.total_cmp(&b.time).then(a.pid.cmp(&b.pid)));
+        recording
+    }
+
+    /// Reads a recording file written by `gpustat record`.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads a recording in the same format from anything else.
+    pub fn read_from(reader: impl BufRead) -> io::Result<Self> {
+        let mut recording = Self::default();
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let record = serde_json::from_str::<RecordLine>(&line).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", number + 1, err),
+                )
+            })?;
+            match record {
+                RecordLine::Gpu(record) => recording.gpus.push(record),
+                RecordLine::Process(record) => recording.processes.push(record),
+            }
+        }
+
+        Ok(recording)
+    }
+}
+
+fn gpu_record(gpu: &GpuDeviceMonitor) -> Option<GpuRecord> {
+    let sample = gpu.last_sample()?;
+    Some(GpuRecord {
+        time: unix_time(SystemTime::now() - sample.time.elapsed()),
+        gpu: gpu.gpu_id(),
+        name: gpu.device_name().to_string(),
+        uuid: gpu.device_uuid().to_string(),
+        utilization: sample.utilization,
+        memory_used: sample.memory_used,
+        memory_total: sample.memory_total,
+        temperature: sample.temperature,
+    })
+}
+
+fn process_records(gpu: &GpuDeviceMonitor, filter: &ProcessFilter) -> Vec<ProcessRecord> {
+    let Ok(processes) = gpu.processes().all_processes() else {
+        return Vec::new();
+    };
+    let time = unix_time(SystemTime::now());
+
+    processes
+        .iter()
+        .filter(|process| filter.matches(process))
+        .map(|process| ProcessRecord {
+            time,
+            gpu: gpu.gpu_id(),
+            pid: process.info.pid,
+            name: process.name.clone(),
+            user: process.user.clone(),
+            memory_used: match process.info.used_gpu_memory {
+                UsedGpuMemory::Used(bytes) => Some(bytes),
+                UsedGpuMemory::Unavailable => None,
+            },
+            gpu_usage: process.gpu_usage.map(|usage| usage as f32),
+        })
+        .collect()
+}
+
+/// Appends every new GPU and process sample to a file, one JSON object per line, until
+/// interrupted or the duration is up.
+pub fn run_record(
+    config: Config,
+    output: &Path,
+    duration: Option<Duration>,
+    filter: &ProcessFilter,
+) -> io::Result<()> {
+    let config = config.without_alert_delivery();
+    let mut data = GpuMonitoringData::new(&config);
+
+    let mut file = BufWriter::new(File::options().create(true).append(true).open(output)?);
+    eprintln!("Recording to {}, press Ctrl+C to stop", output.display());
+
+    let start = Instant::now();
+    let mut last_samples = Vec::<Option<Instant>>::new();
+    let mut last_refreshes = Vec::<Option<Instant>>::new();
+
+    while duration.is_none_or(|duration| start.elapsed() < duration) {
+        data.update();
+
+        let gpus = data.gpus();
+        last_samples.resize(gpus.len(), None);
+        last_refreshes.resize(gpus.len(), None);
+
+        for (index, gpu) in gpus.iter().enumerate() {
+            let sample_time = gpu.last_sample().map(|sample| sample.time);
+            if sample_time.is_some() && sample_time != last_samples[index] {
+                last_samples[index] = sample_time;
+                if let Some(record) = gpu_record(gpu) {
+                    write_line(&mut file, &RecordLine::Gpu(record))?;
+                }
+            }
+
+            let refresh = gpu.processes().last_refresh();
+            if refresh.is_some() && refresh != last_refreshes[index] {
+                last_refreshes[index] = refresh;
+                for record in process_records(gpu, filter) {
+                    write_line(&mut file, &RecordLine::Process(record))?;
+                }
+            }
+        }
+
+        // Flushed every time so that stopping with Ctrl+C doesn't lose anything
+        file.flush()?;
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    Ok(())
+}
+
+fn write_line(writer: &mut impl Write, line: &RecordLine) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, line)?;
+    writeln!(writer)
+}
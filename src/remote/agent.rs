@@ -0,0 +1,292 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use sysinfo::System;
+
+use crate::{config::Config, data::GpuMonitoringData};
+
+use super::protocol::{AgentMessage, Hello, HostSnapshot, PROTOCOL_VERSION};
+
+/// Each client's queue of lines waiting to be written to it.
+type Clients = Arc<Mutex<Vec<ClientQueue>>>;
+
+/// How many snapshots can be waiting for a client before it's dropped for falling behind.
+const MAX_QUEUED_SNAPSHOTS: usize = 8;
+
+struct ClientQueue {
+    peer: String,
+    lines: SyncSender<Arc<str>>,
+}
+
+/// The longest hello line accepted. It's read before the client is authenticated, so it
+/// mustn't be able to grow without limit.
+const MAX_HANDSHAKE_LEN: u64 = 4096;
+
+/// Samples the local GPUs and streams snapshots of them to every connected client.
+pub fn run_agent(config: Config, listen: &str, token: Option<String>) -> io::Result<()> {
+    // The agent only serves its own GPUs, and usually runs without a desktop
+    let config = Config {
+        desktop_notifications: false,
+        remotes: Vec::new(),
+        ..config
+    };
+    let mut data = GpuMonitoringData::new(&config);
+
+    let listener = TcpListener::bind(listen)?;
+    eprintln!("Serving GPU stats on {}", listener.local_addr()?);
+    if token.is_none() {
+        eprintln!("Warning: no token set, anyone who can reach this port can connect");
+    }
+
+    let clients = Clients::default();
+    let accept_clients = clients.clone();
+    thread::spawn(move || accept_connections(listener, token, accept_clients));
+
+    let host = System::host_name().unwrap_or_else(|| "unknown".to_string());
+    let mut last_snapshot: Option<Instant> = None;
+
+    loop {
+        data.update();
+
+        if last_snapshot.is_none_or(|time| time.elapsed() >= Duration::from_millis(500)) {
+            last_snapshot = Some(Instant::now());
+
+            let snapshot = HostSnapshot {
+                host: host.clone(),
+                devices: data
+                    .gpus()
+                    .iter()
+                    .filter_map(|gpu| gpu.snapshot())
+                    .collect(),
+            };
+            broadcast(&clients, &AgentMessage::Snapshot(snapshot));
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+fn accept_connections(listener: TcpListener, token: Option<String>, clients: Clients) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let token = token.clone();
+        let clients = clients.clone();
+        // The handshake waits on the client, so it mustn't hold up other connections
+        thread::spawn(move || {
+            if let Err(err) = handshake(stream, token.as_deref(), &clients) {
+                eprintln!("Rejected client: {}", err);
+            }
+        });
+    }
+}
+
+/// Reads the client's hello, adding it to the clients if it's accepted.
+fn handshake(stream: TcpStream, token: Option<&str>, clients: &Clients) -> Result<(), String> {
+    let peer = stream
+        .peer_addr()
+        .map(|address| address.to_string())
+        .unwrap_or_default();
+
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .map_err(|err| err.to_string())?;
+    // A client that stops reading gets dropped rather than stalling every other client
+    stream
+        .set_write_timeout(Some(Duration::from_secs(5)))
+        .map_err(|err| err.to_string())?;
+
+    let mut line = String::new();
+    BufReader::new((&stream).take(MAX_HANDSHAKE_LEN))
+        .read_line(&mut line)
+        .map_err(|err| format!("{}: {}", peer, err))?;
+
+    let rejection = match serde_json::from_str::<Hello>(&line) {
+        _ if !line.ends_with('\n') && line.len() as u64 >= MAX_HANDSHAKE_LEN => {
+            Some(format!("hello is longer than {} bytes", MAX_HANDSHAKE_LEN))
+        }
+        Err(err) => Some(format!("invalid hello: {}", err)),
+        Ok(hello) if hello.version != PROTOCOL_VERSION => Some(format!(
+            "unsupported protocol version {}, the agent speaks {}",
+            hello.version, PROTOCOL_VERSION
+        )),
+        Ok(hello) if !token_matches(token, hello.token.as_deref()) => {
+            Some("invalid token".to_string())
+        }
+        Ok(_) => None,
+    };
+
+    if let Some(message) = rejection {
+        let line = message_line(&AgentMessage::Error {
+            message: message.clone(),
+        });
+        let _ = (&stream).write_all(line.as_bytes());
+        return Err(format!("{}: {}", peer, message));
+    }
+
+    eprintln!("Client connected: {}", peer);
+    let (lines, queued) = mpsc::sync_channel(MAX_QUEUED_SNAPSHOTS);
+    // Written on its own thread, so a slow client doesn't hold up sampling or the others
+    let writer_peer = peer.clone();
+    thread::spawn(move || write_to_client(stream, &writer_peer, queued));
+    clients.lock().unwrap().push(ClientQueue { peer, lines });
+    Ok(())
+}
+
+/// Writes queued lines to the client until it's dropped or can't be written to.
+fn write_to_client(mut stream: TcpStream, peer: &str, queued: mpsc::Receiver<Arc<str>>) {
+    for line in queued {
+        if let Err(err) = stream.write_all(line.as_bytes()) {
+            eprintln!("Client disconnected: {}: {}", peer, err);
+            return;
+        }
+    }
+}
+
+fn token_matches(expected: Option<&str>, given: Option<&str>) -> bool {
+    let Some(expected) = expected else {
+        return true;
+    };
+    let given = given.unwrap_or_default();
+
+    // Compare every byte so the time taken doesn't give away how much of the token matched
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn message_line(message: &AgentMessage) -> String {
+    let mut line = serde_json::to_string(message).unwrap();
+    line.push('\n');
+    line
+}
+
+/// Queues the message for every client, dropping the ones that have disconnected or fallen
+/// too far behind.
+fn broadcast(clients: &Clients, message: &AgentMessage) {
+    let line: Arc<str> = message_line(message).into();
+    clients
+        .lock()
+        .unwrap()
+        .retain(|client| match client.lines.try_send(line.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                eprintln!("Client too slow, disconnecting: {}", client.peer);
+                false
+            }
+            // The writer already said why it stopped
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Connects a client that sends `hello`, returning the agent's end of the connection.
+    fn connect(hello: &[u8]) -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.write_all(hello).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        (stream, client)
+    }
+
+    #[test]
+    fn accepts_a_valid_hello() {
+        let (stream, _client) = connect(b"{\"version\":1,\"token\":\"secret\"}\n");
+        let clients = Clients::default();
+
+        assert!(handshake(stream, Some("secret"), &clients).is_ok());
+        assert_eq!(clients.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rejects_a_hello_without_the_token() {
+        let (stream, _client) = connect(b"{\"version\":1}\n");
+        let clients = Clients::default();
+
+        let err = handshake(stream, Some("secret"), &clients).unwrap_err();
+        assert!(err.ends_with("invalid token"), "{}", err);
+        assert!(clients.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_an_oversized_hello_without_waiting_for_a_newline() {
+        let hello = vec![b'a'; MAX_HANDSHAKE_LEN as usize * 2];
+        let (stream, _client) = connect(&hello);
+        let clients = Clients::default();
+
+        let err = handshake(stream, None, &clients).unwrap_err();
+        assert!(err.contains("longer than"), "{}", err);
+        assert!(clients.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn broadcasts_to_every_client() {
+        let clients = Clients::default();
+        let mut readers = Vec::new();
+        for _ in 0..2 {
+            let (stream, client) = connect(b"{\"version\":1}\n");
+            handshake(stream, None, &clients).unwrap();
+            readers.push(BufReader::new(client));
+        }
+
+        broadcast(
+            &clients,
+            &AgentMessage::Error {
+                message: "hello".to_string(),
+            },
+        );
+        for reader in &mut readers {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            assert_eq!(line, "{\"type\":\"error\",\"message\":\"hello\"}\n");
+        }
+    }
+
+    #[test]
+    fn drops_a_stalled_client_without_blocking_the_others() {
+        let clients = Clients::default();
+        let (stream, _stalled) = connect(b"{\"version\":1}\n");
+        handshake(stream, None, &clients).unwrap();
+        let (stream, client) = connect(b"{\"version\":1}\n");
+        handshake(stream, None, &clients).unwrap();
+
+        // Keep reading from the second client, while the first never reads
+        let reader = thread::spawn(move || {
+            BufReader::new(client)
+                .lines()
+                .take_while(|line| line.is_ok())
+                .count()
+        });
+
+        // Big enough to fill the stalled client's socket buffers
+        let message = AgentMessage::Error {
+            message: "x".repeat(1 << 20),
+        };
+        for _ in 0..50 {
+            // Blocking on the stalled client would take as long as the 5s write timeout
+            let started = Instant::now();
+            broadcast(&clients, &message);
+            assert!(started.elapsed() < Duration::from_secs(1));
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(clients.lock().unwrap().len(), 1);
+
+        clients.lock().unwrap().clear();
+        assert_eq!(reader.join().unwrap(), 50);
+    }
+}
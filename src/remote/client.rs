@@ -0,0 +1,185 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+use super::protocol::{with_default_port, AgentMessage, Hello, HostSnapshot, PROTOCOL_VERSION};
+
+/// An agent to show the GPUs of, configured under `[[remotes]]`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteConfig {
+    /// `host` or `host:port` of the agent.
+    pub address: String,
+    /// The token the agent was started with, if any.
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Shown instead of the host name the agent reports.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    Connecting,
+    Connected,
+    /// The connection failed or was closed, and will be retried.
+    Disconnected(String),
+}
+
+struct SharedState {
+    status: ConnectionStatus,
+    /// The newest snapshot that hasn't been taken yet.
+    snapshot: Option<HostSnapshot>,
+    /// When the last snapshot arrived.
+    last_received: Option<Instant>,
+    /// The host name from the last snapshot.
+    host: Option<String>,
+}
+
+/// A connection to an agent, kept up on a background thread which reconnects whenever
+/// it drops.
+pub struct RemoteConnection {
+    address: String,
+    shared: Arc<Mutex<SharedState>>,
+}
+
+impl RemoteConnection {
+    pub fn connect(config: RemoteConfig) -> Self {
+        let shared = Arc::new(Mutex::new(SharedState {
+            status: ConnectionStatus::Connecting,
+            snapshot: None,
+            last_received: None,
+            host: config.name.clone(),
+        }));
+
+        let address = with_default_port(&config.address);
+        let worker_shared = shared.clone();
+        thread::spawn(move || run_connection(config, worker_shared));
+
+        Self { address, shared }
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn status(&self) -> ConnectionStatus {
+        self.shared.lock().unwrap().status.clone()
+    }
+
+    /// When a snapshot was last received from the agent.
+    pub fn last_received(&self) -> Option<Instant> {
+        self.shared.lock().unwrap().last_received
+    }
+
+    /// The name of the agent's host, once it's known.
+    pub fn host(&self) -> Option<String> {
+        self.shared.lock().unwrap().host.clone()
+    }
+
+    /// Takes the snapshot received since the last call, if there is one.
+    pub fn take_snapshot(&self) -> Option<HostSnapshot> {
+        self.shared.lock().unwrap().snapshot.take()
+    }
+}
+
+fn run_connection(config: RemoteConfig, shared: Arc<Mutex<SharedState>>) {
+    let mut backoff = Duration::from_secs(1);
+
+    loop {
+        shared.lock().unwrap().status = ConnectionStatus::Connecting;
+
+        let err = stream_snapshots(&config, &shared).unwrap_err();
+
+        let mut state = shared.lock().unwrap();
+        if state.status == ConnectionStatus::Connected {
+            backoff = Duration::from_secs(1);
+        }
+        state.status = ConnectionStatus::Disconnected(err);
+        drop(state);
+
+        thread::sleep(backoff);
+        backoff = (backoff * 2).min(Duration::from_secs(30));
+    }
+}
+
+/// Connects to the agent and stores snapshots as they arrive, until the connection fails.
+fn stream_snapshots(config: &RemoteConfig, shared: &Mutex<SharedState>) -> Result<(), String> {
+    for snapshot in open_stream(config)? {
+        let snapshot = snapshot?;
+        let mut state = shared.lock().unwrap();
+        state.status = ConnectionStatus::Connected;
+        state.last_received = Some(Instant::now());
+        state.host = Some(snapshot.host.clone());
+        state.snapshot = Some(snapshot);
+    }
+
+    Err("connection closed by agent".to_string())
+}
+
+/// Connects to the agent and waits for its next snapshot, for one-off queries.
+pub fn fetch_snapshot(config: &RemoteConfig) -> Result<HostSnapshot, String> {
+    open_stream(config)?
+        .next()
+        .unwrap_or_else(|| Err("connection closed by agent".to_string()))
+}
+
+/// Connects and says hello to the agent, returning the snapshots it sends. The stream ends
+/// with an error if the agent rejects the client or sends something invalid.
+fn open_stream(
+    config: &RemoteConfig,
+) -> Result<impl Iterator<Item = Result<HostSnapshot, String>> + '_, String> {
+    let address = with_default_port(&config.address);
+    let mut stream = connect(&address)?;
+    // The agent sends a snapshot every half second, so a long silence means it's gone
+    stream
+        .set_read_timeout(Some(Duration::from_secs(15)))
+        .map_err(|err| err.to_string())?;
+
+    let hello = Hello {
+        version: PROTOCOL_VERSION,
+        token: config.token.clone(),
+    };
+    let mut line = serde_json::to_string(&hello).map_err(|err| err.to_string())?;
+    line.push('\n');
+    stream
+        .write_all(line.as_bytes())
+        .map_err(|err| err.to_string())?;
+
+    Ok(BufReader::new(stream).lines().map(|line| {
+        let line = line.map_err(|err| err.to_string())?;
+        let message = serde_json::from_str::<AgentMessage>(&line)
+            .map_err(|err| format!("invalid message from agent: {}", err))?;
+
+        match message {
+            AgentMessage::Snapshot(mut snapshot) => {
+                if let Some(name) = &config.name {
+                    snapshot.host = name.clone();
+                }
+                Ok(snapshot)
+            }
+            AgentMessage::Error { message } => Err(message),
+        }
+    }))
+}
+
+fn connect(address: &str) -> Result<TcpStream, String> {
+    let addresses = address
+        .to_socket_addrs()
+        .map_err(|err| format!("couldn't resolve {}: {}", address, err))?;
+
+    let mut last_error = format!("{} didn't resolve to any addresses", address);
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, Duration::from_secs(5)) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_error = format!("couldn't connect to {}: {}", address, err),
+        }
+    }
+    Err(last_error)
+}
@@ -0,0 +1,5 @@
+//! Monitoring GPUs on other machines, through a `gpustat agent` running on each of them.
+
+pub mod agent;
+pub mod client;
+pub mod protocol;
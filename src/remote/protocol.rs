@@ -0,0 +1,84 @@
+//! The agent protocol: newline delimited JSON over TCP.
+//!
+//! A client opens a connection and sends a [`Hello`] line. The agent replies with an
+//! [`AgentMessage::Error`] and closes the connection if it's rejected, otherwise it streams
+//! an [`AgentMessage::Snapshot`] line every time its GPUs are sampled.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{
+        device_info::DeviceInfo, ecc::EccCounts, health::HealthEvent, throttle::ThrottleReasons,
+    },
+    processes::ProcessData,
+};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+pub const DEFAULT_PORT: u16 = 7766;
+
+/// The first line sent by a client.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Hello {
+    pub version: u32,
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentMessage {
+    Snapshot(HostSnapshot),
+    Error { message: String },
+}
+
+/// The state of every GPU on a host at one point in time.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HostSnapshot {
+    pub host: String,
+    pub devices: Vec<DeviceSnapshot>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DeviceSnapshot {
+    pub index: u32,
+    pub uuid: String,
+    pub name: String,
+    pub memory_total: u64,
+    pub utilization: Option<f32>,
+    pub memory_used: Option<u64>,
+    pub temperature: Option<f32>,
+    pub lost: bool,
+    /// Sent with every snapshot, as it's small and clients can connect at any time.
+    #[serde(default)]
+    pub info: Option<DeviceInfo>,
+    #[serde(default)]
+    pub throttle_reasons: Option<ThrottleReasons>,
+    /// The most recent XID errors, oldest first.
+    #[serde(default)]
+    pub health_events: Vec<HealthEvent>,
+    /// Clients work out when the counts go up themselves, from one snapshot to the next.
+    #[serde(default)]
+    pub ecc: Option<EccCounts>,
+    /// The latest process list, or why it couldn't be read.
+    pub processes: Result<Vec<ProcessData>, String>,
+}
+
+/// Adds the default port to an address that doesn't have one.
+pub fn with_default_port(address: &str) -> String {
+    let has_port = match address.rsplit_once(':') {
+        // A bare IPv6 address has colons but no port unless it's bracketed
+        Some((host, port)) => {
+            port.parse::<u16>().is_ok() && (!host.contains(':') || host.ends_with(']'))
+        }
+        None => false,
+    };
+
+    if has_port {
+        address.to_string()
+    } else if address.contains(':') && !address.starts_with('[') {
+        format!("[{}]:{}", address, DEFAULT_PORT)
+    } else {
+        format!("{}:{}", address, DEFAULT_PORT)
+    }
+}
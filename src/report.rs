@@ -0,0 +1,212 @@
+use std::{
+    cmp::Reverse,
+    thread,
+    time::{Duration, Instant},
+};
+
+use nvml_wrapper::enums::device::UsedGpuMemory;
+use serde::Serialize;
+
+use crate::{
+    config::Config,
+    data::{process_filter::ProcessFilter, GpuMonitoringData},
+    processes::ProcessData,
+    remote::client::fetch_snapshot,
+    utils::{bytes_to_mib_gib, format_duration},
+};
+
+#[derive(Serialize)]
+struct IdleProcess {
+    /// The agent's host, for processes on remote GPUs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<String>,
+    gpu: u32,
+    pid: u32,
+    name: String,
+    user: Option<String>,
+    memory_bytes: u64,
+    idle_secs: u64,
+}
+
+impl IdleProcess {
+    fn new(host: Option<&str>, gpu: u32, process: &ProcessData, idle_for: Duration) -> Self {
+        Self {
+            host: host.map(str::to_string),
+            gpu,
+            pid: process.info.pid,
+            name: process.name.clone(),
+            user: process.user.clone(),
+            memory_bytes: match process.info.used_gpu_memory {
+                UsedGpuMemory::Used(bytes) => bytes,
+                UsedGpuMemory::Unavailable => 0,
+            },
+            idle_secs: idle_for.as_secs(),
+        }
+    }
+}
+
+/// Lists the processes that have held memory without using the GPU for at least the given
+/// number of minutes.
+///
+/// With `--remote` or configured remotes, this asks the agents, which have been tracking
+/// idle time for as long as they've been running, so it answers straight away. Otherwise,
+/// or with `local`, it watches this machine's GPUs for that long first, as nothing else is
+/// keeping track of them.
+pub fn run_idle_report(
+    config: Config,
+    minutes: u64,
+    json: bool,
+    local: bool,
+    filter: &ProcessFilter,
+) -> Result<(), String> {
+    let idle = if local || config.remotes.is_empty() {
+        watch_local_gpus(config, minutes, filter)
+    } else {
+        query_agents(&config, minutes, filter)?
+    };
+    print_report(idle, minutes, json);
+    Ok(())
+}
+
+/// Asks each agent for its processes, keeping the ones it's seen idle for long enough.
+fn query_agents(
+    config: &Config,
+    minutes: u64,
+    filter: &ProcessFilter,
+) -> Result<Vec<IdleProcess>, String> {
+    let min_idle = Duration::from_secs(minutes * 60);
+
+    // The local GPUs are only covered if an agent is running here too
+    let addresses = config
+        .remotes
+        .iter()
+        .map(|remote| remote.address.as_str())
+        .collect::<Vec<_>>();
+    eprintln!(
+        "Asking the agents at {} for their idle processes, use --local to watch this \
+         machine's GPUs instead",
+        addresses.join(", ")
+    );
+
+    let mut idle = Vec::new();
+    let mut answered = false;
+    for remote in &config.remotes {
+        let snapshot = match fetch_snapshot(remote) {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                eprintln!("Couldn't query {}: {}", remote.address, err);
+                continue;
+            }
+        };
+        answered = true;
+
+        for device in &snapshot.devices {
+            let Ok(processes) = &device.processes else {
+                continue;
+            };
+            for process in processes.iter().filter(|process| filter.matches(process)) {
+                if let Some(idle_for) = process.idle_for.filter(|idle_for| *idle_for >= min_idle) {
+                    idle.push(IdleProcess::new(
+                        Some(&snapshot.host),
+                        device.index,
+                        process,
+                        idle_for,
+                    ));
+                }
+            }
+        }
+    }
+
+    // An empty report would look like nothing was idle
+    if !answered {
+        return Err("none of the agents could be queried".to_string());
+    }
+    Ok(idle)
+}
+
+/// Watches the local GPUs for the given number of minutes, keeping the processes that held
+/// memory without using the GPU for the whole time.
+fn watch_local_gpus(config: Config, minutes: u64, filter: &ProcessFilter) -> Vec<IdleProcess> {
+    let config = Config {
+        remotes: Vec::new(),
+        ..config.without_alert_delivery()
+    };
+    let mut data = GpuMonitoringData::new(&config);
+
+    let window = Duration::from_secs(minutes * 60);
+    eprintln!("Watching for idle processes for {} minutes...", minutes);
+
+    let start = Instant::now();
+    loop {
+        data.update();
+        if start.elapsed() >= window {
+            break;
+        }
+        thread::sleep(Duration::from_millis(250));
+    }
+
+    // Processes are only sampled once a second, so allow for the first sample being late
+    let min_idle = window.saturating_sub(Duration::from_secs(2));
+
+    let mut idle = Vec::new();
+    for gpu in data.gpus() {
+        let gpu_index = gpu.device_index();
+        let Ok(processes) = gpu.processes_mut().all_processes() else {
+            continue;
+        };
+
+        for process in processes.iter().filter(|process| filter.matches(process)) {
+            if let Some(idle_for) = process.idle_for.filter(|idle_for| *idle_for >= min_idle) {
+                idle.push(IdleProcess::new(None, gpu_index, process, idle_for));
+            }
+        }
+    }
+    idle
+}
+
+fn print_report(mut idle: Vec<IdleProcess>, minutes: u64, json: bool) {
+    idle.sort_by_key(|process| Reverse(process.memory_bytes));
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&idle).unwrap());
+        return;
+    }
+
+    if idle.is_empty() {
+        println!("No processes were idle for {} minutes", minutes);
+        return;
+    }
+
+    let gpu_label = |process: &IdleProcess| match &process.host {
+        Some(host) => format!("{}:{}", host, process.gpu),
+        None => process.gpu.to_string(),
+    };
+    let gpu_width = idle
+        .iter()
+        .map(|p| gpu_label(p).len())
+        .max()
+        .unwrap_or(0)
+        .max(3);
+
+    println!(
+        "{:<gpu_width$} {:<8} {:<12} {:>10} {:>6}  NAME",
+        "GPU",
+        "PID",
+        "USER",
+        "MEMORY",
+        "IDLE",
+        gpu_width = gpu_width
+    );
+    for process in &idle {
+        println!(
+            "{:<gpu_width$} {:<8} {:<12} {:>10} {:>6}  {}",
+            gpu_label(process),
+            process.pid,
+            process.user.as_deref().unwrap_or("?"),
+            bytes_to_mib_gib(process.memory_bytes as f32),
+            format_duration(Duration::from_secs(process.idle_secs)),
+            process.name,
+            gpu_width = gpu_width
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! Colour themes shared by the GUI and the TUI: the built-in dark, light and high-contrast
+//! palettes, user palettes from the config, and following the desktop's preference.
+
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+/// An sRGB colour, written as `"#rrggbb"` in the config.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(try_from = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl TryFrom<String> for Rgb {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid colour {:?}, expected \"#rrggbb\"", text);
+
+        let hex = text.strip_prefix('#').ok_or_else(invalid)?;
+        // from_str_radix would also take a sign, as in "#+1+2+3"
+        if hex.len() != 6 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let channel = |range| u8::from_str_radix(&hex[range], 16).map_err(|_| invalid());
+        Ok(Rgb(channel(0..2)?, channel(2..4)?, channel(4..6)?))
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Palette {
+    /// Whether the palette has a dark background.
+    pub dark: bool,
+    /// Graph lines, buttons and selections.
+    pub primary: Rgb,
+    pub primary_hover: Rgb,
+    pub primary_active: Rgb,
+    /// Text on top of the primary colour.
+    pub primary_text: Rgb,
+    pub background: Rgb,
+    /// Behind graphs and text fields.
+    pub background_dark: Rgb,
+    pub text: Rgb,
+    /// Headings and table headers.
+    pub accent: Rgb,
+    pub warning: Rgb,
+    pub error: Rgb,
+    /// Readings that are out of date.
+    pub muted: Rgb,
+}
+
+impl Palette {
+    pub fn dark() -> Self {
+        Self {
+            dark: true,
+            primary: Rgb(30, 164, 48),
+            primary_hover: Rgb(24, 149, 48),
+            primary_active: Rgb(20, 130, 46),
+            primary_text: Rgb(194, 255, 215),
+            background: Rgb(23, 18, 18),
+            background_dark: Rgb(16, 10, 10),
+            text: Rgb(140, 140, 140),
+            accent: Rgb(86, 196, 214),
+            warning: Rgb(255, 143, 0),
+            error: Rgb(255, 0, 0),
+            muted: Rgb(110, 110, 110),
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            dark: false,
+            primary: Rgb(30, 140, 48),
+            primary_hover: Rgb(24, 125, 44),
+            primary_active: Rgb(20, 110, 40),
+            primary_text: Rgb(255, 255, 255),
+            background: Rgb(246, 244, 244),
+            background_dark: Rgb(255, 255, 255),
+            text: Rgb(60, 60, 60),
+            accent: Rgb(0, 110, 140),
+            warning: Rgb(190, 100, 0),
+            error: Rgb(200, 0, 0),
+            muted: Rgb(150, 150, 150),
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Self {
+            dark: true,
+            primary: Rgb(0, 230, 64),
+            primary_hover: Rgb(64, 255, 120),
+            primary_active: Rgb(0, 200, 60),
+            primary_text: Rgb(0, 0, 0),
+            background: Rgb(0, 0, 0),
+            background_dark: Rgb(0, 0, 0),
+            text: Rgb(255, 255, 255),
+            accent: Rgb(0, 255, 255),
+            warning: Rgb(255, 255, 0),
+            error: Rgb(255, 64, 64),
+            muted: Rgb(190, 190, 190),
+        }
+    }
+
+    /// Replaces green, amber and red with blue, orange and vermillion from the Okabe-Ito
+    /// palette, which stay distinct with the common kinds of colour blindness.
+    pub fn color_blind_safe(self) -> Self {
+        let (primary, primary_text) = if self.dark {
+            (Rgb(86, 180, 233), Rgb(0, 0, 0))
+        } else {
+            (Rgb(0, 114, 178), Rgb(255, 255, 255))
+        };
+        let darken = |Rgb(r, g, b): Rgb, factor: f32| {
+            let channel = |value: u8| (value as f32 * factor) as u8;
+            Rgb(channel(r), channel(g), channel(b))
+        };
+
+        Self {
+            primary,
+            primary_hover: darken(primary, 0.9),
+            primary_active: darken(primary, 0.8),
+            primary_text,
+            warning: Rgb(230, 159, 0),
+            error: Rgb(213, 94, 0),
+            ..self
+        }
+    }
+
+    fn built_in(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            _ => None,
+        }
+    }
+}
+
+/// A user palette from the config, which changes some colours of a built-in theme.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CustomTheme {
+    /// The built-in theme to start from, `dark` by default.
+    pub base: Option<String>,
+    pub dark: Option<bool>,
+    pub primary: Option<Rgb>,
+    pub primary_hover: Option<Rgb>,
+    pub primary_active: Option<Rgb>,
+    pub primary_text: Option<Rgb>,
+    pub background: Option<Rgb>,
+    pub background_dark: Option<Rgb>,
+    pub text: Option<Rgb>,
+    pub accent: Option<Rgb>,
+    pub warning: Option<Rgb>,
+    pub error: Option<Rgb>,
+    pub muted: Option<Rgb>,
+}
+
+impl CustomTheme {
+    fn palette(&self) -> Result<Palette, String> {
+        let base = self.base.as_deref().unwrap_or("dark");
+        let base =
+            Palette::built_in(base).ok_or_else(|| format!("unknown base theme {:?}", base))?;
+
+        Ok(Palette {
+            dark: self.dark.unwrap_or(base.dark),
+            primary: self.primary.unwrap_or(base.primary),
+            primary_hover: self.primary_hover.unwrap_or(base.primary_hover),
+            primary_active: self.primary_active.unwrap_or(base.primary_active),
+            primary_text: self.primary_text.unwrap_or(base.primary_text),
+            background: self.background.unwrap_or(base.background),
+            background_dark: self.background_dark.unwrap_or(base.background_dark),
+            text: self.text.unwrap_or(base.text),
+            accent: self.accent.unwrap_or(base.accent),
+            warning: self.warning.unwrap_or(base.warning),
+            error: self.error.unwrap_or(base.error),
+            muted: self.muted.unwrap_or(base.muted),
+        })
+    }
+}
+
+/// Picks the palette for a theme name, which is a built-in theme, one of `custom`, or
+/// `system` to use the dark or light theme depending on `prefers_dark`.
+pub fn resolve_palette(
+    name: &str,
+    custom: &HashMap<String, CustomTheme>,
+    prefers_dark: Option<bool>,
+) -> Result<Palette, String> {
+    if name == "system" {
+        // Without a known preference, stick with the original dark look
+        return Ok(match prefers_dark {
+            Some(false) => Palette::light(),
+            _ => Palette::dark(),
+        });
+    }
+
+    if let Some(theme) = custom.get(name) {
+        return theme
+            .palette()
+            .map_err(|err| format!("theme {:?}: {}", name, err));
+    }
+
+    Palette::built_in(name).ok_or_else(|| format!("unknown theme {:?}", name))
+}
+
+/// Asks the desktop for its colour scheme through the freedesktop settings portal.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn portal_prefers_dark(connection: &zbus::blocking::Connection) -> Option<bool> {
+    use zbus::zvariant::OwnedValue;
+
+    let reply = connection
+        .call_method(
+            Some("org.freedesktop.portal.Desktop"),
+            "/org/freedesktop/portal/desktop",
+            Some("org.freedesktop.portal.Settings"),
+            "ReadOne",
+            &("org.freedesktop.appearance", "color-scheme"),
+        )
+        .ok()?;
+    let value = reply.body().deserialize::<OwnedValue>().ok()?;
+
+    // 1 is a preference for dark, 2 for light, and 0 no preference
+    match u32::try_from(value).ok()? {
+        1 => Some(true),
+        2 => Some(false),
+        _ => None,
+    }
+}
+
+/// Keeps track of whether the desktop prefers dark or light themes, checking in the
+/// background so that the GUI can follow it when it changes.
+pub struct SystemThemeWatcher {
+    prefers_dark: Arc<Mutex<Option<bool>>>,
+}
+
+impl SystemThemeWatcher {
+    pub fn spawn() -> Self {
+        let prefers_dark = Arc::new(Mutex::new(None));
+
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            let prefers_dark = prefers_dark.clone();
+            thread::spawn(move || {
+                let Ok(connection) = zbus::blocking::Connection::session() else {
+                    return;
+                };
+                loop {
+                    *prefers_dark.lock().unwrap() = portal_prefers_dark(&connection);
+                    thread::sleep(Duration::from_secs(5));
+                }
+            });
+        }
+
+        Self { prefers_dark }
+    }
+
+    pub fn prefers_dark(&self) -> Option<bool> {
+        *self.prefers_dark.lock().unwrap()
+    }
+}
+
+/// Whether the terminal has a dark background, going by the `COLORFGBG` variable some
+/// terminals set, or else the desktop's preference.
+pub fn terminal_prefers_dark() -> Option<bool> {
+    // "foreground;background", where the background is an ANSI colour index
+    if let Some(background) = env::var("COLORFGBG")
+        .ok()
+        .and_then(|value| value.rsplit(';').next()?.parse::<u8>().ok())
+    {
+        return Some(matches!(background, 0..=6 | 8));
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        let connection = zbus::blocking::Connection::session().ok()?;
+        portal_prefers_dark(&connection)
+    }
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Rgb, String> {
+        Rgb::try_from(text.to_string())
+    }
+
+    #[test]
+    fn parses_hex_colours() {
+        assert_eq!(parse("#1a2B3c"), Ok(Rgb(0x1a, 0x2b, 0x3c)));
+        assert_eq!(parse("#000000"), Ok(Rgb(0, 0, 0)));
+        assert_eq!(parse("#ffffff"), Ok(Rgb(255, 255, 255)));
+
+        for text in [
+            "1a2b3c", "#1a2b3", "#1a2b3c4", "#gg0000", "#+1+2+3", "#1a2b3é", "",
+        ] {
+            let err = parse(text).unwrap_err();
+            assert!(err.contains("expected \"#rrggbb\""), "{}: {}", text, err);
+        }
+    }
+
+    #[test]
+    fn custom_themes_change_their_base() {
+        let themes = toml::from_str::<HashMap<String, CustomTheme>>(
+            r##"
+            [paper]
+            base = "light"
+            primary = "#336699"
+
+            [broken]
+            base = "sepia"
+            "##,
+        )
+        .unwrap();
+
+        let paper = resolve_palette("paper", &themes, None).unwrap();
+        assert_eq!(
+            paper,
+            Palette {
+                primary: Rgb(0x33, 0x66, 0x99),
+                ..Palette::light()
+            }
+        );
+
+        let err = resolve_palette("broken", &themes, None).unwrap_err();
+        assert!(err.contains("unknown base theme"), "{}", err);
+        assert!(resolve_palette("missing", &themes, None).is_err());
+        assert_eq!(
+            resolve_palette("system", &themes, Some(false)),
+            Ok(Palette::light())
+        );
+        assert_eq!(
+            resolve_palette("system", &themes, None),
+            Ok(Palette::dark())
+        );
+    }
+}
@@ -0,0 +1,356 @@
+//! Running in the system tray, as a StatusNotifierItem whose icon shows how busy and how hot
+//! the GPUs are. The GUI opens from the tray's menu, with the history sampled while it was
+//! closed.
+
+use std::{
+    cell::RefCell,
+    error::Error,
+    rc::Rc,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    time::Duration,
+};
+
+use eframe::{egui, App};
+use image::{Rgba, RgbaImage};
+use ksni::{
+    blocking::{Handle, TrayMethods},
+    menu::StandardItem,
+    MenuItem, ToolTip,
+};
+
+use crate::{
+    config::Config,
+    data::{
+        levels::{GraphConfig, Level},
+        GpuMonitoringData,
+    },
+    gui::{native_options, GpuApp, ICON},
+    theme::{Palette, Rgb},
+    utils::bytes_to_mib_gib,
+};
+
+/// How often the GPUs are sampled while the window is closed.
+const TICK: Duration = Duration::from_millis(250);
+
+enum TrayAction {
+    Open,
+    Quit,
+}
+
+/// The latest readings of a GPU, as the tray shows them.
+struct GpuSummary {
+    label: String,
+    utilization: Option<f32>,
+    memory_used: Option<u64>,
+    memory_total: u64,
+    temperature: Option<f32>,
+}
+
+impl GpuSummary {
+    fn of_all(data: &mut GpuMonitoringData) -> Vec<Self> {
+        data.gpus()
+            .iter()
+            .map(|gpu| {
+                let sample = gpu.last_sample();
+                GpuSummary {
+                    label: gpu.label(),
+                    utilization: sample.and_then(|sample| sample.utilization),
+                    memory_used: sample.and_then(|sample| sample.memory_used),
+                    memory_total: gpu.max_memory(),
+                    temperature: sample.and_then(|sample| sample.temperature),
+                }
+            })
+            .collect()
+    }
+
+    fn describe(&self) -> String {
+        let utilization = self
+            .utilization
+            .map(|utilization| format!("{:.0}%", utilization))
+            .unwrap_or_else(|| "-".to_string());
+        let memory = self
+            .memory_used
+            .map(|used| {
+                format!(
+                    "{} / {}",
+                    bytes_to_mib_gib(used as f32),
+                    bytes_to_mib_gib(self.memory_total as f32)
+                )
+            })
+            .unwrap_or_else(|| "-".to_string());
+        let temperature = self
+            .temperature
+            .map(|temperature| format!("{:.0}°C", temperature))
+            .unwrap_or_else(|| "-".to_string());
+
+        format!(
+            "{}: {}, {}, {}",
+            self.label, utilization, memory, temperature
+        )
+    }
+}
+
+/// What the tray shows, which ksni reads from its own thread.
+struct GpuTray {
+    gpus: Vec<GpuSummary>,
+    icon: ksni::Icon,
+    actions: Sender<TrayAction>,
+}
+
+impl ksni::Tray for GpuTray {
+    fn id(&self) -> String {
+        "gpustat".to_string()
+    }
+
+    fn title(&self) -> String {
+        "gpustat".to_string()
+    }
+
+    fn icon_pixmap(&self) -> Vec<ksni::Icon> {
+        vec![self.icon.clone()]
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        ToolTip {
+            title: "gpustat".to_string(),
+            description: self
+                .gpus
+                .iter()
+                .map(GpuSummary::describe)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            ..Default::default()
+        }
+    }
+
+    fn activate(&mut self, _x: i32, _y: i32) {
+        let _ = self.actions.send(TrayAction::Open);
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let mut items = self
+            .gpus
+            .iter()
+            .map(|gpu| {
+                StandardItem {
+                    // Underscores would otherwise mark the next letter as the access key
+                    label: gpu.describe().replace('_', "__"),
+                    enabled: false,
+                    ..Default::default()
+                }
+                .into()
+            })
+            .collect::<Vec<_>>();
+        if items.is_empty() {
+            items.push(
+                StandardItem {
+                    label: "Waiting for GPUs...".to_string(),
+                    enabled: false,
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        items.push(MenuItem::Separator);
+        items.push(
+            StandardItem {
+                label: "Open gpustat".to_string(),
+                activate: Box::new(|tray: &mut Self| {
+                    let _ = tray.actions.send(TrayAction::Open);
+                }),
+                ..Default::default()
+            }
+            .into(),
+        );
+        items.push(
+            StandardItem {
+                label: "Quit".to_string(),
+                activate: Box::new(|tray: &mut Self| {
+                    let _ = tray.actions.send(TrayAction::Quit);
+                }),
+                ..Default::default()
+            }
+            .into(),
+        );
+        items
+    }
+}
+
+/// The app icon with bars along the bottom for the busiest GPU's utilization and the hottest
+/// GPU's temperature, which is coloured by how close it is to its limit.
+fn render_icon(
+    base: &RgbaImage,
+    gpus: &[GpuSummary],
+    palette: &Palette,
+    graphs: &GraphConfig,
+) -> ksni::Icon {
+    let mut image = base.clone();
+    let (width, height) = image.dimensions();
+    let bar_height = height / 6;
+    let gap = height / 32;
+
+    let utilization = gpus
+        .iter()
+        .filter_map(|gpu| gpu.utilization)
+        .reduce(f32::max);
+    let temperature = gpus
+        .iter()
+        .filter_map(|gpu| gpu.temperature)
+        .reduce(f32::max);
+    let temperature_color = match temperature.map(|t| graphs.temperature.level(t)) {
+        Some(Level::Critical) => palette.error,
+        Some(Level::Warning) => palette.warning,
+        _ => palette.accent,
+    };
+
+    let top = height - bar_height * 2 - gap;
+    draw_bar(
+        &mut image,
+        top,
+        bar_height,
+        utilization.map(|utilization| utilization / 100.0),
+        palette.primary,
+        palette.background_dark,
+    );
+    draw_bar(
+        &mut image,
+        top + bar_height + gap,
+        bar_height,
+        temperature.map(|temperature| temperature / 100.0),
+        temperature_color,
+        palette.background_dark,
+    );
+
+    // ARGB in network byte order
+    let data = image
+        .pixels()
+        .flat_map(|Rgba([r, g, b, a])| [*a, *r, *g, *b])
+        .collect();
+    ksni::Icon {
+        width: width as i32,
+        height: height as i32,
+        data,
+    }
+}
+
+/// Draws a bar across the image filled from the left, or left empty without a reading.
+fn draw_bar(
+    image: &mut RgbaImage,
+    top: u32,
+    height: u32,
+    fraction: Option<f32>,
+    Rgb(r, g, b): Rgb,
+    Rgb(track_r, track_g, track_b): Rgb,
+) {
+    let width = image.width();
+    let filled = (fraction.unwrap_or(0.0).clamp(0.0, 1.0) * width as f32).round() as u32;
+    for y in top..top + height {
+        for x in 0..width {
+            let pixel = if x < filled {
+                Rgba([r, g, b, 255])
+            } else {
+                Rgba([track_r, track_g, track_b, 255])
+            };
+            image.put_pixel(x, y, pixel);
+        }
+    }
+}
+
+struct TrayState {
+    app: GpuApp,
+    handle: Handle<GpuTray>,
+    actions: Receiver<TrayAction>,
+    base_icon: RgbaImage,
+    palette: Palette,
+    graphs: GraphConfig,
+    quitting: bool,
+}
+
+impl TrayState {
+    /// Shows the latest readings in the tray.
+    fn refresh_tray(&mut self) {
+        let gpus = GpuSummary::of_all(self.app.data_mut());
+        let icon = render_icon(&self.base_icon, &gpus, &self.palette, &self.graphs);
+        self.handle.update(|tray| {
+            tray.gpus = gpus;
+            tray.icon = icon;
+        });
+    }
+}
+
+/// The GUI opened from the tray, which keeps the tray up to date while it's open.
+struct TrayWindow(Rc<RefCell<TrayState>>);
+
+impl App for TrayWindow {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let mut state = self.0.borrow_mut();
+        state.app.update(ctx, frame);
+        state.refresh_tray();
+
+        while let Ok(action) = state.actions.try_recv() {
+            match action {
+                TrayAction::Open => ctx.send_viewport_cmd(egui::ViewportCommand::Focus),
+                TrayAction::Quit => {
+                    state.quitting = true;
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+            }
+        }
+    }
+}
+
+pub fn run_tray(config: Config) -> Result<(), Box<dyn Error>> {
+    let palette = config.palette(None);
+    let graphs = config.graphs.clone();
+    let base_icon = image::load_from_memory(ICON)?.into_rgba8();
+
+    let (sender, actions) = mpsc::channel();
+    let tray = GpuTray {
+        gpus: Vec::new(),
+        icon: render_icon(&base_icon, &[], &palette, &graphs),
+        actions: sender,
+    };
+    let handle = tray.spawn()?;
+
+    let state = Rc::new(RefCell::new(TrayState {
+        app: GpuApp::new(config),
+        handle,
+        actions,
+        base_icon,
+        palette,
+        graphs,
+        quitting: false,
+    }));
+
+    loop {
+        let action = {
+            let mut state = state.borrow_mut();
+            if state.quitting {
+                break;
+            }
+            state.app.data_mut().update();
+            state.refresh_tray();
+            state.actions.recv_timeout(TICK)
+        };
+
+        match action {
+            Ok(TrayAction::Open) => {
+                // Sampling carries on in the window, so its graphs continue the history
+                eframe::run_native(
+                    "gpustat",
+                    native_options(),
+                    Box::new({
+                        let state = state.clone();
+                        |_cc| Box::new(TrayWindow(state))
+                    }),
+                )?;
+            }
+            Ok(TrayAction::Quit) | Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+    }
+
+    state.borrow().handle.shutdown().wait();
+    Ok(())
+}
@@ -0,0 +1,271 @@
+//! Layouts for the TUI, which assign panels to rows of the screen. There are built-in presets
+//! and ones from the config, and the user cycles through them with a key.
+
+use ratatui::layout::{Constraint, Layout, Rect};
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TuiPanel {
+    Usage,
+    Memory,
+    Temperature,
+    Processes,
+    Events,
+    Gpus,
+    /// The selected GPU's device info.
+    Info,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TuiRow {
+    /// The row's share of the height, relative to the other rows.
+    pub height: u16,
+    pub panels: Vec<TuiPanel>,
+    /// The panels' shares of the width, or equal shares if not given.
+    #[serde(default)]
+    pub widths: Vec<u16>,
+}
+
+impl TuiRow {
+    fn new(height: u16, panels: &[TuiPanel]) -> Self {
+        Self {
+            height,
+            panels: panels.to_vec(),
+            widths: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TuiPreset {
+    pub name: String,
+    pub rows: Vec<TuiRow>,
+}
+
+/// The `[tui_layout]` config section.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct TuiLayoutConfig {
+    /// The preset to start with, `default` if not given.
+    pub preset: Option<String>,
+    /// More presets, which replace the built-in ones with the same name.
+    pub presets: Vec<TuiPreset>,
+}
+
+/// Panels narrower or shorter than this have no room for their contents.
+const MIN_PANEL_WIDTH: u16 = 24;
+const MIN_PANEL_HEIGHT: u16 = 6;
+
+fn built_in_presets() -> Vec<TuiPreset> {
+    use TuiPanel::*;
+
+    vec![
+        TuiPreset {
+            name: "default".to_string(),
+            rows: vec![
+                TuiRow::new(60, &[Usage, Processes]),
+                TuiRow::new(40, &[Memory, Temperature]),
+            ],
+        },
+        TuiPreset {
+            name: "processes".to_string(),
+            rows: vec![
+                TuiRow::new(30, &[Usage, Memory]),
+                TuiRow::new(70, &[Processes]),
+            ],
+        },
+        TuiPreset {
+            name: "charts".to_string(),
+            rows: vec![
+                TuiRow::new(1, &[Usage]),
+                TuiRow::new(1, &[Memory]),
+                TuiRow::new(1, &[Temperature]),
+            ],
+        },
+        TuiPreset {
+            name: "overview".to_string(),
+            rows: vec![
+                TuiRow::new(50, &[Gpus, Usage]),
+                TuiRow::new(50, &[Processes, Events]),
+            ],
+        },
+    ]
+}
+
+impl TuiLayoutConfig {
+    /// The built-in presets followed by the configured ones.
+    pub fn presets(&self) -> Vec<TuiPreset> {
+        let mut presets = built_in_presets();
+        for preset in &self.presets {
+            match presets.iter_mut().find(|p| p.name == preset.name) {
+                Some(existing) => *existing = preset.clone(),
+                None => presets.push(preset.clone()),
+            }
+        }
+        presets
+    }
+
+    /// The index of the preset to start with in `presets()`.
+    pub fn initial_preset(&self) -> usize {
+        let name = self.preset.as_deref().unwrap_or("default");
+        self.presets()
+            .iter()
+            .position(|preset| preset.name == name)
+            .unwrap_or(0)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for preset in &self.presets {
+            let invalid = |reason: &str| format!("TUI preset {:?} {}", preset.name, reason);
+            if preset.rows.is_empty() {
+                return Err(invalid("has no rows"));
+            }
+            for row in &preset.rows {
+                if row.panels.is_empty() {
+                    return Err(invalid("has a row without panels"));
+                }
+                if !row.widths.is_empty() && row.widths.len() != row.panels.len() {
+                    return Err(invalid(
+                        "has a row with a different number of widths and panels",
+                    ));
+                }
+            }
+        }
+
+        if let Some(name) = &self.preset {
+            if !self.presets().iter().any(|preset| &preset.name == name) {
+                return Err(format!("unknown TUI preset {:?}", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TuiPreset {
+    /// Where each panel goes in `area`. The panels of a row that would be too narrow side by
+    /// side are stacked instead.
+    fn place(&self, area: Rect) -> Vec<(TuiPanel, Rect)> {
+        let total_height = self.rows.iter().map(|row| row.height as u32).sum::<u32>();
+        let row_areas = Layout::vertical(
+            self.rows
+                .iter()
+                .map(|row| Constraint::Ratio(row.height as u32, total_height.max(1))),
+        )
+        .split(area);
+
+        let mut placed = Vec::new();
+        for (row, &row_area) in self.rows.iter().zip(row_areas.iter()) {
+            let count = row.panels.len() as u16;
+            let panel_areas = if row_area.width / count < MIN_PANEL_WIDTH {
+                Layout::vertical(vec![Constraint::Ratio(1, count as u32); count as usize])
+                    .split(row_area)
+            } else if row.widths.is_empty() {
+                Layout::horizontal(vec![Constraint::Ratio(1, count as u32); count as usize])
+                    .split(row_area)
+            } else {
+                let total_width = row.widths.iter().map(|width| *width as u32).sum::<u32>();
+                Layout::horizontal(
+                    row.widths
+                        .iter()
+                        .map(|width| Constraint::Ratio(*width as u32, total_width.max(1))),
+                )
+                .split(row_area)
+            };
+            placed.extend(row.panels.iter().copied().zip(panel_areas.iter().copied()));
+        }
+        placed
+    }
+}
+
+/// Where each panel of `preset` goes in `area`. Terminals too small for the preset get the
+/// usage chart above the processes, or only the processes if even that doesn't fit.
+pub fn place_panels(preset: &TuiPreset, area: Rect) -> Vec<(TuiPanel, Rect)> {
+    let placed = preset.place(area);
+    let fits = placed
+        .iter()
+        .all(|(_, rect)| rect.width >= MIN_PANEL_WIDTH && rect.height >= MIN_PANEL_HEIGHT);
+    if fits {
+        return placed;
+    }
+
+    if area.height >= MIN_PANEL_HEIGHT * 2 && area.width >= MIN_PANEL_WIDTH {
+        let [usage, processes] =
+            Layout::vertical([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(area);
+        vec![(TuiPanel::Usage, usage), (TuiPanel::Processes, processes)]
+    } else {
+        vec![(TuiPanel::Processes, area)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placed(preset: &str, width: u16, height: u16) -> Vec<(TuiPanel, Rect)> {
+        let presets = built_in_presets();
+        let preset = presets.iter().find(|p| p.name == preset).unwrap();
+        place_panels(preset, Rect::new(0, 0, width, height))
+    }
+
+    fn panels(placed: &[(TuiPanel, Rect)]) -> Vec<TuiPanel> {
+        placed.iter().map(|(panel, _)| *panel).collect()
+    }
+
+    #[test]
+    fn places_the_preset_when_it_fits() {
+        use TuiPanel::*;
+
+        let placed = placed("default", 120, 40);
+        assert_eq!(
+            placed,
+            [
+                (Usage, Rect::new(0, 0, 60, 24)),
+                (Processes, Rect::new(60, 0, 60, 24)),
+                (Memory, Rect::new(0, 24, 60, 16)),
+                (Temperature, Rect::new(60, 24, 60, 16)),
+            ]
+        );
+    }
+
+    #[test]
+    fn stacks_panels_that_would_be_too_narrow_side_by_side() {
+        use TuiPanel::*;
+
+        let placed = placed("default", 40, 40);
+        assert_eq!(panels(&placed), [Usage, Processes, Memory, Temperature]);
+        assert!(placed
+            .iter()
+            .all(|(_, rect)| rect.x == 0 && rect.width == 40));
+    }
+
+    #[test]
+    fn small_terminals_fall_back_to_fewer_panels() {
+        use TuiPanel::*;
+
+        let placed = placed("default", 30, 14);
+        assert_eq!(panels(&placed), [Usage, Processes]);
+        assert_eq!(placed[0].1.height + placed[1].1.height, 14);
+
+        // Too small even for two panels, or for anything at all
+        for (width, height) in [(30, 11), (20, 40), (10, 3), (0, 0)] {
+            let area = Rect::new(0, 0, width, height);
+            let presets = built_in_presets();
+            for preset in &presets {
+                assert_eq!(place_panels(preset, area), [(Processes, area)]);
+            }
+        }
+    }
+
+    #[test]
+    fn rows_without_height_dont_divide_by_zero() {
+        let preset = TuiPreset {
+            name: "flat".to_string(),
+            rows: vec![TuiRow::new(0, &[TuiPanel::Usage])],
+        };
+        let placed = place_panels(&preset, Rect::new(0, 0, 80, 24));
+        assert_eq!(panels(&placed), [TuiPanel::Usage, TuiPanel::Processes]);
+    }
+}
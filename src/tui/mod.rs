@@ -1,74 +1,497 @@
 use std::{
     error::Error,
-    io,
+    io::{self, Write},
     time::{Duration, Instant},
 };
 
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use eframe::App;
 use ratatui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Alignment, Constraint, Layout, Rect},
-    style::{Color, Style, Stylize},
-    symbols,
-    widgets::{block::Title, Axis, Block, Borders, Chart, Dataset, GraphType, LegendPosition},
+    layout::{Constraint, Layout, Rect},
     Frame, Terminal,
 };
 
 use crate::{
-    data::{process_table::ProcessTableData, GpuDeviceMonitor, GpuMonitoringData},
-    utils::bytes_to_mib_gib,
+    config::Config,
+    data::{
+        cluster::{ClusterColumn, ClusterTableData},
+        graph::GraphViewerData,
+        levels::GraphConfig,
+        process_table::ProcessTableData,
+        GpuMonitoringData,
+    },
+    theme::terminal_prefers_dark,
+    utils::{base64_encode, bytes_to_mib_gib, export_path, format_elapsed},
 };
 
-use self::views::{
-    render_memory_chart, render_process_table, render_temperature_chart, render_usage_chart,
-    ProcessTableState,
+use self::{
+    layout::{place_panels, TuiPanel, TuiPreset},
+    theme::TuiTheme,
+    views::{
+        render_alert_bar, render_cluster_view, render_device_info, render_event_log,
+        render_gpu_list, render_gpu_tabs, render_memory_chart, render_process_memory_chart,
+        render_process_table, render_process_usage_chart, render_status_line,
+        render_temperature_chart, render_throttle_strip, render_usage_chart,
+        render_waiting_for_gpus, throttle_strip_height, ClusterViewState, GpuPickerState,
+        ProcessTableState, TableHit,
+    },
 };
 
+pub mod layout;
+mod theme;
 mod views;
 
-#[derive(Default, Debug, PartialEq, Eq)]
-enum SelectedProcessTab {
-    #[default]
-    Graphics,
-    Compute,
-}
-
-#[derive(Default, Debug, PartialEq, Eq)]
-enum SelectedSection {
-    #[default]
-    Graphics,
-    Compute,
-}
-
 pub struct TuiApp {
     data: GpuMonitoringData,
-
-    selected_process_tab: SelectedProcessTab,
-    updated_style: bool,
+    theme: TuiTheme,
+    graphs: GraphConfig,
+    presets: Vec<TuiPreset>,
+    /// The index of the layout preset in use, which `v` cycles through.
+    preset: usize,
+    /// Where each panel was last rendered, to find what's clicked.
+    placed: Vec<(TuiPanel, Rect)>,
 
     selected_gpu: usize,
+    gpu_picker: GpuPickerState,
+    /// Whether every GPU across all hosts is shown instead of the selected GPU.
+    show_cluster: bool,
+    cluster_table: ClusterTableData,
+    cluster_state: ClusterViewState,
 
     table_state: ProcessTableState,
+    /// Whether keystrokes are currently being typed into the process search query.
+    searching: bool,
+    /// Whether the selected process's graphs replace the memory and temperature charts.
+    show_process_detail: bool,
+    /// Whether the event log replaces the temperature chart.
+    show_event_log: bool,
+    event_export_status: String,
+    /// Whether the device info replaces the process table.
+    show_device_info: bool,
+    info_copy_status: String,
+    /// How far the device info is scrolled down.
+    info_scroll: u16,
+    /// The value under the last click on a chart.
+    chart_status: String,
 }
 
-impl Default for TuiApp {
-    fn default() -> Self {
+impl TuiApp {
+    pub fn new(config: Config) -> Self {
         Self {
-            data: GpuMonitoringData::new(),
-            updated_style: false,
-            selected_process_tab: SelectedProcessTab::Graphics,
+            data: GpuMonitoringData::new(&config),
+            theme: TuiTheme::new(&config.palette(terminal_prefers_dark())),
+            graphs: config.graphs.clone(),
+            presets: config.tui_layout.presets(),
+            preset: config.tui_layout.initial_preset(),
+            placed: Vec::new(),
             selected_gpu: 0,
+            gpu_picker: Default::default(),
+            show_cluster: !config.remotes.is_empty(),
+            cluster_table: ClusterTableData::new(),
+            cluster_state: Default::default(),
             table_state: Default::default(),
+            searching: false,
+            show_process_detail: false,
+            show_event_log: false,
+            event_export_status: String::new(),
+            show_device_info: false,
+            info_copy_status: String::new(),
+            info_scroll: 0,
+            chart_status: String::new(),
         }
     }
 }
 
-pub fn run_tui_app() -> Result<(), Box<dyn Error>> {
+impl TuiApp {
+    /// Handles a key press, returning true if the app should exit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return true;
+        }
+
+        if !self.searching {
+            match key.code {
+                KeyCode::Char('l') => {
+                    self.show_event_log = !self.show_event_log;
+                    return false;
+                }
+                KeyCode::Char('c') => {
+                    self.show_cluster = !self.show_cluster;
+                    return false;
+                }
+                KeyCode::Char('v') => {
+                    self.preset = (self.preset + 1) % self.presets.len();
+                    return false;
+                }
+                KeyCode::Char('g') => {
+                    let gpu_count = self.data.gpus().len();
+                    if gpu_count > 0 {
+                        self.selected_gpu = (self.selected_gpu + 1) % gpu_count;
+                    }
+                    return false;
+                }
+                KeyCode::Char('x') => {
+                    let path = export_path("gpustat-events", "tsv");
+                    self.event_export_status = match self.data.events().export(&path) {
+                        Ok(()) => format!("exported to {}", path.display()),
+                        Err(err) => format!("export failed: {}", err),
+                    };
+                    self.show_event_log = true;
+                    return false;
+                }
+                KeyCode::Char('i') => {
+                    self.show_device_info = !self.show_device_info;
+                    return false;
+                }
+                KeyCode::Char('y') => {
+                    let report = self
+                        .data
+                        .gpus()
+                        .get(self.selected_gpu)
+                        .and_then(|gpu| gpu.info_report());
+                    self.info_copy_status = match report.map(|report| copy_to_clipboard(&report)) {
+                        Some(Ok(())) => "copied".to_string(),
+                        Some(Err(err)) => format!("copy failed: {}", err),
+                        None => "nothing to copy".to_string(),
+                    };
+                    self.show_device_info = true;
+                    return false;
+                }
+                _ => {}
+            }
+        }
+
+        if self.show_cluster && !self.searching {
+            self.handle_cluster_key(key);
+            return false;
+        }
+
+        let Some(gpu) = self.data.gpus().get_mut(self.selected_gpu) else {
+            return false;
+        };
+        let table = gpu.processes_mut();
+
+        match key.code {
+            KeyCode::Up if !self.searching => {
+                move_selection(table, -1);
+                return false;
+            }
+            KeyCode::Down if !self.searching => {
+                move_selection(table, 1);
+                return false;
+            }
+            KeyCode::Enter if !self.searching => {
+                self.show_process_detail = !self.show_process_detail;
+                return false;
+            }
+            _ => {}
+        }
+
+        let filter = table.filter_mut();
+
+        if self.searching {
+            match key.code {
+                KeyCode::Char(c) => {
+                    let mut query = filter.query().to_string();
+                    query.push(c);
+                    filter.set_query(query);
+                }
+                KeyCode::Backspace => {
+                    let mut query = filter.query().to_string();
+                    query.pop();
+                    filter.set_query(query);
+                }
+                KeyCode::Enter => self.searching = false,
+                KeyCode::Esc => {
+                    filter.set_query("");
+                    self.searching = false;
+                }
+                _ => {}
+            }
+            return false;
+        }
+
+        match key.code {
+            KeyCode::Char('/') => self.searching = true,
+            KeyCode::Char('m') => filter.only_current_user = !filter.only_current_user,
+            KeyCode::Char('M') => filter.min_memory_mib = next_min_memory(filter.min_memory_mib),
+            KeyCode::Char('r') => {
+                let use_regex = !filter.use_regex();
+                filter.set_use_regex(use_regex);
+            }
+            KeyCode::Esc => filter.clear(),
+            _ => {}
+        }
+
+        false
+    }
+}
+
+impl TuiApp {
+    /// Handles a click or a turn of the scroll wheel.
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let (column, row) = (mouse.column, mouse.row);
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => self.click(column, row),
+            MouseEventKind::ScrollUp => self.scroll(column, row, -1),
+            MouseEventKind::ScrollDown => self.scroll(column, row, 1),
+            _ => {}
+        }
+    }
+
+    fn click(&mut self, column: u16, row: u16) {
+        if self.show_cluster {
+            let mut rows = self.data.cluster_rows();
+            self.cluster_table.sort(&mut rows);
+            match self.cluster_state.hit(column, row) {
+                Some(TableHit::Header(index)) => {
+                    self.cluster_table
+                        .sorting_mut()
+                        .click(ClusterColumn::ALL[index]);
+                }
+                Some(TableHit::Row(index)) => {
+                    if let Some(row) = rows.get(index) {
+                        self.cluster_table.set_selected_gpu(Some(row.gpu));
+                        self.selected_gpu = row.gpu;
+                        self.show_cluster = false;
+                    }
+                }
+                None => {}
+            }
+            return;
+        }
+
+        self.chart_status.clear();
+        if let Some(gpu) = self.gpu_picker.gpu_at(column, row) {
+            if gpu < self.data.gpus().len() {
+                self.selected_gpu = gpu;
+            }
+            return;
+        }
+
+        let Some((panel, rect)) = self.panel_at(column, row) else {
+            return;
+        };
+        match panel {
+            TuiPanel::Processes => {
+                let Some(gpu) = self.data.gpus().get_mut(self.selected_gpu) else {
+                    return;
+                };
+                let table = gpu.processes_mut();
+                match self.table_state.hit(column, row) {
+                    Some(TableHit::Header(index)) => {
+                        if let Some(column) = ProcessTableState::sort_column(index) {
+                            table.sorting_mut().click(column);
+                        }
+                    }
+                    Some(TableHit::Row(index)) => {
+                        let pid = table
+                            .processes()
+                            .ok()
+                            .and_then(|processes| Some(processes.get(index)?.info.pid));
+                        if let Some(pid) = pid {
+                            table.toggle_selected_pid(pid);
+                        }
+                    }
+                    None => {}
+                }
+            }
+            TuiPanel::Usage | TuiPanel::Memory | TuiPanel::Temperature => {
+                // Charts have two values to a cell, the newest at the right edge
+                let index = rect.right().saturating_sub(2).saturating_sub(column) as usize * 2;
+                self.chart_status = self.describe_chart_value(panel, index).unwrap_or_default();
+            }
+            TuiPanel::Events | TuiPanel::Gpus | TuiPanel::Info => {}
+        }
+    }
+
+    fn scroll(&mut self, column: u16, row: u16, offset: isize) {
+        if self.show_cluster {
+            self.move_cluster_selection(offset);
+            return;
+        }
+
+        let on_gpus = self.gpu_picker.gpu_at(column, row).is_some();
+        match self.panel_at(column, row) {
+            _ if on_gpus => {
+                let gpu_count = self.data.gpus().len();
+                if gpu_count > 0 {
+                    self.selected_gpu = self
+                        .selected_gpu
+                        .saturating_add_signed(offset)
+                        .min(gpu_count - 1);
+                }
+            }
+            Some((TuiPanel::Processes, _)) => {
+                if let Some(gpu) = self.data.gpus().get_mut(self.selected_gpu) {
+                    move_selection(gpu.processes_mut(), offset);
+                }
+            }
+            Some((TuiPanel::Info, _)) => {
+                self.info_scroll = self.info_scroll.saturating_add_signed(offset as i16);
+            }
+            _ => {}
+        }
+    }
+
+    fn panel_at(&self, column: u16, row: u16) -> Option<(TuiPanel, Rect)> {
+        self.placed.iter().copied().find(|(_, rect)| {
+            (rect.left()..rect.right()).contains(&column)
+                && (rect.top()..rect.bottom()).contains(&row)
+        })
+    }
+
+    /// Describes the value `index` samples back in the chart shown in `panel`.
+    fn describe_chart_value(&mut self, panel: TuiPanel, index: usize) -> Option<String> {
+        let show_process_detail = self.show_process_detail;
+        let show_event_log = self.show_event_log;
+        let gpu = self.data.gpus().get_mut(self.selected_gpu)?;
+
+        // The selected process's graphs replace the memory and temperature charts
+        let detail = gpu
+            .processes()
+            .selected_history()
+            .filter(|_| show_process_detail);
+        match (panel, detail) {
+            (TuiPanel::Memory, Some((pid, history))) => describe_sample(
+                &format!("{} ({}) memory", history.name(), pid),
+                history.memory_graph(),
+                index,
+                bytes_to_mib_gib,
+            ),
+            (TuiPanel::Temperature, Some((pid, history))) => describe_sample(
+                &format!("{} ({}) GPU usage", history.name(), pid),
+                history.usage_graph(),
+                index,
+                |v| format!("{:.0}%", v),
+            ),
+            (TuiPanel::Temperature, None) if show_event_log => None,
+            (TuiPanel::Temperature, None) => {
+                describe_sample("Temperature", gpu.temperature_graph_mut(), index, |v| {
+                    format!("{:.0}°C", v)
+                })
+            }
+            (TuiPanel::Memory, None) => {
+                describe_sample("Memory", gpu.memory_graph_mut(), index, bytes_to_mib_gib)
+            }
+            (TuiPanel::Usage, _) => {
+                describe_sample("GPU usage", gpu.usage_graph_mut(), index, |v| {
+                    format!("{:.0}%", v)
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Describes a graph's value `index` samples back and how long ago it was recorded.
+fn describe_sample(
+    name: &str,
+    graph: &GraphViewerData,
+    index: usize,
+    value_to_string: impl Fn(f32) -> String,
+) -> Option<String> {
+    let (time, value) = graph.get_sample_at(index)?;
+    let value = value.map_or_else(|| "no reading".to_string(), value_to_string);
+    let elapsed = time.elapsed().unwrap_or_default();
+    Some(format!("{}: {} {}", name, value, format_elapsed(elapsed)))
+}
+
+impl TuiApp {
+    /// Moves the selected GPU up or down the (sorted) cluster table.
+    fn move_cluster_selection(&mut self, offset: isize) {
+        let mut rows = self.data.cluster_rows();
+        self.cluster_table.sort(&mut rows);
+        if rows.is_empty() {
+            return;
+        }
+
+        let current = rows
+            .iter()
+            .position(|row| Some(row.gpu) == self.cluster_table.selected_gpu());
+        let next = match current {
+            Some(index) => index.saturating_add_signed(offset).min(rows.len() - 1),
+            None => 0,
+        };
+        self.cluster_table.set_selected_gpu(Some(rows[next].gpu));
+    }
+
+    fn handle_cluster_key(&mut self, key: KeyEvent) {
+        let mut rows = self.data.cluster_rows();
+        self.cluster_table.sort(&mut rows);
+
+        let current = rows
+            .iter()
+            .position(|row| Some(row.gpu) == self.cluster_table.selected_gpu());
+
+        match key.code {
+            KeyCode::Up => self.move_cluster_selection(-1),
+            KeyCode::Down => self.move_cluster_selection(1),
+            KeyCode::Enter => {
+                if let Some(index) = current {
+                    self.selected_gpu = rows[index].gpu;
+                    self.show_cluster = false;
+                }
+            }
+            KeyCode::Char('s') => {
+                let sorting = self.cluster_table.sorting_mut();
+                let position = ClusterColumn::ALL
+                    .iter()
+                    .position(|column| *column == sorting.column)
+                    .unwrap_or(0);
+                sorting.click(ClusterColumn::ALL[(position + 1) % ClusterColumn::ALL.len()]);
+            }
+            KeyCode::Char('S') => {
+                let sorting = self.cluster_table.sorting_mut();
+                sorting.click(sorting.column);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The minimum memory filter's steps in MiB, which `M` cycles through before turning it off.
+const MIN_MEMORY_STEPS_MIB: [u64; 3] = [100, 1024, 10 * 1024];
+
+fn next_min_memory(current: Option<u64>) -> Option<u64> {
+    match current {
+        None => Some(MIN_MEMORY_STEPS_MIB[0]),
+        Some(current) => MIN_MEMORY_STEPS_MIB
+            .into_iter()
+            .find(|step| *step > current),
+    }
+}
+
+/// Moves the selected process up or down the (filtered and sorted) process list.
+fn move_selection(table: &mut ProcessTableData, offset: isize) {
+    let Ok(processes) = table.processes() else {
+        return;
+    };
+    if processes.is_empty() {
+        table.set_selected_pid(None);
+        return;
+    }
+
+    let current = processes
+        .iter()
+        .position(|process| Some(process.info.pid) == table.selected_pid());
+    let next = match current {
+        Some(index) => index.saturating_add_signed(offset).min(processes.len() - 1),
+        None => 0,
+    };
+
+    let pid = processes[next].info.pid;
+    table.set_selected_pid(Some(pid));
+}
+
+pub fn run_tui_app(config: Config) -> Result<(), Box<dyn Error>> {
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -78,7 +501,7 @@ pub fn run_tui_app() -> Result<(), Box<dyn Error>> {
 
     // create app and run it
     let tick_rate = Duration::from_millis(250);
-    let app = TuiApp::default();
+    let app = TuiApp::new(config);
     let res = run_app(&mut terminal, app, tick_rate);
 
     // restore terminal
@@ -98,29 +521,170 @@ pub fn run_tui_app() -> Result<(), Box<dyn Error>> {
 }
 
 fn ui(frame: &mut Frame, app: &mut TuiApp) {
-    let area = frame.size();
+    let mut area = frame.size();
 
     app.data.update();
 
-    let vertical = Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)]);
-    let horizontal = Layout::horizontal([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]);
+    if app.data.gpus().is_empty() {
+        render_waiting_for_gpus(frame, area, app.data.remotes());
+        return;
+    }
+
+    let firing_alerts = app.data.alerts().firing().count().min(3) as u16;
+    if firing_alerts > 0 {
+        let [alerts, rest] =
+            Layout::vertical([Constraint::Length(firing_alerts), Constraint::Min(0)]).areas(area);
+        render_alert_bar(frame, alerts, &app.theme, app.data.alerts());
+        area = rest;
+    }
 
-    let [top, bottom] = vertical.areas(area);
-    let [top_left, top_right] = horizontal.areas(top);
-    let [bottom_left, bottom_right] = horizontal.areas(bottom);
+    if app.show_cluster {
+        let mut rows = app.data.cluster_rows();
+        app.cluster_table.sort(&mut rows);
+        render_cluster_view(
+            frame,
+            area,
+            &app.theme,
+            &app.data.hosts(),
+            &rows,
+            &app.cluster_table,
+            &mut app.cluster_state,
+        );
+        return;
+    }
 
-    let gpu = &mut app.data.gpus()[0];
+    let theme = &app.theme;
+    let alerting = (0..app.data.gpus().len())
+        .map(|i| app.data.is_alerting(i))
+        .collect::<Vec<_>>();
 
-    render_process_table(
-        frame,
-        top_right,
-        gpu.all_processes_mut().processes(),
-        &mut app.table_state,
-    );
+    // Tabs to click between GPUs, when there's more than one
+    app.gpu_picker.clear();
+    if app.data.gpus().len() > 1 {
+        let [tabs, rest] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(area);
+        render_gpu_tabs(
+            frame,
+            tabs,
+            theme,
+            app.data.gpus(),
+            &alerting,
+            app.selected_gpu,
+            &mut app.gpu_picker,
+        );
+        area = rest;
+    }
 
-    render_usage_chart(frame, top_left, gpu);
-    render_memory_chart(frame, bottom_left, gpu);
-    render_temperature_chart(frame, bottom_right, gpu);
+    if !app.chart_status.is_empty() {
+        let [rest, status] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(area);
+        render_status_line(frame, status, theme, &app.chart_status);
+        area = rest;
+    }
+
+    app.placed = place_panels(&app.presets[app.preset], area);
+    // The device info replaces the process table
+    if app.show_device_info {
+        for (panel, _) in app.placed.iter_mut() {
+            if *panel == TuiPanel::Processes {
+                *panel = TuiPanel::Info;
+            }
+        }
+    }
+    for &(panel, rect) in &app.placed {
+        let gpu = &mut app.data.gpus()[app.selected_gpu];
+        let max_memory = gpu.max_memory();
+        let detail = gpu
+            .processes()
+            .selected_history()
+            .filter(|_| app.show_process_detail);
+
+        match panel {
+            // Throttling is shown in a strip under the usage chart
+            TuiPanel::Usage => {
+                let strip_height = throttle_strip_height(gpu, rect);
+                let [chart, strip] =
+                    Layout::vertical([Constraint::Min(0), Constraint::Length(strip_height)])
+                        .areas(rect);
+                render_usage_chart(frame, chart, theme, gpu);
+                if strip_height > 0 {
+                    render_throttle_strip(frame, strip, theme, gpu);
+                }
+            }
+            TuiPanel::Processes => render_process_table(
+                frame,
+                rect,
+                theme,
+                gpu.processes(),
+                app.searching,
+                &mut app.table_state,
+            ),
+            // The selected process's graphs replace the memory and temperature charts
+            TuiPanel::Memory => match detail {
+                Some((pid, history)) => {
+                    render_process_memory_chart(frame, rect, theme, pid, history, max_memory)
+                }
+                None => render_memory_chart(
+                    frame,
+                    rect,
+                    theme,
+                    gpu,
+                    app.graphs.memory_levels(max_memory),
+                ),
+            },
+            TuiPanel::Temperature => match detail {
+                Some((pid, history)) => {
+                    render_process_usage_chart(frame, rect, theme, pid, history)
+                }
+                None if app.show_event_log => render_event_log(
+                    frame,
+                    rect,
+                    theme,
+                    app.data.events(),
+                    &app.event_export_status,
+                ),
+                None => render_temperature_chart(
+                    frame,
+                    rect,
+                    theme,
+                    gpu,
+                    app.graphs.temperature_levels(),
+                ),
+            },
+            TuiPanel::Events => render_event_log(
+                frame,
+                rect,
+                theme,
+                app.data.events(),
+                &app.event_export_status,
+            ),
+            TuiPanel::Gpus => render_gpu_list(
+                frame,
+                rect,
+                theme,
+                app.data.gpus(),
+                &alerting,
+                app.selected_gpu,
+                &mut app.gpu_picker,
+            ),
+            TuiPanel::Info => render_device_info(
+                frame,
+                rect,
+                theme,
+                gpu,
+                &app.info_copy_status,
+                &mut app.info_scroll,
+            ),
+        }
+    }
+}
+
+/// Copies text to the clipboard with an OSC 52 escape sequence, which the terminal passes
+/// on to the system clipboard. This also works over SSH, but not every terminal supports it.
+fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64_encode(text.as_bytes()))?;
+    stdout.flush()
 }
 
 fn run_app<B: Backend>(
@@ -136,12 +700,12 @@ fn run_app<B: Backend>(
             .checked_sub(last_tick.elapsed())
             .unwrap_or_else(|| Duration::from_secs(0));
         if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                if key.modifiers & KeyModifiers::CONTROL != KeyModifiers::empty() {
-                    if key.code == KeyCode::Char('c') {
-                        return Ok(());
-                    }
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press && app.handle_key(key) => {
+                    return Ok(());
                 }
+                Event::Mouse(mouse) => app.handle_mouse(mouse),
+                _ => {}
             }
         }
         if last_tick.elapsed() >= tick_rate {
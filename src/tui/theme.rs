@@ -0,0 +1,91 @@
+use std::env;
+
+use ratatui::style::Color;
+
+use crate::theme::{Palette, Rgb};
+
+/// The colours of a palette as the terminal can show them.
+pub struct TuiTheme {
+    pub primary: Color,
+    pub accent: Color,
+    pub warning: Color,
+    pub error: Color,
+    pub muted: Color,
+}
+
+impl TuiTheme {
+    pub fn new(palette: &Palette) -> Self {
+        // Terminals without 24-bit colour get the closest of the standard 256
+        let truecolor =
+            env::var("COLORTERM").is_ok_and(|value| value == "truecolor" || value == "24bit");
+        let color = |rgb: Rgb| {
+            if truecolor {
+                Color::Rgb(rgb.0, rgb.1, rgb.2)
+            } else {
+                Color::Indexed(nearest_256(rgb))
+            }
+        };
+
+        Self {
+            primary: color(palette.primary),
+            accent: color(palette.accent),
+            warning: color(palette.warning),
+            error: color(palette.error),
+            muted: color(palette.muted),
+        }
+    }
+}
+
+/// The index of the closest colour in the 6x6x6 cube or grey ramp of the 256-colour palette.
+fn nearest_256(Rgb(r, g, b): Rgb) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |value: u8| {
+        (0..LEVELS.len())
+            .min_by_key(|&i| LEVELS[i].abs_diff(value))
+            .unwrap() as u8
+    };
+    let distance = |(r2, g2, b2): (u8, u8, u8)| {
+        [(r, r2), (g, g2), (b, b2)]
+            .into_iter()
+            .map(|(a, b)| (a as i32 - b as i32).pow(2))
+            .sum::<i32>()
+    };
+
+    let (ri, gi, bi) = (level(r), level(g), level(b));
+    let cube = (
+        LEVELS[ri as usize],
+        LEVELS[gi as usize],
+        LEVELS[bi as usize],
+    );
+
+    // The grey ramp goes from 8 to 238 in steps of 10
+    let average = (r as u16 + g as u16 + b as u16) / 3;
+    let grey_index = (average.saturating_sub(3) / 10).min(23) as u8;
+    let grey = 8 + grey_index * 10;
+
+    if distance((grey, grey, grey)) < distance(cube) {
+        232 + grey_index
+    } else {
+        16 + 36 * ri + 6 * gi + bi
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_the_nearest_of_the_256_colours() {
+        // The corners of the colour cube
+        assert_eq!(nearest_256(Rgb(0, 0, 0)), 16);
+        assert_eq!(nearest_256(Rgb(255, 255, 255)), 231);
+        assert_eq!(nearest_256(Rgb(255, 0, 0)), 196);
+        // Colours in the cube map exactly, and others to their nearest level
+        assert_eq!(nearest_256(Rgb(95, 135, 175)), 67);
+        assert_eq!(nearest_256(Rgb(230, 159, 0)), 178);
+        // Greys between the cube's levels are closer to the grey ramp
+        assert_eq!(nearest_256(Rgb(128, 128, 128)), 244);
+        assert_eq!(nearest_256(Rgb(238, 238, 238)), 255);
+        assert_eq!(nearest_256(Rgb(8, 8, 8)), 232);
+    }
+}
@@ -1,19 +1,36 @@
-use nvml_wrapper::{enums::device::UsedGpuMemory, error::NvmlError};
+use nvml_wrapper::enums::device::UsedGpuMemory;
 use ratatui::{
-    layout::{Alignment, Constraint, Rect},
+    layout::{Alignment, Constraint, Flex, Layout, Margin, Rect},
     style::{Color, Modifier, Style, Stylize},
     symbols,
-    text::Text,
+    text::{Line, Span, Text},
     widgets::{
-        block::Title, Axis, Block, Borders, Cell, Chart, Dataset, GraphType, HighlightSpacing, Row,
-        Table, TableState,
+        block::Title, Axis, Block, Borders, Cell, Chart, Dataset, GraphType, HighlightSpacing,
+        List, ListItem, ListState, Paragraph, Row, Table, TableState,
     },
     Frame,
 };
 
-use crate::{data::GpuDeviceMonitor, processes::ProcessData, utils::bytes_to_mib_gib};
+use crate::{
+    data::{
+        alerts::AlertEngine,
+        cluster::{ClusterColumn, ClusterRow, ClusterTableData, HostHealth},
+        events::EventLog,
+        graph::GraphViewerData,
+        levels::{GraphLevels, Level},
+        process_filter::ProcessFilter,
+        process_history::ProcessHistory,
+        process_table::{ProcessTableData, SortingDirection, TableColumn, TableSorting},
+        throttle::ThrottleReason,
+        GpuDeviceMonitor,
+    },
+    remote::client::{ConnectionStatus, RemoteConnection},
+    utils::{bytes_to_mib_gib, format_duration, format_elapsed},
+};
 
-pub fn render_usage_chart(f: &mut Frame, area: Rect, gpu: &mut GpuDeviceMonitor) {
+use super::theme::TuiTheme;
+
+pub fn render_usage_chart(f: &mut Frame, area: Rect, theme: &TuiTheme, gpu: &mut GpuDeviceMonitor) {
     let data = gpu.usage_graph_mut();
 
     // Padding of the graph border/axis
@@ -21,7 +38,7 @@ pub fn render_usage_chart(f: &mut Frame, area: Rect, gpu: &mut GpuDeviceMonitor)
     let right_padding = 1;
 
     let mut points = Vec::new();
-    let length = (area.width - left_padding - right_padding) as usize * 2;
+    let length = area.width.saturating_sub(left_padding + right_padding) as usize * 2;
     for i in 0..length {
         let value = data.get_value_at(i);
         if let Some(value) = value {
@@ -33,7 +50,7 @@ pub fn render_usage_chart(f: &mut Frame, area: Rect, gpu: &mut GpuDeviceMonitor)
 
     let datasets = vec![Dataset::default()
         .marker(symbols::Marker::Braille)
-        .style(Style::default().fg(Color::Green))
+        .style(Style::default().fg(theme.primary))
         .graph_type(GraphType::Line)
         .data(&points)];
 
@@ -42,7 +59,11 @@ pub fn render_usage_chart(f: &mut Frame, area: Rect, gpu: &mut GpuDeviceMonitor)
             Block::default()
                 .title(
                     Title::default()
-                        .content("GPU Usage".cyan().bold())
+                        .content(
+                            format!("GPU Usage: {}", gpu.label())
+                                .fg(theme.accent)
+                                .bold(),
+                        )
                         .alignment(Alignment::Center),
                 )
                 .borders(Borders::ALL),
@@ -63,7 +84,119 @@ pub fn render_usage_chart(f: &mut Frame, area: Rect, gpu: &mut GpuDeviceMonitor)
     f.render_widget(chart, area)
 }
 
-pub fn render_memory_chart(f: &mut Frame, area: Rect, gpu: &mut GpuDeviceMonitor) {
+/// The height of the throttle strip under the usage chart: a line for each throttle reason
+/// seen in the chart's history, or nothing if the GPU reports neither throttle reasons nor
+/// XID errors, or the chart would be left too short.
+pub fn throttle_strip_height(gpu: &GpuDeviceMonitor, area: Rect) -> u16 {
+    let sample_count = area.width.saturating_sub(2) as usize * 2;
+    let history = gpu.throttle_history();
+    if !history.is_known(sample_count) && gpu.health_events().is_empty() {
+        return 0;
+    }
+
+    let height = 2 + history.reasons_seen(sample_count).len() as u16;
+    if area.height < height + 8 {
+        return 0;
+    }
+    height
+}
+
+/// Renders a lane for each reason the GPU has been throttled for, lined up with the usage
+/// chart above with two samples to a cell, and marks XID errors across them. The title says
+/// whether and why the GPU is throttled now.
+pub fn render_throttle_strip(f: &mut Frame, area: Rect, theme: &TuiTheme, gpu: &GpuDeviceMonitor) {
+    let width = area.width.saturating_sub(2) as usize;
+    let history = gpu.throttle_history();
+
+    // The sample index of each XID error, going by when the usage samples were taken
+    let usage_graph = gpu.usage_graph();
+    let error_indices = gpu
+        .health_events()
+        .iter()
+        .filter_map(|event| {
+            (0..width * 2).find(|&index| {
+                usage_graph
+                    .get_sample_at(index)
+                    .is_some_and(|(time, _)| time <= event.time)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let lines = history
+        .reasons_seen(width * 2)
+        .into_iter()
+        .map(|reason| {
+            let color = match reason {
+                ThrottleReason::HwThermal
+                | ThrottleReason::HwSlowdown
+                | ThrottleReason::HwPowerBrake => theme.error,
+                ThrottleReason::SwThermal => theme.warning,
+                _ => theme.primary,
+            };
+            let label = format!("{} ", reason.label());
+
+            // The oldest samples are at the left, under the label
+            let spans = (0..width)
+                .map(|column| {
+                    let newest = (width - 1 - column) * 2;
+                    if let Some(c) = label.chars().nth(column) {
+                        return Span::from(c.to_string()).gray();
+                    }
+                    let throttled = [newest, newest + 1].iter().any(|&index| {
+                        history
+                            .get_at(index)
+                            .is_some_and(|reasons| reasons.contains(reason))
+                    });
+                    let error = error_indices.iter().any(|&index| index / 2 == newest / 2);
+                    match (throttled, error) {
+                        (true, true) => Span::from("█").fg(theme.error),
+                        (true, false) => Span::from("█").fg(color),
+                        (false, true) => Span::from("╎").fg(theme.error),
+                        (false, false) => Span::from(" "),
+                    }
+                })
+                .collect::<Vec<_>>();
+            Line::from(spans)
+        })
+        .collect::<Vec<_>>();
+
+    let mut title = Vec::new();
+    if let Some(current) = history.current() {
+        title.push(if current.is_empty() {
+            Span::from(current.describe()).gray()
+        } else {
+            Span::from(format!("⚠ {}", current.describe()))
+                .fg(theme.warning)
+                .bold()
+        });
+    }
+    if let Some(event) = gpu.health_events().back() {
+        let elapsed = event.time.elapsed().unwrap_or_default();
+        if !title.is_empty() {
+            title.push(Span::from(" · "));
+        }
+        title.push(Span::from(format!("{}, {}", event, format_elapsed(elapsed))).fg(theme.error));
+    }
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .title(
+                Title::default()
+                    .content(Line::from(title))
+                    .alignment(Alignment::Center),
+            )
+            .borders(Borders::ALL),
+    );
+    f.render_widget(paragraph, area)
+}
+
+pub fn render_memory_chart(
+    f: &mut Frame,
+    area: Rect,
+    theme: &TuiTheme,
+    gpu: &mut GpuDeviceMonitor,
+    levels: Option<GraphLevels>,
+) {
     let data = gpu.memory_graph_mut();
 
     // Padding of the graph border/axis
@@ -71,7 +204,7 @@ pub fn render_memory_chart(f: &mut Frame, area: Rect, gpu: &mut GpuDeviceMonitor
     let right_padding = 1;
 
     let mut points = Vec::new();
-    let length = (area.width - left_padding - right_padding) as usize * 2;
+    let length = area.width.saturating_sub(left_padding + right_padding) as usize * 2;
     for i in 0..length {
         let value = data.get_value_at(i);
         if let Some(value) = value {
@@ -83,18 +216,15 @@ pub fn render_memory_chart(f: &mut Frame, area: Rect, gpu: &mut GpuDeviceMonitor
 
     let max_memory = gpu.max_memory();
 
-    let datasets = vec![Dataset::default()
-        .marker(symbols::Marker::Braille)
-        .style(Style::default().fg(Color::Green))
-        .graph_type(GraphType::Line)
-        .data(&points)];
+    let runs = level_runs(&points, levels);
+    let datasets = level_datasets(&runs, theme, levels);
 
     let chart = Chart::new(datasets)
         .block(
             Block::default()
                 .title(
                     Title::default()
-                        .content("Memory Usage".cyan().bold())
+                        .content("Memory Usage".fg(theme.accent).bold())
                         .alignment(Alignment::Center),
                 )
                 .borders(Borders::ALL),
@@ -111,15 +241,21 @@ pub fn render_memory_chart(f: &mut Frame, area: Rect, gpu: &mut GpuDeviceMonitor
                 .bounds([0.0, max_memory as f64])
                 .labels(vec![
                     "0".bold(),
-                    format!("{}", bytes_to_mib_gib((max_memory / 2) as f32)).into(),
-                    format!("{}", bytes_to_mib_gib(max_memory as f32)).bold(),
+                    bytes_to_mib_gib((max_memory / 2) as f32).into(),
+                    bytes_to_mib_gib(max_memory as f32).bold(),
                 ]),
         );
 
     f.render_widget(chart, area)
 }
 
-pub fn render_temperature_chart(f: &mut Frame, area: Rect, gpu: &mut GpuDeviceMonitor) {
+pub fn render_temperature_chart(
+    f: &mut Frame,
+    area: Rect,
+    theme: &TuiTheme,
+    gpu: &mut GpuDeviceMonitor,
+    levels: Option<GraphLevels>,
+) {
     let data = gpu.temperature_graph_mut();
 
     // Padding of the graph border/axis
@@ -127,7 +263,7 @@ pub fn render_temperature_chart(f: &mut Frame, area: Rect, gpu: &mut GpuDeviceMo
     let right_padding = 1;
 
     let mut points = Vec::new();
-    let length = (area.width - left_padding - right_padding) as usize * 2;
+    let length = area.width.saturating_sub(left_padding + right_padding) as usize * 2;
     for i in 0..length {
         let value = data.get_value_at(i);
         if let Some(value) = value {
@@ -137,18 +273,15 @@ pub fn render_temperature_chart(f: &mut Frame, area: Rect, gpu: &mut GpuDeviceMo
 
     let last_temp = gpu.temperature_graph_mut().get_value_at(0).unwrap_or(0.0);
 
-    let datasets = vec![Dataset::default()
-        .marker(symbols::Marker::Braille)
-        .style(Style::default().fg(Color::Green))
-        .graph_type(GraphType::Line)
-        .data(&points)];
+    let runs = level_runs(&points, levels);
+    let datasets = level_datasets(&runs, theme, levels);
 
     let chart = Chart::new(datasets)
         .block(
             Block::default()
                 .title(
                     Title::default()
-                        .content("Temperature".cyan().bold())
+                        .content("Temperature".fg(theme.accent).bold())
                         .alignment(Alignment::Center),
                 )
                 .borders(Borders::ALL),
@@ -169,85 +302,882 @@ pub fn render_temperature_chart(f: &mut Frame, area: Rect, gpu: &mut GpuDeviceMo
     f.render_widget(chart, area)
 }
 
+/// Splits a chart's points where their level changes, so that each run can be drawn in its
+/// own colour. The point either side of a change is in both runs to keep the line joined,
+/// and the segment between them takes the higher level.
+fn level_runs(points: &[(f64, f64)], levels: Option<GraphLevels>) -> Vec<(Level, Vec<(f64, f64)>)> {
+    let mut runs: Vec<(Level, Vec<(f64, f64)>)> = Vec::new();
+    for &point in points {
+        let level = levels
+            .map(|levels| levels.thresholds.level(point.1 as f32))
+            .unwrap_or(Level::Normal);
+
+        match runs.last_mut() {
+            Some((run_level, run)) if *run_level == level => run.push(point),
+            Some((run_level, run)) => {
+                let last = *run.last().unwrap();
+                if level > *run_level {
+                    runs.push((level, vec![last, point]));
+                } else {
+                    run.push(point);
+                    runs.push((level, vec![point]));
+                }
+            }
+            None => runs.push((level, vec![point])),
+        }
+    }
+    runs
+}
+
+/// A line for each run, coloured by level. With patterns, warning and critical runs are also
+/// drawn with dots and blocks rather than braille.
+fn level_datasets<'a>(
+    runs: &'a [(Level, Vec<(f64, f64)>)],
+    theme: &TuiTheme,
+    levels: Option<GraphLevels>,
+) -> Vec<Dataset<'a>> {
+    let patterns = levels.is_some_and(|levels| levels.patterns);
+
+    runs.iter()
+        .map(|(level, points)| {
+            let (color, marker) = match level {
+                Level::Normal => (theme.primary, symbols::Marker::Braille),
+                Level::Warning => (theme.warning, symbols::Marker::Dot),
+                Level::Critical => (theme.error, symbols::Marker::HalfBlock),
+            };
+            let marker = if patterns {
+                marker
+            } else {
+                symbols::Marker::Braille
+            };
+
+            Dataset::default()
+                .marker(marker)
+                .style(Style::default().fg(color))
+                .graph_type(GraphType::Line)
+                .data(points)
+        })
+        .collect()
+}
+
+const SPARKLINE_WIDTH: usize = 20;
+
+/// The process table's columns, and the ones that can be sorted by.
+const PROCESS_COLUMNS: [(&str, Option<TableColumn>, Constraint); 6] = [
+    ("PID", Some(TableColumn::Pid), Constraint::Length(10)),
+    ("Process", Some(TableColumn::Name), Constraint::Min(20)),
+    ("Type", Some(TableColumn::Kind), Constraint::Length(8)),
+    (
+        "Memory",
+        Some(TableColumn::GpuMemory),
+        Constraint::Length(12),
+    ),
+    ("Usage", Some(TableColumn::GpuUsage), Constraint::Length(13)),
+    ("History", None, Constraint::Length(SPARKLINE_WIDTH as u16)),
+];
+
+/// The bar left of the selected process, which is always given room.
+const SELECTION_BAR: &str = " █ ";
+const SELECTION_BAR_WIDTH: u16 = 3;
+
+/// Where a click landed in a table.
+pub enum TableHit {
+    /// The index of the column whose header was clicked.
+    Header(usize),
+    /// The index of the clicked row, counting rows scrolled out of view.
+    Row(usize),
+}
+
+/// Finds what's at a position in a bordered table with a one-line header, laying out its
+/// columns the same way the table does.
+fn table_hit(
+    area: Rect,
+    widths: &[Constraint],
+    selection_width: u16,
+    offset: usize,
+    column: u16,
+    row: u16,
+) -> Option<TableHit> {
+    let inner = area.inner(&Margin::new(1, 1));
+    if !contains(inner, column, row) {
+        return None;
+    }
+
+    if row > inner.y {
+        return Some(TableHit::Row(offset + (row - inner.y - 1) as usize));
+    }
+
+    let [_, columns_area] =
+        Layout::horizontal([Constraint::Length(selection_width), Constraint::Fill(0)])
+            .areas(Rect::new(inner.x, inner.y, inner.width, 1));
+    Layout::horizontal(widths.iter().copied())
+        .flex(Flex::Start)
+        .spacing(1)
+        .split(columns_area)
+        .iter()
+        .position(|rect| contains(*rect, column, row))
+        .map(TableHit::Header)
+}
+
+fn contains(rect: Rect, column: u16, row: u16) -> bool {
+    (rect.left()..rect.right()).contains(&column) && (rect.top()..rect.bottom()).contains(&row)
+}
+
+/// Adds an arrow to the header of the column a table is sorted by.
+fn sorted_header<C: Copy + PartialEq>(
+    label: &str,
+    column: C,
+    sorting: &TableSorting<C>,
+) -> Cell<'static> {
+    if sorting.column != column {
+        return Cell::from(label.to_string());
+    }
+    let arrow = match sorting.direction {
+        SortingDirection::Ascending => "▲",
+        SortingDirection::Descending => "▼",
+    };
+    Cell::from(format!("{} {}", label, arrow))
+}
+
+#[derive(Default)]
 pub struct ProcessTableState {
     inner_state: TableState,
+    /// Where the table was last rendered, to find what's clicked.
+    area: Rect,
 }
 
-impl Default for ProcessTableState {
-    fn default() -> Self {
-        Self {
-            inner_state: TableState::default(),
-        }
+impl ProcessTableState {
+    pub fn hit(&self, column: u16, row: u16) -> Option<TableHit> {
+        let widths = PROCESS_COLUMNS.map(|(_, _, width)| width);
+        table_hit(
+            self.area,
+            &widths,
+            SELECTION_BAR_WIDTH,
+            self.inner_state.offset(),
+            column,
+            row,
+        )
+    }
+
+    /// The column that the header at `index` sorts by, if it sorts at all.
+    pub fn sort_column(index: usize) -> Option<TableColumn> {
+        PROCESS_COLUMNS
+            .get(index)
+            .and_then(|(_, column, _)| *column)
+    }
+}
+
+fn process_table_title(theme: &TuiTheme, filter: &ProcessFilter, searching: bool) -> Line<'static> {
+    let mut spans = vec![Span::from("Processes").fg(theme.accent).bold()];
+
+    if searching || !filter.query().is_empty() {
+        let cursor = if searching { "_" } else { "" };
+        spans.push(format!(" /{}{}", filter.query(), cursor).fg(theme.warning));
     }
+    if filter.use_regex() {
+        spans.push(" [regex]".gray());
+    }
+    if filter.only_current_user {
+        spans.push(" [mine]".gray());
+    }
+    if let Some(min_memory_mib) = filter.min_memory_mib {
+        spans.push(format!(" [≥{} MiB]", min_memory_mib).gray());
+    }
+    if filter.regex_error().is_some() {
+        spans.push(" invalid regex".fg(theme.error));
+    }
+
+    Line::from(spans)
+}
+
+/// Renders the most recent values of a graph as a row of block characters.
+fn sparkline_text(data: &GraphViewerData, max_value: f32, width: usize) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max_value = max_value.max(1.0);
+
+    (0..width)
+        .rev()
+        .map(|i| match data.get_value_at(i) {
+            Some(value) => {
+                let level = (value / max_value).clamp(0.0, 1.0) * (BARS.len() - 1) as f32;
+                BARS[level.round() as usize]
+            }
+            None => ' ',
+        })
+        .collect()
 }
 
 pub fn render_process_table(
     f: &mut Frame,
     area: Rect,
-    processes: Result<Vec<&ProcessData>, &NvmlError>,
+    theme: &TuiTheme,
+    table: &ProcessTableData,
+    searching: bool,
     state: &mut ProcessTableState,
 ) {
-    state.inner_state.select(Some(1));
+    let processes = table.processes();
+    let filter = table.filter();
 
-    let header_style = Style::default().fg(Color::Cyan).bold();
+    let selected_index = processes.as_ref().ok().and_then(|processes| {
+        processes
+            .iter()
+            .position(|process| Some(process.info.pid) == table.selected_pid())
+    });
+    state.inner_state.select(selected_index);
+
+    let header_style = Style::default().fg(theme.accent).bold();
     let selected_style = Style::default()
         .add_modifier(Modifier::REVERSED)
-        .fg(Color::LightGreen);
+        .fg(theme.primary);
 
-    let header = ["PID", "Process", "Memory"]
+    let header = PROCESS_COLUMNS
         .into_iter()
-        .map(Cell::from)
+        .map(|(label, column, _)| match column {
+            Some(column) => sorted_header(label, column, table.sorting()),
+            None => Cell::from(label),
+        })
         .collect::<Row>()
         .style(header_style);
 
     let rows = if let Ok(processes) = processes {
         processes
             .iter()
-            .enumerate()
-            .map(|(i, data)| {
+            .map(|data| {
                 let memory_str = if let UsedGpuMemory::Used(memory) = data.info.used_gpu_memory {
                     bytes_to_mib_gib(memory as f32)
                 } else {
                     "Unknown".to_string()
                 };
 
+                let usage = data.gpu_usage_label();
+                let usage_cell = match table.flagged_idle(data) {
+                    Some(idle_for) => Cell::from(Text::from(format!(
+                        "{} idle {}",
+                        usage,
+                        format_duration(idle_for)
+                    )))
+                    .style(Style::new().fg(theme.warning)),
+                    None => Cell::from(Text::from(usage)),
+                };
+
+                let sparkline = table
+                    .history(data.info.pid)
+                    .map(|history| {
+                        let memory_graph = history.memory_graph();
+                        let max_memory = memory_graph.max_value().unwrap_or(0.0);
+                        sparkline_text(memory_graph, max_memory, SPARKLINE_WIDTH)
+                    })
+                    .unwrap_or_default();
+
                 [
                     Cell::from(Text::from(format!("{}", data.info.pid))),
-                    Cell::from(Text::from(format!("{}", data.name))),
-                    Cell::from(Text::from(format!("{}", memory_str))),
+                    Cell::from(Text::from(data.name.clone())),
+                    Cell::from(Text::from(data.kind.label())),
+                    Cell::from(Text::from(memory_str)),
+                    usage_cell,
+                    Cell::from(Text::from(sparkline)),
                 ]
                 .into_iter()
                 .collect::<Row>()
-                .style(Style::new().fg(Color::Green))
+                .style(Style::new().fg(theme.primary))
             })
             .collect::<Vec<_>>()
     } else {
         let row = vec![Cell::from("Error fetching processes")]
             .into_iter()
             .collect::<Row>()
-            .style(Style::new().fg(Color::Red).bg(Color::Black));
+            .style(Style::new().fg(theme.error));
 
         vec![row]
     };
 
-    let bar = " █ ";
-    let t = Table::new(
-        rows,
-        [
-            Constraint::Length(10),
-            Constraint::Min(40),
-            Constraint::Min(30),
-        ],
-    )
-    .header(header)
-    .highlight_style(selected_style)
-    .highlight_symbol(Text::from(vec![
-        "".into(),
-        bar.into(),
-        bar.into(),
-        "".into(),
-    ]))
-    .highlight_spacing(HighlightSpacing::Always);
+    let bar = SELECTION_BAR;
+    let t = Table::new(rows, PROCESS_COLUMNS.map(|(_, _, width)| width))
+        .header(header)
+        .block(
+            Block::default()
+                .title(Title::default().content(process_table_title(theme, filter, searching)))
+                .borders(Borders::ALL),
+        )
+        .highlight_style(selected_style)
+        .highlight_symbol(Text::from(vec![
+            "".into(),
+            bar.into(),
+            bar.into(),
+            "".into(),
+        ]))
+        .highlight_spacing(HighlightSpacing::Always);
 
     f.render_stateful_widget(t, area, &mut state.inner_state);
+    state.area = area;
+}
+
+fn render_history_chart(
+    f: &mut Frame,
+    area: Rect,
+    theme: &TuiTheme,
+    title: String,
+    data: &GraphViewerData,
+    max_value: f32,
+    value_to_string: impl Fn(f32) -> String,
+) {
+    // Padding of the graph border/axis
+    let left_padding = 10;
+    let right_padding = 1;
+
+    let mut points = Vec::new();
+    let length = area.width.saturating_sub(left_padding + right_padding) as usize * 2;
+    for i in 0..length {
+        if let Some(value) = data.get_value_at(i) {
+            points.push(((length - i - 1) as f64, value as f64));
+        }
+    }
+
+    let last_value = data.get_value_at(0);
+
+    let datasets = vec![Dataset::default()
+        .marker(symbols::Marker::Braille)
+        .style(Style::default().fg(theme.primary))
+        .graph_type(GraphType::Line)
+        .data(&points)];
+
+    let current = match last_value {
+        Some(value) => value_to_string(value),
+        None => "exited".to_string(),
+    };
+
+    let chart = Chart::new(datasets)
+        .block(
+            Block::default()
+                .title(
+                    Title::default()
+                        .content(title.fg(theme.accent).bold())
+                        .alignment(Alignment::Center),
+                )
+                .borders(Borders::ALL),
+        )
+        .x_axis(
+            Axis::default()
+                .style(Style::default().gray())
+                .bounds([0.0, length as f64]),
+        )
+        .y_axis(
+            Axis::default()
+                .title(current.bold())
+                .style(Style::default().gray())
+                .bounds([0.0, max_value as f64])
+                .labels(vec![
+                    "0".bold(),
+                    value_to_string(max_value / 2.0).into(),
+                    value_to_string(max_value).bold(),
+                ]),
+        );
+
+    f.render_widget(chart, area)
+}
+
+pub fn render_process_memory_chart(
+    f: &mut Frame,
+    area: Rect,
+    theme: &TuiTheme,
+    pid: u32,
+    history: &ProcessHistory,
+    max_memory: u64,
+) {
+    render_history_chart(
+        f,
+        area,
+        theme,
+        format!("{} ({}) Memory", history.name(), pid),
+        history.memory_graph(),
+        max_memory as f32,
+        bytes_to_mib_gib,
+    );
+}
+
+pub fn render_process_usage_chart(
+    f: &mut Frame,
+    area: Rect,
+    theme: &TuiTheme,
+    pid: u32,
+    history: &ProcessHistory,
+) {
+    render_history_chart(
+        f,
+        area,
+        theme,
+        format!("{} ({}) GPU Usage", history.name(), pid),
+        history.usage_graph(),
+        100.0,
+        |v| format!("{:.0}%", v),
+    );
+}
+
+pub fn render_event_log(
+    f: &mut Frame,
+    area: Rect,
+    theme: &TuiTheme,
+    events: &EventLog,
+    status: &str,
+) {
+    // Only the newest events that fit are shown
+    let visible = area.height.saturating_sub(2) as usize;
+    let items = events
+        .events()
+        .rev()
+        .take(visible)
+        .map(|event| {
+            let elapsed = event.time.elapsed().unwrap_or_default();
+            Line::from(vec![
+                format!("{:>8} ", format_elapsed(elapsed)).gray(),
+                Span::from(event.to_string()).fg(theme.primary),
+            ])
+        })
+        .map(ListItem::new)
+        .collect::<Vec<_>>();
+
+    let mut title = vec![Span::from("Events").fg(theme.accent).bold()];
+    if !status.is_empty() {
+        title.push(format!(" {}", status).gray());
+    }
+
+    let list = List::new(items).block(
+        Block::default()
+            .title(
+                Title::default()
+                    .content(Line::from(title))
+                    .alignment(Alignment::Center),
+            )
+            .borders(Borders::ALL),
+    );
+
+    f.render_widget(list, area)
+}
+
+pub fn render_alert_bar(f: &mut Frame, area: Rect, theme: &TuiTheme, alerts: &AlertEngine) {
+    let mut firing = alerts.firing().collect::<Vec<_>>();
+    firing.sort_by_key(|alert| (alert.device_index, alert.since));
+
+    let lines = firing
+        .into_iter()
+        .take(area.height as usize)
+        .map(|alert| {
+            Line::from(format!(
+                " ⚠ GPU {} {}: {} ({}, {})",
+                alert.device_index,
+                alert.rule,
+                alert.message,
+                alert.device_name,
+                format_elapsed(alert.since.elapsed())
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let paragraph =
+        Paragraph::new(lines).style(Style::new().fg(Color::White).bg(theme.error).bold());
+    f.render_widget(paragraph, area)
+}
+
+/// Where the GPU list and tabs were last rendered, to find which GPU is clicked.
+#[derive(Default)]
+pub struct GpuPickerState {
+    list_state: ListState,
+    list_area: Rect,
+    tabs: Vec<Rect>,
+}
+
+impl GpuPickerState {
+    pub fn gpu_at(&self, column: u16, row: u16) -> Option<usize> {
+        if let Some(index) = self.tabs.iter().position(|tab| contains(*tab, column, row)) {
+            return Some(index);
+        }
+
+        let inner = self.list_area.inner(&Margin::new(1, 1));
+        contains(inner, column, row).then(|| self.list_state.offset() + (row - inner.y) as usize)
+    }
+
+    /// Forgets where the list and tabs were, for frames that don't show them.
+    pub fn clear(&mut self) {
+        self.list_area = Rect::default();
+        self.tabs.clear();
+    }
+}
+
+/// Renders a tab for every GPU on one line, highlighting the selected one.
+pub fn render_gpu_tabs(
+    f: &mut Frame,
+    area: Rect,
+    theme: &TuiTheme,
+    gpus: &[GpuDeviceMonitor],
+    alerting: &[bool],
+    selected: usize,
+    state: &mut GpuPickerState,
+) {
+    let mut spans = Vec::new();
+    let mut x = area.x;
+    for (index, (gpu, alerting)) in gpus.iter().zip(alerting).enumerate() {
+        if index > 0 {
+            spans.push(Span::from("│").gray());
+            x += 1;
+        }
+
+        let marker = if *alerting { "⚠ " } else { "" };
+        let label = format!(" {}{} ", marker, gpu.label());
+        let width = Span::from(label.as_str()).width() as u16;
+        state
+            .tabs
+            .push(Rect::new(x, area.y, width, 1).intersection(area));
+        x = x.saturating_add(width);
+
+        let style = if index == selected {
+            Style::default()
+                .add_modifier(Modifier::REVERSED)
+                .fg(theme.primary)
+        } else if *alerting {
+            Style::default().fg(theme.error)
+        } else {
+            Style::default()
+        };
+        spans.push(Span::styled(label, style));
+    }
+
+    f.render_widget(Paragraph::new(Line::from(spans)), area)
+}
+
+/// Renders a line for every GPU with its latest readings, highlighting the selected one.
+pub fn render_gpu_list(
+    f: &mut Frame,
+    area: Rect,
+    theme: &TuiTheme,
+    gpus: &[GpuDeviceMonitor],
+    alerting: &[bool],
+    selected: usize,
+    state: &mut GpuPickerState,
+) {
+    let items = gpus
+        .iter()
+        .zip(alerting)
+        .map(|(gpu, alerting)| {
+            let sample = gpu.last_sample();
+            let utilization = sample
+                .and_then(|sample| sample.utilization)
+                .map(|utilization| format!("{:>3.0}%", utilization))
+                .unwrap_or_else(|| "   -".to_string());
+            let memory = sample
+                .and_then(|sample| sample.memory_used)
+                .map(|used| bytes_to_mib_gib(used as f32))
+                .unwrap_or_else(|| "-".to_string());
+            let temperature = sample
+                .and_then(|sample| sample.temperature)
+                .map(|temperature| format!("{:.0}°C", temperature))
+                .unwrap_or_else(|| "-".to_string());
+
+            let marker = if *alerting { "⚠ " } else { "  " };
+            Line::from(vec![
+                Span::from(marker).fg(theme.error),
+                Span::from(gpu.label()).bold(),
+                Span::from(format!(" {} {} {}", utilization, memory, temperature)),
+            ])
+        })
+        .map(ListItem::new)
+        .collect::<Vec<_>>();
+
+    state.list_state.select(Some(selected));
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(
+                    Title::default()
+                        .content("GPUs (g: next)".fg(theme.accent).bold())
+                        .alignment(Alignment::Center),
+                )
+                .borders(Borders::ALL),
+        )
+        .highlight_style(
+            Style::default()
+                .add_modifier(Modifier::REVERSED)
+                .fg(theme.primary),
+        );
+
+    f.render_stateful_widget(list, area, &mut state.list_state);
+    state.list_area = area;
+}
+
+/// Renders the selected GPU's driver, firmware and limits, scrolled down by `scroll` lines if
+/// they don't all fit.
+pub fn render_device_info(
+    f: &mut Frame,
+    area: Rect,
+    theme: &TuiTheme,
+    gpu: &GpuDeviceMonitor,
+    status: &str,
+    scroll: &mut u16,
+) {
+    let lines = match gpu.info() {
+        Some(info) => {
+            let ecc_rows = gpu.ecc().map(|ecc| ecc.rows()).unwrap_or_default();
+            let graphs = gpu.ecc_history().graphs();
+            let width = info
+                .rows()
+                .iter()
+                .chain(&ecc_rows)
+                .map(|(label, _)| label.len())
+                .chain(graphs.iter().map(|(name, _)| name.len()))
+                .max()
+                .unwrap_or(0);
+            let row_line = |(label, value): (&str, String)| {
+                Line::from(vec![
+                    format!("{:width$} ", label, width = width).gray(),
+                    Span::from(value).fg(theme.primary),
+                ])
+            };
+
+            let mut lines = info.rows().into_iter().map(row_line).collect::<Vec<_>>();
+            if !ecc_rows.is_empty() {
+                lines.push(Line::default());
+                lines.push(Line::from("Memory errors".bold()));
+                if let Some(increase) = gpu.ecc_increase() {
+                    lines.push(Line::from(
+                        Span::from(format!(
+                            "{}, {}",
+                            increase.description,
+                            format_elapsed(increase.time.elapsed())
+                        ))
+                        .fg(theme.error),
+                    ));
+                }
+                lines.extend(ecc_rows.into_iter().map(row_line));
+
+                // Graphs of the counts, as wide as the panel has room for
+                let sparkline_width = (area.width as usize).saturating_sub(width + 3);
+                for (name, graph) in graphs {
+                    let max_value = graph.max_value().unwrap_or(0.0);
+                    lines.push(Line::from(vec![
+                        format!("{:width$} ", name, width = width).gray(),
+                        Span::from(sparkline_text(graph, max_value, sparkline_width))
+                            .fg(theme.primary),
+                    ]));
+                }
+            }
+            lines
+        }
+        None => vec![Line::from(
+            "The agent this GPU is on doesn't report device info".gray(),
+        )],
+    };
+
+    let visible = area.height.saturating_sub(2);
+    *scroll = (*scroll).min((lines.len() as u16).saturating_sub(visible));
+
+    let mut title = vec![Span::from("Device info (y: copy)").fg(theme.accent).bold()];
+    if !status.is_empty() {
+        title.push(format!(" {}", status).gray());
+    }
+
+    let paragraph = Paragraph::new(lines).scroll((*scroll, 0)).block(
+        Block::default()
+            .title(
+                Title::default()
+                    .content(Line::from(title))
+                    .alignment(Alignment::Center),
+            )
+            .borders(Borders::ALL),
+    );
+    f.render_widget(paragraph, area)
+}
+
+/// Renders the value under the last click on a chart.
+pub fn render_status_line(f: &mut Frame, area: Rect, theme: &TuiTheme, status: &str) {
+    let line = Line::from(vec![
+        Span::from(format!(" {}", status)).fg(theme.accent),
+        " (click elsewhere to hide)".gray(),
+    ]);
+    f.render_widget(Paragraph::new(line), area)
+}
+
+/// Shown until there's a GPU to show, e.g. while the only GPUs are on agents that haven't
+/// sent anything yet.
+pub fn render_waiting_for_gpus(f: &mut Frame, area: Rect, remotes: &[RemoteConnection]) {
+    let mut lines = Vec::new();
+    if remotes.is_empty() {
+        lines.push(Line::from("No GPUs found."));
+    } else {
+        lines.push(Line::from("Waiting for remote agents...".bold()));
+        for remote in remotes {
+            let status = match remote.status() {
+                ConnectionStatus::Connecting => "connecting".to_string(),
+                ConnectionStatus::Connected => "connected".to_string(),
+                ConnectionStatus::Disconnected(err) => err,
+            };
+            lines.push(Line::from(format!("{}: {}", remote.address(), status)));
+        }
+    }
+
+    let paragraph = Paragraph::new(lines).block(Block::default().borders(Borders::ALL));
+    f.render_widget(paragraph, area)
+}
+
+const CLUSTER_COLUMN_WIDTHS: [Constraint; 6] = [
+    Constraint::Min(16),
+    Constraint::Min(20),
+    Constraint::Length(6),
+    Constraint::Length(22),
+    Constraint::Length(6),
+    Constraint::Min(16),
+];
+
+#[derive(Default)]
+pub struct ClusterViewState {
+    table_state: TableState,
+    /// Where the GPU table was last rendered, to find what's clicked.
+    table_area: Rect,
+}
+
+impl ClusterViewState {
+    pub fn hit(&self, column: u16, row: u16) -> Option<TableHit> {
+        table_hit(
+            self.table_area,
+            &CLUSTER_COLUMN_WIDTHS,
+            0,
+            self.table_state.offset(),
+            column,
+            row,
+        )
+    }
+}
+
+/// Renders a line for every host's connection, followed by a table of every GPU across them.
+pub fn render_cluster_view(
+    f: &mut Frame,
+    area: Rect,
+    theme: &TuiTheme,
+    hosts: &[HostHealth],
+    rows: &[ClusterRow],
+    table: &ClusterTableData,
+    state: &mut ClusterViewState,
+) {
+    let [hosts_area, table_area] = Layout::vertical([
+        Constraint::Length(hosts.len() as u16 + 2),
+        Constraint::Min(0),
+    ])
+    .areas(area);
+
+    let host_lines = hosts
+        .iter()
+        .map(|host| {
+            let color = match host.status {
+                ConnectionStatus::Connected if host.is_stale() => theme.warning,
+                ConnectionStatus::Connected => theme.primary,
+                ConnectionStatus::Connecting => theme.warning,
+                ConnectionStatus::Disconnected(_) => theme.error,
+            };
+            let updated = host
+                .last_update
+                .map(|last_update| format!(", updated {}", format_elapsed(last_update)))
+                .unwrap_or_default();
+
+            Line::from(vec![
+                Span::styled(" ● ", Style::new().fg(color)),
+                Span::from(host.name.clone()).bold(),
+                Span::from(format!(
+                    " ({}) {} GPUs: {}{}",
+                    host.address.as_deref().unwrap_or("local"),
+                    host.gpu_count,
+                    host.describe(),
+                    updated
+                )),
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    let hosts_paragraph = Paragraph::new(host_lines).block(
+        Block::default()
+            .title(Title::default().content("Hosts".fg(theme.accent).bold()))
+            .borders(Borders::ALL),
+    );
+    f.render_widget(hosts_paragraph, hosts_area);
+
+    let header = ClusterColumn::ALL
+        .into_iter()
+        .map(|column| sorted_header(column.label(), column, table.sorting()))
+        .collect::<Row>()
+        .style(Style::default().fg(theme.accent).bold());
+
+    let table_rows = rows
+        .iter()
+        .map(|row| {
+            let host = if row.lost {
+                format!("{} lost", row.host)
+            } else if let Some(stale_for) = row.stale_for {
+                format!("{} stale {}", row.host, format_duration(stale_for))
+            } else {
+                row.host.clone()
+            };
+            let memory = match row.memory_used {
+                Some(used) => format!(
+                    "{} / {}",
+                    bytes_to_mib_gib(used as f32),
+                    bytes_to_mib_gib(row.memory_total as f32)
+                ),
+                None => "-".to_string(),
+            };
+            let top_user = match &row.top_user {
+                Some((user, memory)) => format!("{} ({})", user, bytes_to_mib_gib(*memory as f32)),
+                None => "-".to_string(),
+            };
+
+            // Old readings are dimmed so they aren't mistaken for current ones
+            let color = if row.stale_for.is_some() || row.lost {
+                theme.muted
+            } else {
+                theme.primary
+            };
+
+            [
+                host,
+                format!("{}: {}", row.device_index, row.device_name),
+                row.utilization
+                    .map(|utilization| format!("{:.0}%", utilization))
+                    .unwrap_or_else(|| "-".to_string()),
+                memory,
+                row.temperature
+                    .map(|temperature| format!("{:.0}°C", temperature))
+                    .unwrap_or_else(|| "-".to_string()),
+                top_user,
+            ]
+            .into_iter()
+            .map(Cell::from)
+            .collect::<Row>()
+            .style(Style::new().fg(color))
+        })
+        .collect::<Vec<_>>();
+
+    state.table_state.select(
+        rows.iter()
+            .position(|row| Some(row.gpu) == table.selected_gpu()),
+    );
+
+    let cluster_table = Table::new(table_rows, CLUSTER_COLUMN_WIDTHS)
+        .header(header)
+        .block(
+            Block::default()
+                .title(
+                    Title::default().content(
+                        "GPUs (s: sort, S: reverse, Enter: open)"
+                            .fg(theme.accent)
+                            .bold(),
+                    ),
+                )
+                .borders(Borders::ALL),
+        )
+        .highlight_style(
+            Style::default()
+                .add_modifier(Modifier::REVERSED)
+                .fg(theme.primary),
+        )
+        .highlight_spacing(HighlightSpacing::Always);
+
+    f.render_stateful_widget(cluster_table, table_area, &mut state.table_state);
+    state.table_area = table_area;
 }
@@ -1,3 +1,8 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
 pub fn bytes_to_mib_gib(bytes: f32) -> String {
     if bytes > 1024.0 * 1024.0 * 1024.0 {
         format!("{:.2}GiB", bytes / 1024.0 / 1024.0 / 1024.0)
@@ -7,3 +12,89 @@ pub fn bytes_to_mib_gib(bytes: f32) -> String {
         format!("{:.2}KiB", bytes / 1024.0)
     }
 }
+
+/// Quotes a field of a CSV or TSV row, separated by `separator`, if it contains anything
+/// that would otherwise break the row.
+pub fn quote_field(text: &str, separator: char) -> String {
+    if text.contains([separator, '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Picks a file name in the current directory for exported data, timestamped so that
+/// repeated exports don't overwrite each other.
+pub fn export_path(prefix: &str, extension: &str) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    PathBuf::from(format!("{}-{}.{}", prefix, timestamp, extension))
+}
+
+/// Formats a duration coarsely, e.g. "5s", "3m" or "2h".
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 60 * 60 {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}h", secs / 60 / 60)
+    }
+}
+
+/// Formats how long ago something happened, e.g. "5s ago" or "3m ago".
+pub fn format_elapsed(elapsed: Duration) -> String {
+    format!("{} ago", format_duration(elapsed))
+}
+
+/// Encodes bytes as standard base64, with padding.
+pub fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_encodes_the_rfc_4648_vectors() {
+        for (input, expected) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64_encode(input.as_bytes()), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn base64_encodes_every_bit() {
+        // The last characters of the alphabet, and bytes that aren't text
+        assert_eq!(base64_encode(&[0xfb, 0xff, 0xbf]), "+/+/");
+        assert_eq!(base64_encode(&[0xff, 0xff, 0xff, 0xff]), "/////w==");
+        assert_eq!(base64_encode(&[0, 0, 0, 0, 0]), "AAAAAAA=");
+        assert_eq!(base64_encode("GPU ✓".as_bytes()), "R1BVIOKckw==");
+    }
+}
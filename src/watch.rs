@@ -0,0 +1,576 @@
+//! `gpustat watch`, a compact plain-text summary of every GPU for when a full-screen
+//! interface is too much.
+
+use std::{
+    cmp::Reverse,
+    env,
+    io::{self, IsTerminal, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use crossterm::{
+    cursor::MoveToPreviousLine,
+    queue,
+    style::{Color, Stylize},
+    terminal::{self, Clear, ClearType},
+};
+use nvml_wrapper::enums::device::UsedGpuMemory;
+use sysinfo::System;
+
+use crate::{
+    config::Config,
+    data::{
+        cluster::{users_by_memory, STALE_AFTER},
+        process_filter::ProcessFilter,
+        GpuDeviceMonitor, GpuMonitoringData,
+    },
+    remote::client::ConnectionStatus,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, ValueEnum)]
+pub enum ColorChoice {
+    /// Colour the output when it's going to a terminal and `NO_COLOR` isn't set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+pub struct WatchOptions {
+    /// Also print a line for every process.
+    pub processes: bool,
+    /// Print each update after the last rather than redrawing it.
+    pub append: bool,
+    /// Print one update and exit.
+    pub once: bool,
+    pub interval: Duration,
+    pub color: ColorChoice,
+    /// Which processes to print lines for.
+    pub filter: ProcessFilter,
+}
+
+/// Adds colour to text, or leaves it plain when colour is off.
+struct Painter {
+    enabled: bool,
+}
+
+impl Painter {
+    fn paint(&self, text: &str, color: Color) -> String {
+        if self.enabled {
+            text.with(color).to_string()
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn bold(&self, text: &str) -> String {
+        if self.enabled {
+            text.bold().to_string()
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Green, yellow or red depending on how close a value is to its limit.
+fn level_color(value: f32, warn: f32, critical: f32) -> Color {
+    if value >= critical {
+        Color::Red
+    } else if value >= warn {
+        Color::Yellow
+    } else {
+        Color::Green
+    }
+}
+
+fn mib(bytes: u64) -> u64 {
+    bytes / 1024 / 1024
+}
+
+/// Cuts text down to `width` characters, ending it with "…" if anything was removed.
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let mut truncated = text
+        .chars()
+        .take(width.saturating_sub(1))
+        .collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+/// The width of the terminal, or of the `COLUMNS` variable when not writing to one.
+fn output_width() -> Option<usize> {
+    if io::stdout().is_terminal() {
+        if let Ok((columns, _)) = terminal::size() {
+            return Some(columns as usize);
+        }
+    }
+    env::var("COLUMNS").ok()?.parse().ok()
+}
+
+/// The width of a GPU line besides the id, name and utilization bar: the brackets around
+/// the id, the alert marker, the space before the name, the temperature, utilization and
+/// memory, and the separators between them.
+const GPU_LINE_FIXED_WIDTH: usize = 43;
+/// Room kept at the end of a GPU line for at least one user, and the ` …` when there are
+/// more, e.g. ` alice(1024M) …`.
+const MIN_USERS_WIDTH: usize = 15;
+/// GPU names aren't cut any shorter than this, even if the line then doesn't fit.
+const MIN_NAME_WIDTH: usize = 8;
+/// The utilization bar's width on outputs at least as wide as each step, widest first.
+/// Narrower outputs don't get a bar.
+const BAR_WIDTHS: [(usize, usize); 2] = [(110, 10), (90, 5)];
+
+/// How wide the pieces of a GPU line are, picked to fit the output.
+struct LineLayout {
+    id_width: usize,
+    name_width: usize,
+    bar_width: usize,
+    width: Option<usize>,
+}
+
+impl LineLayout {
+    fn new(gpus: &[GpuDeviceMonitor], width: Option<usize>) -> Self {
+        let id_width = gpus
+            .iter()
+            .map(|gpu| gpu.gpu_id().chars().count())
+            .max()
+            .unwrap_or(0);
+        let longest_name = gpus
+            .iter()
+            .map(|gpu| gpu.device_name().chars().count())
+            .max()
+            .unwrap_or(0);
+        Self::fit(id_width, longest_name, width)
+    }
+
+    /// Fits GPU lines to `width`, dropping the bar on narrow outputs and then cutting the
+    /// names short, so that there's still room for the user holding the most memory.
+    fn fit(id_width: usize, longest_name: usize, width: Option<usize>) -> Self {
+        let Some(width) = width else {
+            return Self {
+                id_width,
+                name_width: longest_name,
+                bar_width: BAR_WIDTHS[0].1,
+                width: None,
+            };
+        };
+
+        let bar_width = BAR_WIDTHS
+            .into_iter()
+            .find(|(min_width, _)| width >= *min_width)
+            .map_or(0, |(_, bar_width)| bar_width);
+        // The bar has a space before it
+        let bar_space = if bar_width > 0 { bar_width + 1 } else { 0 };
+        let fixed = id_width + GPU_LINE_FIXED_WIDTH + bar_space;
+        let name_width = longest_name.min(
+            width
+                .saturating_sub(fixed + MIN_USERS_WIDTH)
+                .max(MIN_NAME_WIDTH),
+        );
+
+        Self {
+            id_width,
+            name_width,
+            bar_width,
+            width: Some(width),
+        }
+    }
+}
+
+/// A line of output that keeps track of how wide it is without the colour codes.
+#[derive(Default)]
+struct Line {
+    text: String,
+    width: usize,
+}
+
+impl Line {
+    fn push(&mut self, plain: &str, colored: String) {
+        self.width += plain.chars().count();
+        self.text.push_str(&colored);
+    }
+
+    fn push_plain(&mut self, plain: &str) {
+        self.push(plain, plain.to_string());
+    }
+}
+
+fn gpu_line(gpu: &GpuDeviceMonitor, alerting: bool, layout: &LineLayout, p: &Painter) -> String {
+    let sample = gpu.last_sample();
+    let mut line = Line::default();
+
+    let id = format!("[{:>width$}]", gpu.gpu_id(), width = layout.id_width);
+    line.push(&id, p.paint(&id, Color::Cyan));
+    let marker = if alerting { "⚠" } else { " " };
+    line.push(marker, p.paint(marker, Color::Red));
+
+    let name = format!(
+        " {:<width$}",
+        truncate(gpu.device_name(), layout.name_width),
+        width = layout.name_width
+    );
+    line.push(&name, p.paint(&name, Color::Blue));
+    line.push_plain(" | ");
+
+    if sample.is_some_and(|sample| sample.lost) {
+        line.push("lost", p.paint("lost", Color::Red));
+        return line.text;
+    }
+
+    let temperature = sample.and_then(|sample| sample.temperature);
+    let text = match temperature {
+        Some(temperature) => format!("{:>3.0}°C", temperature),
+        None => "  ?°C".to_string(),
+    };
+    let color = level_color(temperature.unwrap_or(0.0), 70.0, 85.0);
+    line.push(&text, p.paint(&text, color));
+    line.push_plain(" | ");
+
+    let utilization = sample.and_then(|sample| sample.utilization);
+    let text = match utilization {
+        Some(utilization) => format!("{:>3.0} %", utilization),
+        None => "  ? %".to_string(),
+    };
+    line.push(&text, p.paint(&text, Color::Green));
+    if layout.bar_width > 0 {
+        let filled =
+            (utilization.unwrap_or(0.0) / 100.0 * layout.bar_width as f32).round() as usize;
+        let filled = filled.min(layout.bar_width);
+        let bar = format!(
+            " {}{}",
+            "█".repeat(filled),
+            "░".repeat(layout.bar_width - filled)
+        );
+        line.push(&bar, p.paint(&bar, Color::Green));
+    }
+    line.push_plain(" | ");
+
+    let memory_total = gpu.max_memory();
+    let memory_used = sample.and_then(|sample| sample.memory_used);
+    let used = match memory_used {
+        Some(used) => format!("{:>6}", mib(used)),
+        None => format!("{:>6}", "?"),
+    };
+    let percent = match memory_used {
+        Some(used) if memory_total > 0 => used as f32 / memory_total as f32 * 100.0,
+        _ => 0.0,
+    };
+    line.push(&used, p.paint(&used, level_color(percent, 75.0, 90.0)));
+    let total = format!(" / {:>6} MB", mib(memory_total));
+    line.push_plain(&total);
+
+    if let Some(age) = sample
+        .map(|sample| sample.time.elapsed())
+        .filter(|age| *age >= STALE_AFTER)
+    {
+        let text = format!(" | stale {}s", age.as_secs());
+        line.push(&text, p.paint(&text, Color::DarkGrey));
+        return line.text;
+    }
+
+    line.push_plain(" |");
+    let users = users_by_memory(gpu);
+    let count = users.len();
+    for (index, (user, memory)) in users.into_iter().enumerate() {
+        let text = format!(" {}({}M)", user, mib(memory));
+        // Unless it's the last user, there has to be room left for the " …" after it
+        let needed = if index + 1 < count { 2 } else { 0 };
+        if layout
+            .width
+            .is_some_and(|width| line.width + text.chars().count() + needed > width)
+        {
+            line.push_plain(" …");
+            break;
+        }
+        let colored = format!(
+            " {}({}M)",
+            p.paint(&user, Color::Grey),
+            p.paint(&mib(memory).to_string(), Color::Yellow)
+        );
+        line.push(&text, colored);
+    }
+
+    line.text
+}
+
+fn process_lines(
+    gpu: &GpuDeviceMonitor,
+    filter: &ProcessFilter,
+    width: Option<usize>,
+    p: &Painter,
+) -> Vec<String> {
+    let Ok(processes) = gpu.processes().all_processes() else {
+        return vec![p.paint("  └─ failed to fetch the process list", Color::Red)];
+    };
+
+    let mut processes = processes
+        .iter()
+        .filter(|process| filter.matches(process))
+        .collect::<Vec<_>>();
+    processes.sort_by_key(|process| match process.info.used_gpu_memory {
+        UsedGpuMemory::Used(bytes) => Reverse(bytes),
+        UsedGpuMemory::Unavailable => Reverse(0),
+    });
+
+    let count = processes.len();
+    processes
+        .into_iter()
+        .enumerate()
+        .map(|(index, process)| {
+            let branch = if index + 1 == count {
+                "└─"
+            } else {
+                "├─"
+            };
+            let memory = match process.info.used_gpu_memory {
+                UsedGpuMemory::Used(bytes) => format!("{}M", mib(bytes)),
+                UsedGpuMemory::Unavailable => "?".to_string(),
+            };
+            let user = format!(
+                "{:<10}",
+                truncate(process.user.as_deref().unwrap_or("unknown"), 10)
+            );
+            let memory = format!("{:>7}", memory);
+
+            let mut line = Line::default();
+            line.push_plain(&format!("  {} {:>7} ", branch, process.info.pid));
+            line.push(&user, p.paint(&user, Color::Grey));
+            line.push_plain(" ");
+            line.push(&memory, p.paint(&memory, Color::Yellow));
+            line.push_plain(&format!(" {:>4} ", process.gpu_usage_label()));
+
+            let command = if process.command.is_empty() {
+                &process.name
+            } else {
+                &process.command
+            };
+            match width {
+                Some(width) => {
+                    line.push_plain(&truncate(command, width.saturating_sub(line.width)))
+                }
+                None => line.push_plain(command),
+            }
+            line.text
+        })
+        .collect()
+}
+
+fn render(data: &mut GpuMonitoringData, options: &WatchOptions, p: &Painter) -> Vec<String> {
+    let width = output_width();
+    let host = System::host_name().unwrap_or_else(|| "localhost".to_string());
+
+    let mut lines = vec![p.bold(&host)];
+    if data.gpus().is_empty() {
+        lines.push("Waiting for GPUs...".to_string());
+        return lines;
+    }
+
+    let alerting = (0..data.gpus().len())
+        .map(|i| data.is_alerting(i))
+        .collect::<Vec<_>>();
+    let gpus = data.gpus();
+    let layout = LineLayout::new(gpus, width);
+
+    for (gpu, alerting) in gpus.iter().zip(alerting) {
+        lines.push(gpu_line(gpu, alerting, &layout, p));
+        if options.processes {
+            lines.extend(process_lines(gpu, &options.filter, width, p));
+        }
+    }
+    lines
+}
+
+/// Whether every agent has either sent its GPUs or failed to connect, so the first update
+/// isn't missing any.
+fn remotes_ready(data: &GpuMonitoringData) -> bool {
+    data.remotes().iter().all(|remote| match remote.status() {
+        ConnectionStatus::Connecting => false,
+        ConnectionStatus::Connected => remote.last_received().is_some(),
+        ConnectionStatus::Disconnected(_) => true,
+    })
+}
+
+/// Prints a one-line-per-GPU summary, refreshing it every interval until interrupted.
+pub fn run_watch(config: Config, options: WatchOptions) -> io::Result<()> {
+    let config = config.without_alert_delivery();
+    let mut data = GpuMonitoringData::new(&config);
+
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let painter = Painter {
+        enabled: match options.color {
+            ColorChoice::Auto => !no_color && io::stdout().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        },
+    };
+    // Redrawing in place only makes sense on a terminal
+    let append = options.append || !io::stdout().is_terminal();
+
+    let start = Instant::now();
+    while !remotes_ready(&data) && start.elapsed() < Duration::from_secs(5) {
+        data.update();
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    let mut stdout = io::stdout().lock();
+    let mut printed_lines = 0;
+    loop {
+        data.update();
+        let lines = render(&mut data, &options, &painter);
+
+        if !append && printed_lines > 0 {
+            queue!(
+                stdout,
+                MoveToPreviousLine(printed_lines as u16),
+                Clear(ClearType::FromCursorDown)
+            )?;
+        }
+        for line in &lines {
+            writeln!(stdout, "{}", line)?;
+        }
+        if append && !options.once {
+            writeln!(stdout)?;
+        }
+        stdout.flush()?;
+        printed_lines = lines.len();
+
+        if options.once {
+            return Ok(());
+        }
+
+        // Keep sampling between updates so that the graphs and process history stay smooth
+        let next = Instant::now() + options.interval;
+        while Instant::now() < next {
+            data.update();
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nvml_wrapper::struct_wrappers::device::ProcessInfo;
+
+    use crate::{
+        data::events::EventLog,
+        processes::{ProcessData, ProcessKind},
+        remote::protocol::DeviceSnapshot,
+    };
+
+    use super::*;
+
+    #[test]
+    fn truncates_to_a_number_of_characters() {
+        assert_eq!(truncate("python", 6), "python");
+        assert_eq!(truncate("python", 10), "python");
+        assert_eq!(truncate("python3", 6), "pytho…");
+        assert_eq!(truncate("Grafikkarte über", 10), "Grafikkar…");
+        assert_eq!(truncate("äöü", 2), "ä…");
+        assert_eq!(truncate("python", 1), "…");
+        assert_eq!(truncate("python", 0), "");
+    }
+
+    #[test]
+    fn narrower_outputs_drop_the_bar_then_cut_the_names() {
+        let layout = LineLayout::fit(4, 30, None);
+        assert_eq!((layout.name_width, layout.bar_width), (30, 10));
+
+        let layout = LineLayout::fit(4, 30, Some(120));
+        assert_eq!((layout.name_width, layout.bar_width), (30, 10));
+        let layout = LineLayout::fit(4, 30, Some(100));
+        assert_eq!((layout.name_width, layout.bar_width), (30, 5));
+        let layout = LineLayout::fit(4, 30, Some(80));
+        assert_eq!((layout.name_width, layout.bar_width), (18, 0));
+        let layout = LineLayout::fit(4, 30, Some(20));
+        assert_eq!((layout.name_width, layout.bar_width), (MIN_NAME_WIDTH, 0));
+    }
+
+    fn remote_gpu(name: &str) -> GpuDeviceMonitor {
+        let process = |pid, user: &str, memory_mib: u64| ProcessData {
+            info: ProcessInfo {
+                pid,
+                used_gpu_memory: UsedGpuMemory::Used(memory_mib * 1024 * 1024),
+                gpu_instance_id: None,
+                compute_instance_id: None,
+            },
+            name: "python".to_string(),
+            command: String::new(),
+            start_time: None,
+            user: Some(user.to_string()),
+            is_current_user: false,
+            kind: ProcessKind::Compute,
+            gpu_usage: Some(50),
+            idle_for: None,
+        };
+        let snapshot = DeviceSnapshot {
+            index: 0,
+            uuid: "GPU-0".to_string(),
+            name: name.to_string(),
+            memory_total: 24 * 1024 * 1024 * 1024,
+            utilization: Some(50.0),
+            memory_used: Some(3 * 1024 * 1024 * 1024),
+            temperature: Some(60.0),
+            lost: false,
+            info: None,
+            throttle_reasons: None,
+            health_events: Vec::new(),
+            ecc: None,
+            processes: Ok(vec![
+                process(1, "alice", 2048),
+                process(2, "bob", 1024),
+                process(3, "carol", 512),
+            ]),
+        };
+
+        let mut gpu = GpuDeviceMonitor::from_snapshot(0, "box", &snapshot, Duration::MAX);
+        gpu.apply_snapshot(snapshot, &mut EventLog::new());
+        gpu
+    }
+
+    #[test]
+    fn gpu_lines_fit_the_output() {
+        let gpus = [remote_gpu("NVIDIA GeForce RTX 4090 Laptop GPU")];
+        let painter = Painter { enabled: false };
+
+        let layout = LineLayout::new(&gpus, None);
+        let line = gpu_line(&gpus[0], false, &layout, &painter);
+        assert!(
+            line.ends_with(" | alice(2048M) bob(1024M) carol(512M)"),
+            "{}",
+            line
+        );
+
+        for width in [140, 120, 100, 80] {
+            let layout = LineLayout::new(&gpus, Some(width));
+            let line = gpu_line(&gpus[0], false, &layout, &painter);
+            assert!(
+                line.chars().count() <= width,
+                "{:?} is wider than {}",
+                line,
+                width
+            );
+
+            // There's always room for the top user
+            let (gpu, users) = line.split_once(" alice(2048M)").unwrap();
+            let bar_space = match layout.bar_width {
+                0 => 0,
+                bar_width => bar_width + 1,
+            };
+            assert_eq!(
+                gpu.chars().count(),
+                layout.id_width + layout.name_width + GPU_LINE_FIXED_WIDTH + bar_space,
+                "{:?}",
+                line
+            );
+            assert!(users.is_empty() || users.starts_with(" bob(1024M)") || users == " …");
+        }
+    }
+}
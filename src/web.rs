@@ -0,0 +1,366 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    mem,
+    net::{TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, SyncSender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{config::Config, data::GpuMonitoringData, remote::protocol::DeviceSnapshot};
+
+const INDEX_HTML: &str = include_str!("../assets/web/index.html");
+const APP_JS: &str = include_str!("../assets/web/app.js");
+const STYLE_CSS: &str = include_str!("../assets/web/style.css");
+const ICON: &[u8] = include_bytes!("../assets/gpustat_icon_32px.png");
+
+/// How many samples of history a page gets when it connects, about five minutes' worth.
+const HISTORY_SAMPLES: usize = 600;
+
+/// The most a request's line and headers can take up together. Only small GET requests are
+/// expected, and anyone who can reach the port can send one.
+const MAX_REQUEST_LEN: u64 = 16 * 1024;
+/// The most headers a request can have.
+const MAX_HEADERS: usize = 100;
+
+#[derive(Serialize)]
+struct GpuHistory {
+    id: usize,
+    label: String,
+    memory_total: u64,
+    utilization: Vec<Option<f32>>,
+    memory_used: Vec<Option<f32>>,
+    temperature: Vec<Option<f32>>,
+}
+
+#[derive(Serialize)]
+struct History {
+    gpus: Vec<GpuHistory>,
+}
+
+#[derive(Serialize)]
+struct GpuUpdate {
+    id: usize,
+    label: String,
+    /// The alerts firing for the GPU, as "rule: message".
+    alerts: Vec<String>,
+    #[serde(flatten)]
+    snapshot: DeviceSnapshot,
+}
+
+#[derive(Serialize)]
+struct Update {
+    gpus: Vec<GpuUpdate>,
+}
+
+/// The queues of events waiting to be written to each page's event stream.
+type Streams = Arc<Mutex<Vec<SyncSender<Arc<str>>>>>;
+
+/// How many events can be waiting for a page before it's dropped for falling behind.
+const MAX_QUEUED_EVENTS: usize = 8;
+
+/// Serves a web page showing the same graphs and process table as the GUI, kept live
+/// over server-sent events.
+pub fn run_http_server(config: Config, listen: &str) -> io::Result<()> {
+    let config = Config {
+        desktop_notifications: false,
+        ..config
+    };
+    let mut data = GpuMonitoringData::new(&config);
+
+    let listener = TcpListener::bind(listen)?;
+    eprintln!("Serving the web UI on http://{}", listener.local_addr()?);
+
+    // Event streams are handed over to the sampling loop, which queues the history so far
+    // for each one before adding it to the live streams. Each stream is written to on its
+    // own thread, so a page that stops reading can't hold up sampling or the other pages
+    let new_streams = Streams::default();
+    let accept_streams = new_streams.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let new_streams = accept_streams.clone();
+            thread::spawn(move || {
+                if let Err(err) = handle_request(stream, &new_streams) {
+                    eprintln!("Failed to handle request: {}", err);
+                }
+            });
+        }
+    });
+
+    let mut streams = Vec::new();
+    let mut last_update: Option<Instant> = None;
+
+    loop {
+        data.update();
+
+        let mut connected = mem::take(&mut *new_streams.lock().unwrap());
+        if !connected.is_empty() {
+            send_event(&mut connected, sse_event("history", &history(&mut data)));
+            streams.append(&mut connected);
+        }
+
+        if last_update.is_none_or(|time| time.elapsed() >= Duration::from_millis(500)) {
+            last_update = Some(Instant::now());
+            send_event(&mut streams, sse_event("update", &update(&mut data)));
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+fn history(data: &mut GpuMonitoringData) -> History {
+    let gpus = data
+        .gpus()
+        .iter_mut()
+        .enumerate()
+        .map(|(id, gpu)| GpuHistory {
+            id,
+            label: gpu.label(),
+            memory_total: gpu.max_memory(),
+            utilization: gpu.usage_graph_mut().recent(HISTORY_SAMPLES),
+            memory_used: gpu.memory_graph_mut().recent(HISTORY_SAMPLES),
+            temperature: gpu.temperature_graph_mut().recent(HISTORY_SAMPLES),
+        })
+        .collect();
+
+    History { gpus }
+}
+
+fn update(data: &mut GpuMonitoringData) -> Update {
+    let alerts = data.alerts().firing().cloned().collect::<Vec<_>>();
+
+    let gpus = data
+        .gpus()
+        .iter()
+        .enumerate()
+        .filter_map(|(id, gpu)| {
+            Some(GpuUpdate {
+                id,
+                label: gpu.label(),
+                alerts: alerts
+                    .iter()
+                    .filter(|alert| alert.device_uuid == gpu.device_uuid())
+                    .map(|alert| format!("{}: {}", alert.rule, alert.message))
+                    .collect(),
+                snapshot: gpu.snapshot()?,
+            })
+        })
+        .collect();
+
+    Update { gpus }
+}
+
+/// Queues the event for every stream, dropping any that have stopped or fallen behind.
+fn send_event(streams: &mut Vec<SyncSender<Arc<str>>>, event: String) {
+    let event: Arc<str> = event.into();
+    streams.retain(|stream| stream.try_send(event.clone()).is_ok());
+}
+
+/// Starts writing queued events to the stream on a thread of its own, returning its queue.
+/// The thread stops once the queue is dropped or a write fails.
+fn open_event_stream(stream: TcpStream) -> SyncSender<Arc<str>> {
+    let (events, queued) = mpsc::sync_channel::<Arc<str>>(MAX_QUEUED_EVENTS);
+    thread::spawn(move || {
+        let mut stream = stream;
+        for event in queued {
+            if stream.write_all(event.as_bytes()).is_err() {
+                return;
+            }
+        }
+    });
+    events
+}
+
+fn sse_event(name: &str, data: &impl Serialize) -> String {
+    format!(
+        "event: {}\ndata: {}\n\n",
+        name,
+        serde_json::to_string(data).unwrap()
+    )
+}
+
+fn handle_request(stream: TcpStream, new_streams: &Streams) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    // A page that stops reading gets dropped rather than stalling the others
+    stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut reader = BufReader::new((&stream).take(MAX_REQUEST_LEN));
+    let Some(request_line) = read_request_line(&mut reader)? else {
+        return respond(
+            &mut &stream,
+            "431 Request Header Fields Too Large",
+            "text/plain",
+            b"",
+        );
+    };
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+
+    let mut stream = &stream;
+    if method != "GET" {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", b"");
+    }
+
+    match path {
+        "/" | "/index.html" => respond(
+            &mut stream,
+            "200 OK",
+            "text/html; charset=utf-8",
+            INDEX_HTML.as_bytes(),
+        ),
+        "/app.js" => respond(
+            &mut stream,
+            "200 OK",
+            "text/javascript; charset=utf-8",
+            APP_JS.as_bytes(),
+        ),
+        "/style.css" => respond(
+            &mut stream,
+            "200 OK",
+            "text/css; charset=utf-8",
+            STYLE_CSS.as_bytes(),
+        ),
+        "/icon.png" => respond(&mut stream, "200 OK", "image/png", ICON),
+        "/events" => {
+            stream.write_all(
+                b"HTTP/1.1 200 OK\r\n\
+                  Content-Type: text/event-stream\r\n\
+                  Cache-Control: no-cache\r\n\
+                  Connection: keep-alive\r\n\r\n",
+            )?;
+            let events = open_event_stream(stream.try_clone()?);
+            new_streams.lock().unwrap().push(events);
+            Ok(())
+        }
+        _ => respond(&mut stream, "404 Not Found", "text/plain", b"Not found"),
+    }
+}
+
+/// Reads the request line, then the headers, which aren't needed but have to be read before
+/// responding. Returns `None` if the request is too long or has too many headers.
+fn read_request_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    for _ in 0..=MAX_HEADERS {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            // Either the client stopped sending, or the length limit was hit partway
+            return Ok(request_line.ends_with('\n').then_some(request_line));
+        }
+        if !header.ends_with('\n') {
+            return Ok(None);
+        }
+        if header.trim().is_empty() {
+            return Ok(Some(request_line));
+        }
+    }
+    Ok(None)
+}
+
+fn respond(
+    stream: &mut impl Write,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    /// Opens an event stream to a new connection, returning the page's end of it.
+    fn connect() -> (SyncSender<Arc<str>>, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let page = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        stream
+            .set_write_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        (open_event_stream(stream), page)
+    }
+
+    #[test]
+    fn a_stalled_page_does_not_hold_up_the_others() {
+        let (stalled, _stalled_page) = connect();
+        let (events, page) = connect();
+        let mut streams = vec![stalled, events];
+
+        // Keep reading from the second page, while the first never reads
+        let reader = thread::spawn(move || {
+            BufReader::new(page)
+                .lines()
+                .take_while(|line| line.is_ok())
+                .filter(|line| line.as_ref().unwrap().starts_with("event: update"))
+                .count()
+        });
+
+        // Big enough to fill the stalled page's socket buffers
+        let data = "x".repeat(1 << 20);
+        for _ in 0..50 {
+            // Blocking on the stalled page would take as long as the 5s write timeout
+            let started = Instant::now();
+            send_event(&mut streams, sse_event("update", &data));
+            assert!(started.elapsed() < Duration::from_secs(1));
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(streams.len(), 1);
+
+        streams.clear();
+        assert_eq!(reader.join().unwrap(), 50);
+    }
+
+    fn read(request: &[u8]) -> Option<String> {
+        let mut reader = BufReader::new(request.take(MAX_REQUEST_LEN));
+        read_request_line(&mut reader).unwrap()
+    }
+
+    #[test]
+    fn reads_the_request_line_and_skips_headers() {
+        let request = b"GET /events HTTP/1.1\r\nHost: localhost\r\nAccept: */*\r\n\r\n";
+        assert_eq!(read(request).as_deref(), Some("GET /events HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn rejects_a_request_line_without_an_end() {
+        let request = vec![b'a'; MAX_REQUEST_LEN as usize * 2];
+        assert_eq!(read(&request), None);
+    }
+
+    #[test]
+    fn rejects_headers_without_an_end() {
+        let mut request = b"GET / HTTP/1.1\r\nX-Padding: ".to_vec();
+        request.resize(MAX_REQUEST_LEN as usize * 2, b'a');
+        assert_eq!(read(&request), None);
+    }
+
+    #[test]
+    fn rejects_too_many_headers() {
+        let mut request = b"GET / HTTP/1.1\r\n".to_vec();
+        for _ in 0..=MAX_HEADERS {
+            request.extend_from_slice(b"A: b\r\n");
+        }
+        request.extend_from_slice(b"\r\n");
+        assert_eq!(read(&request), None);
+    }
+}